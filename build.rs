@@ -0,0 +1,21 @@
+// With the ffi feature, regenerates the C header from src/ffi.rs so it can't drift from the
+// library it declares.
+
+fn main() {
+    #[cfg(feature = "ffi")]
+    write_header();
+}
+
+#[cfg(feature = "ffi")]
+fn write_header() {
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let config = cbindgen::Config::from_root_or_default(&crate_dir);
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(format!("{}/src/ffi.rs", crate_dir))
+        .generate()
+        .expect("src/ffi.rs is valid for cbindgen")
+        .write_to_file(format!("{}/include/mahjong_simulator.h", crate_dir));
+}
@@ -0,0 +1,149 @@
+/* Hand evaluator and scorer of mahjong-simulator.
+ *
+ * Hands are MPSZ notation: the closed tiles, the tile won on last, and each called meld as
+ * its own string. Every function returns an MjStatus and writes its result through the
+ * last pointer only on MJ_STATUS_OK. Tile kinds are numbered 0 to 8 for 1m to 9m, 9 to 17
+ * for the pinzu, 18 to 26 for the souzu and 27 to 33 for the winds East to North and the
+ * dragons haku, hatsu and chun. */
+
+#ifndef MAHJONG_SIMULATOR_H
+#define MAHJONG_SIMULATOR_H
+
+/* Generated by cbindgen from src/ffi.rs on every build with the ffi feature; don't edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Raised whenever a function or struct of this interface changes incompatibly.
+#define MJ_ABI_VERSION 1
+
+// Most yaku a score lists.
+#define MJ_MAX_YAKU 16
+
+// Bytes a yaku name takes, its terminating NUL included.
+#define MJ_YAKU_NAME_SIZE 32
+
+// Whether a call worked, and why not.
+typedef enum MjStatus {
+  MJ_STATUS_OK = 0,
+  // A pointer that may not be NULL was
+  MJ_STATUS_NULL_ARGUMENT = 1,
+  // A string isn't UTF-8, MPSZ notation or a meld
+  MJ_STATUS_INVALID_NOTATION = 2,
+  // The hand holds the wrong number of tiles for the call
+  MJ_STATUS_WRONG_TILE_COUNT = 3,
+  // The hand to score isn't complete
+  MJ_STATUS_NOT_COMPLETE = 4,
+  // A seat wind isn't 0 to 3
+  MJ_STATUS_INVALID_SEAT_WIND = 5,
+  // A bug on our side; the call had no effect
+  MJ_STATUS_INTERNAL = 6,
+} MjStatus;
+
+// The scoring rules that differ between tables.
+typedef struct MjRuleset {
+  // Red fives, written 0m, 0p and 0s, are one dora each
+  bool red_fives;
+  // 13 han or more is a yakuman rather than a sanbaiman
+  bool kazoe_yakuman;
+} MjRuleset;
+
+// How a hand was won.
+typedef struct MjWinContext {
+  // Won on a self-drawn tile rather than a discard
+  bool tsumo;
+  // 0 for East, which deals, to 3 for North
+  uint8_t seat_wind;
+  // Dora indicators in MPSZ notation, or NULL when none count
+  const char *dora_indicators;
+  // Counters on the table, each worth 300 points to the winner
+  uint32_t honba;
+  // Riichi deposits the winner collects, 1000 points each
+  uint32_t riichi_sticks;
+} MjWinContext;
+
+// A yaku, or dora, and its han.
+typedef struct MjYaku {
+  // NUL-terminated
+  char name[MJ_YAKU_NAME_SIZE];
+  int32_t han;
+} MjYaku;
+
+// What the winner is paid, honba and riichi sticks included.
+typedef struct MjPayments {
+  // Paid by the discarder on a ron
+  int32_t ron;
+  // Paid by the dealer on someone else's tsumo
+  int32_t tsumo_dealer;
+  // Paid by each other player on a tsumo
+  int32_t tsumo_non_dealer;
+  // Everything the winner collects
+  int32_t total;
+} MjPayments;
+
+// The score of a winning hand.
+typedef struct MjScore {
+  int32_t han;
+  int32_t fu;
+  int32_t base_points;
+  // Entries of `yaku` in use
+  uint32_t yaku_count;
+  struct MjYaku yaku[MJ_MAX_YAKU];
+  struct MjPayments payments;
+} MjScore;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The version of this interface the library implements, `MJ_ABI_VERSION` when it matches
+// the header.
+uint32_t mj_abi_version(void);
+
+// A static, NUL-terminated description of a status.
+const char *mj_status_message(enum MjStatus status);
+
+// The rules the simulator plays by: red fives count and 13 han is a yakuman.
+struct MjRuleset mj_ruleset_default(void);
+
+// Tiles away from tenpai of a hand of 13 or 14 tiles: 0 is tenpai and -1 complete.
+//
+// # Safety
+// `closed` is a NUL-terminated string, `melds` points to `meld_count` of them (or may be
+// NULL when `meld_count` is 0), and `shanten_out` is valid for a write.
+enum MjStatus mj_shanten(const char *closed,
+                         const char *const *melds,
+                         size_t meld_count,
+                         int8_t *shanten_out);
+
+// The tile kinds completing a hand of 13 tiles, bit `i` for kind `i`; 0 when it isn't
+// tenpai.
+//
+// # Safety
+// As for `mj_shanten`, with `waits_out` valid for a write.
+enum MjStatus mj_waits(const char *closed,
+                       const char *const *melds,
+                       size_t meld_count,
+                       uint64_t *waits_out);
+
+// Scores a complete hand of 14 tiles, the last closed tile being the one won on. Yaku past
+// `MJ_MAX_YAKU` are left out of the list but not the han.
+//
+// # Safety
+// As for `mj_shanten`; `context` and `ruleset` point to valid structs, the indicators in
+// `context` are NULL or a NUL-terminated string, and `score_out` is valid for a write.
+enum MjStatus mj_score_hand(const char *closed,
+                            const char *const *melds,
+                            size_t meld_count,
+                            const struct MjWinContext *context,
+                            const struct MjRuleset *ruleset,
+                            struct MjScore *score_out);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* MAHJONG_SIMULATOR_H */
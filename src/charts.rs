@@ -0,0 +1,479 @@
+use crate::output::ChartFormat;
+use crate::run::{ScoreHistory, SCORE_BUCKET};
+use crate::stats::StrategyStats;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Charts of one run, drawn as PNG or SVG next to each other in a directory: the final score
+// distribution and the cumulative point difference of every seat of the line-up, and
+// placement rates and per-strategy statistics from the summary.
+
+type ChartResult = Result<(), Box<dyn Error>>;
+
+impl ChartFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ChartFormat::Png => "png",
+            ChartFormat::Svg => "svg",
+        }
+    }
+}
+
+const SIZE: (u32, u32) = (1000, 700);
+
+/// Matplotlib's default colours, so the charts look like the ones we used to make
+const COLORS: [RGBColor; 10] = [
+    RGBColor(0x1f, 0x77, 0xb4),
+    RGBColor(0xff, 0x7f, 0x0e),
+    RGBColor(0x2c, 0xa0, 0x2c),
+    RGBColor(0xd6, 0x27, 0x28),
+    RGBColor(0x94, 0x67, 0xbd),
+    RGBColor(0x8c, 0x56, 0x4b),
+    RGBColor(0xe3, 0x77, 0xc2),
+    RGBColor(0x7f, 0x7f, 0x7f),
+    RGBColor(0xbc, 0xbd, 0x22),
+    RGBColor(0x17, 0xbe, 0xcf),
+];
+
+fn color(index: usize) -> RGBColor {
+    COLORS[index % COLORS.len()]
+}
+
+/// Everything the charts of one run are drawn from.
+pub struct ChartData<'a> {
+    /// Name of every line-up seat
+    pub seats: Vec<String>,
+    /// Final scores of the games in line-up order
+    pub history: &'a ScoreHistory,
+    pub starting_points: i32,
+    pub strategies: &'a BTreeMap<String, StrategyStats>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Chart {
+    Distribution,
+    Cumulative,
+    Placements,
+    Rates,
+    Shanten,
+}
+
+impl Chart {
+    const ALL: [Chart; 5] = [
+        Chart::Distribution,
+        Chart::Cumulative,
+        Chart::Placements,
+        Chart::Rates,
+        Chart::Shanten,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Chart::Distribution => "distribution",
+            Chart::Cumulative => "cumulative",
+            Chart::Placements => "placements",
+            Chart::Rates => "rates",
+            Chart::Shanten => "shanten",
+        }
+    }
+}
+
+/// Draws every chart into `directory` as `<stem>-<chart>.<format>` and returns the files.
+pub fn write_charts(
+    directory: &Path,
+    stem: &str,
+    format: ChartFormat,
+    data: &ChartData,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    fs::create_dir_all(directory)?;
+    let mut written = Vec::new();
+    for chart in Chart::ALL {
+        let path = directory.join(format!("{}-{}.{}", stem, chart.name(), format.extension()));
+        match format {
+            ChartFormat::Png => draw(
+                BitMapBackend::new(&path, SIZE).into_drawing_area(),
+                chart,
+                data,
+            )?,
+            ChartFormat::Svg => draw(
+                SVGBackend::new(&path, SIZE).into_drawing_area(),
+                chart,
+                data,
+            )?,
+        }
+        written.push(path);
+    }
+    Ok(written)
+}
+
+fn draw<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    chart: Chart,
+    data: &ChartData,
+) -> ChartResult
+where
+    DB::ErrorType: 'static,
+{
+    root.fill(&WHITE)?;
+    match chart {
+        Chart::Distribution => score_distribution(&root, data)?,
+        Chart::Cumulative => cumulative_difference(&root, data)?,
+        Chart::Placements => placements(&root, data)?,
+        Chart::Rates => rates(&root, data)?,
+        Chart::Shanten => shanten_by_turn(&root, data)?,
+    }
+    root.present()?;
+    Ok(())
+}
+
+/// Bins for a histogram of `count` values, more of them the more games were played.
+fn bin_count(count: usize) -> usize {
+    ((count as f64).sqrt() as usize).clamp(10, 150)
+}
+
+fn padded_range(values: impl Iterator<Item = f64> + Clone) -> (f64, f64) {
+    let low = values.clone().fold(f64::INFINITY, f64::min);
+    let high = values.fold(f64::NEG_INFINITY, f64::max);
+    if !low.is_finite() || !high.is_finite() {
+        return (0.0, 1.0);
+    }
+    let padding = ((high - low) * 0.05).max(1.0);
+    (low - padding, high + padding)
+}
+
+/// Density of every seat's final score, overlaid.
+fn score_distribution<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    data: &ChartData,
+) -> ChartResult
+where
+    DB::ErrorType: 'static,
+{
+    // Join the history's buckets into as many bins as the games call for
+    let buckets = &data.history.buckets;
+    let first = buckets.keys().next().copied().unwrap_or(0);
+    let last = buckets.keys().next_back().copied().unwrap_or(0);
+    let span = ((last - first) / SCORE_BUCKET + 1) as usize;
+    let per_bin = span.div_ceil(bin_count(data.history.games));
+    let bins = span.div_ceil(per_bin);
+    let width = (per_bin as i32 * SCORE_BUCKET) as f64;
+    let (low, high) = (first as f64, first as f64 + bins as f64 * width);
+    let games = data.history.games.max(1) as f64;
+    let densities: Vec<Vec<f64>> = (0..4)
+        .map(|seat| {
+            let mut counts = vec![0.0; bins];
+            for (bucket, seats) in buckets {
+                let bin = ((bucket - first) / SCORE_BUCKET) as usize / per_bin;
+                counts[bin] += seats[seat] as f64;
+            }
+            counts.iter().map(|count| count / games / width).collect()
+        })
+        .collect();
+    let top = densities
+        .iter()
+        .flatten()
+        .fold(0.0, |top: f64, &density| top.max(density));
+
+    let mut chart = ChartBuilder::on(root)
+        .caption("Points at the end of a game", ("sans-serif", 28))
+        .margin(20)
+        .x_label_area_size(45)
+        .y_label_area_size(80)
+        .build_cartesian_2d(low..high, 0.0..top * 1.05)?;
+    chart
+        .configure_mesh()
+        .x_desc("Points")
+        .x_label_formatter(&|points| format!("{:.0}", points))
+        .y_desc("Density")
+        .y_label_formatter(&|density| format!("{:.1e}", density))
+        .draw()?;
+    for (seat, seat_densities) in densities.iter().enumerate() {
+        let fill = color(seat).mix(0.5).filled();
+        chart
+            .draw_series(seat_densities.iter().enumerate().map(|(bin, &density)| {
+                let left = low + bin as f64 * width;
+                Rectangle::new([(left, 0.0), (left + width, density)], fill)
+            }))?
+            .label(format!("Player {} ({})", seat + 1, data.seats[seat]))
+            .legend(move |(x, y)| Rectangle::new([(x, y - 5), (x + 15, y + 5)], fill));
+    }
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+    Ok(())
+}
+
+/// Running total of every seat's points above or below the starting points.
+fn cumulative_difference<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    data: &ChartData,
+) -> ChartResult
+where
+    DB::ErrorType: 'static,
+{
+    let history = data.history;
+    let mut samples = history.samples.clone();
+    if samples
+        .last()
+        .is_none_or(|(games, _)| *games < history.games)
+    {
+        samples.push((history.games, history.totals));
+    }
+    let totals: Vec<Vec<(f64, f64)>> = (0..4)
+        .map(|seat| {
+            samples
+                .iter()
+                .map(|(games, totals)| {
+                    let difference = totals[seat] - *games as i64 * data.starting_points as i64;
+                    (*games as f64, difference as f64)
+                })
+                .collect()
+        })
+        .collect();
+    let (low, high) = padded_range(
+        totals
+            .iter()
+            .flatten()
+            .map(|(_, difference)| *difference)
+            .chain([0.0]),
+    );
+
+    let mut chart = ChartBuilder::on(root)
+        .caption("Cumulative point difference", ("sans-serif", 28))
+        .margin(20)
+        .x_label_area_size(45)
+        .y_label_area_size(90)
+        .build_cartesian_2d(0.0..history.games.max(1) as f64, low..high)?;
+    chart
+        .configure_mesh()
+        .x_desc("Games")
+        .x_label_formatter(&|games| format!("{:.0}", games))
+        .y_desc("Points")
+        .y_label_formatter(&|points| format!("{:.0}", points))
+        .draw()?;
+    for (seat, seat_totals) in totals.iter().enumerate() {
+        let line = color(seat);
+        chart
+            .draw_series(LineSeries::new(
+                seat_totals.iter().copied(),
+                line.stroke_width(2),
+            ))?
+            .label(format!("Player {} ({})", seat + 1, data.seats[seat]))
+            .legend(move |(x, y)| {
+                PathElement::new(vec![(x, y), (x + 20, y)], line.stroke_width(2))
+            });
+    }
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperLeft)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+    Ok(())
+}
+
+/// Bars in groups, one group per strategy and one bar per value in `values`.
+fn grouped_bars<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    caption: &str,
+    y_desc: &str,
+    series: &[&str],
+    values: &[(String, Vec<f64>)],
+) -> ChartResult
+where
+    DB::ErrorType: 'static,
+{
+    let top = values
+        .iter()
+        .flat_map(|(_, values)| values)
+        .fold(0.0, |top: f64, &value| top.max(value));
+    let groups = values.len().max(1);
+    let mut chart = ChartBuilder::on(root)
+        .caption(caption, ("sans-serif", 28))
+        .margin(20)
+        .x_label_area_size(45)
+        .y_label_area_size(70)
+        // Headroom for the legend above the bars
+        .build_cartesian_2d(-0.5..groups as f64 - 0.5, 0.0..(top * 1.35).max(1e-9))?;
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels(groups)
+        .x_label_formatter(&|x| {
+            let index = x.round();
+            match values.get(index as usize) {
+                Some((label, _)) if (x - index).abs() < 1e-6 && index >= 0.0 => label.clone(),
+                _ => String::new(),
+            }
+        })
+        .y_desc(y_desc)
+        .y_label_formatter(&|value| format!("{:.0}", value))
+        .draw()?;
+    let width = 0.8 / series.len() as f64;
+    for (index, name) in series.iter().enumerate() {
+        let fill = color(index).filled();
+        chart
+            .draw_series(values.iter().enumerate().map(|(group, (_, values))| {
+                let left = group as f64 - 0.4 + index as f64 * width;
+                Rectangle::new([(left, 0.0), (left + width, values[index])], fill)
+            }))?
+            .label(*name)
+            .legend(move |(x, y)| Rectangle::new([(x, y - 5), (x + 15, y + 5)], fill));
+    }
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperRight)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+    Ok(())
+}
+
+/// Share of games each strategy finished in each place.
+fn placements<DB: DrawingBackend>(root: &DrawingArea<DB, Shift>, data: &ChartData) -> ChartResult
+where
+    DB::ErrorType: 'static,
+{
+    let values: Vec<(String, Vec<f64>)> = data
+        .strategies
+        .iter()
+        .map(|(label, strategy)| {
+            let games = strategy.games.max(1) as f64;
+            let rates = strategy
+                .placements
+                .iter()
+                .map(|&count| 100.0 * count as f64 / games)
+                .collect();
+            (label.clone(), rates)
+        })
+        .collect();
+    grouped_bars(
+        root,
+        "Placements",
+        "% of games",
+        &["1st", "2nd", "3rd", "4th"],
+        &values,
+    )
+}
+
+/// Win, deal-in, riichi, call and tenpai rates of each strategy.
+fn rates<DB: DrawingBackend>(root: &DrawingArea<DB, Shift>, data: &ChartData) -> ChartResult
+where
+    DB::ErrorType: 'static,
+{
+    let values: Vec<(String, Vec<f64>)> = data
+        .strategies
+        .iter()
+        .map(|(label, strategy)| {
+            let rates = [
+                strategy.win_rate(),
+                strategy.deal_in_rate(),
+                strategy.riichi_rate(),
+                strategy.call_rate(),
+                strategy.tenpai_rate(),
+            ];
+            (
+                label.clone(),
+                rates.iter().map(|rate| 100.0 * rate).collect(),
+            )
+        })
+        .collect();
+    grouped_bars(
+        root,
+        "Strategy statistics",
+        "%",
+        &[
+            "win per round",
+            "deal-in per round",
+            "riichi per round",
+            "call per round",
+            "tenpai at draw",
+        ],
+        &values,
+    )
+}
+
+/// Average shanten after each turn's discard, one line per strategy.
+fn shanten_by_turn<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    data: &ChartData,
+) -> ChartResult
+where
+    DB::ErrorType: 'static,
+{
+    let lines: Vec<(&String, Vec<f64>)> = data
+        .strategies
+        .iter()
+        .map(|(label, strategy)| (label, strategy.average_shanten()))
+        .collect();
+    let turns = lines
+        .iter()
+        .map(|(_, line)| line.len())
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let top = lines
+        .iter()
+        .flat_map(|(_, line)| line)
+        .fold(1.0, |top: f64, &shanten| top.max(shanten));
+
+    let mut chart = ChartBuilder::on(root)
+        .caption("Average shanten by turn", ("sans-serif", 28))
+        .margin(20)
+        .x_label_area_size(45)
+        .y_label_area_size(60)
+        .build_cartesian_2d(1.0..turns as f64, 0.0..top * 1.05)?;
+    chart
+        .configure_mesh()
+        .x_desc("Turn")
+        .x_label_formatter(&|turn| format!("{:.0}", turn))
+        .y_desc("Shanten")
+        .draw()?;
+    for (index, (label, line)) in lines.iter().enumerate() {
+        let stroke = color(index);
+        chart
+            .draw_series(LineSeries::new(
+                line.iter()
+                    .enumerate()
+                    .map(|(turn, &shanten)| ((turn + 1) as f64, shanten)),
+                stroke.stroke_width(2),
+            ))?
+            .label(label.as_str())
+            .legend(move |(x, y)| {
+                PathElement::new(vec![(x, y), (x + 20, y)], stroke.stroke_width(2))
+            });
+    }
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+    Ok(())
+}
+
+#[test]
+fn test_write_charts() {
+    let mut strategies = BTreeMap::new();
+    strategies.insert("completor".to_string(), StrategyStats::default());
+    let mut history = ScoreHistory::default();
+    history.add([45000, 30000, 15000, 10000]);
+    history.add([20000, 20000, 30000, 30000]);
+    let data = ChartData {
+        seats: vec!["completor".to_string(); 4],
+        history: &history,
+        starting_points: 25000,
+        strategies: &strategies,
+    };
+    let directory = std::env::temp_dir().join(format!("mahjong-charts-{}", std::process::id()));
+    let written = write_charts(&directory, "run", ChartFormat::Svg, &data).unwrap();
+    assert_eq!(written.len(), Chart::ALL.len());
+    assert_eq!(written[0], directory.join("run-distribution.svg"));
+    assert!(written.iter().all(|path| path.exists()));
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,396 @@
+use clap::{Args, Parser, Subcommand};
+use mahjong_simulator::experiment::*;
+use mahjong_simulator::output::{ChartFormat, OutputFormat};
+use mahjong_simulator::significance::StoppingRule;
+use mahjong_simulator::strategies::*;
+use mahjong_simulator::tournament::Schedule;
+use mahjong_simulator::types::*;
+use std::path::PathBuf;
+
+const DEFAULT_SEATS: &str = "completor,completor,kanchan-completor,standard";
+
+/// Simulates riichi mahjong games to compare strategies. Without a subcommand it runs
+/// `simulate` with the given options.
+#[derive(Parser, Debug)]
+#[command(version, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    /// Worker threads, one per core by default
+    #[arg(long, global = true)]
+    pub threads: Option<usize>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub simulate: SimulateArgs,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Simulate games between four strategies and write each game's final scores
+    Simulate(SimulateArgs),
+    /// Show the shanten, waits and score of a hand written in MPSZ notation
+    AnalyzeHand(AnalyzeHandArgs),
+    /// Play one game of a seeded simulation again, drawing the table after every discard
+    Replay(ReplayArgs),
+    /// Play a pool of strategies against each other at tables of four and rank them
+    Tournament(TournamentArgs),
+    /// Join the results files of every shard of a run into the results of the whole run
+    Merge(MergeArgs),
+    /// Convert games from an event log into Tenhou.net/6 JSON logs
+    TenhouExport(TenhouExportArgs),
+    /// Replay Tenhou logs through our rules and scoring and report where they disagree
+    ImportLog(ImportLogArgs),
+    /// Ask a strategy about every decision one seat made in recorded games
+    AnalyzeLog(AnalyzeLogArgs),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct TableArgs {
+    /// Rules to play by: hanchan or tonpuusen
+    #[arg(long, default_value = "hanchan", value_parser = parse_ruleset)]
+    pub ruleset: Ruleset,
+
+    /// Strategies for the East, South, West and North seats
+    #[arg(long, default_value = DEFAULT_SEATS, value_parser = parse_seats)]
+    pub seats: [String; 4],
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct SimulateArgs {
+    /// Experiment file describing the runs; replaces the other options
+    #[arg(long, conflicts_with_all = ["games", "ruleset", "seats", "seed", "output", "format", "events", "stats", "charts", "chart_format", "rotation", "stop_at", "compare", "check_every"])]
+    pub config: Option<PathBuf>,
+
+    /// Number of games to play, or of wall sets with a duplicate rotation
+    #[arg(short = 'n', long, default_value_t = 1000)]
+    pub games: usize,
+
+    #[command(flatten)]
+    pub table: TableArgs,
+
+    /// Master seed every game's seed is derived from. A random one is picked and recorded
+    /// in the output when none is given.
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Results file, `<games>_games.<format>` by default
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    #[arg(long, value_enum, default_value_t = OutputFormat::Dat)]
+    pub format: OutputFormat,
+
+    /// Also write every game's events to this file as JSON lines
+    #[arg(long)]
+    pub events: Option<PathBuf>,
+
+    /// Also write per-strategy statistics to this file as CSV
+    #[arg(long)]
+    pub stats: Option<PathBuf>,
+
+    /// Also draw charts of the run into this directory
+    #[arg(long)]
+    pub charts: Option<PathBuf>,
+
+    #[arg(long, value_enum, default_value_t = ChartFormat::Png)]
+    pub chart_format: ChartFormat,
+
+    /// How the line-up changes seats from game to game
+    #[arg(long, value_enum, default_value_t = Rotation::None)]
+    pub rotation: Rotation,
+
+    /// Play one slice of the run's wall sets, e.g. `2/8` for the second of eight. Its files
+    /// are named with `.2-of-8` before the extension, ready for `merge`.
+    #[arg(long)]
+    pub shard: Option<Shard>,
+
+    /// Carry on an interrupted run from the last whole game in its results file
+    #[arg(long)]
+    pub resume: bool,
+
+    /// Stop once the strategies differ in mean score at this confidence, e.g. 0.95;
+    /// --games is then the most to play
+    #[arg(long, value_parser = parse_confidence)]
+    pub stop_at: Option<f64>,
+
+    /// Strategies --stop-at compares: two, or one to compare every other strategy against.
+    /// Every pair by default.
+    #[arg(long, value_delimiter = ',', requires = "stop_at")]
+    pub compare: Vec<String>,
+
+    /// Games, or wall sets with a duplicate rotation, between the checks of --stop-at
+    #[arg(long, default_value_t = 1000, value_parser = parse_check_every)]
+    pub check_every: usize,
+}
+
+impl SimulateArgs {
+    /// The run the options describe, every strategy with its default parameters.
+    pub fn experiment(&self) -> Experiment {
+        let format = self.format;
+        Experiment {
+            games: self.games,
+            seed: self.seed,
+            rotation: self.rotation,
+            shard: None,
+            stop: self.stop_at.map(|confidence| StoppingRule {
+                confidence,
+                compare: self.compare.clone(),
+                check_every: self.check_every,
+            }),
+            ruleset: self.table.ruleset.clone(),
+            seats: self
+                .table
+                .seats
+                .iter()
+                .map(|strategy| SeatConfig {
+                    strategy: strategy.clone(),
+                    params: default_params(strategy),
+                })
+                .collect(),
+            output: OutputConfig {
+                path: self
+                    .output
+                    .clone()
+                    .unwrap_or(default_output(self.games, format)),
+                format,
+                events: self.events.clone(),
+                stats: self.stats.clone(),
+                charts: self.charts.clone(),
+                chart_format: self.chart_format,
+            },
+        }
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct MergeArgs {
+    /// Results files of the shards, in any order
+    #[arg(required = true)]
+    pub inputs: Vec<PathBuf>,
+
+    /// Results file of the whole run, in the format of the shards
+    #[arg(short, long)]
+    pub output: PathBuf,
+
+    /// Also write per-strategy statistics to this file as CSV
+    #[arg(long)]
+    pub stats: Option<PathBuf>,
+
+    /// Also draw charts of the run into this directory
+    #[arg(long)]
+    pub charts: Option<PathBuf>,
+
+    #[arg(long, value_enum, default_value_t = ChartFormat::Png)]
+    pub chart_format: ChartFormat,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct AnalyzeHandArgs {
+    /// Hand in MPSZ notation with called melds in brackets, e.g. `123m0p55z[777s]`. In a
+    /// complete hand the last tile is the winning tile.
+    pub hand: Hand,
+
+    /// Seat wind of the player holding the hand
+    #[arg(long, default_value = "east", value_parser = str::parse::<SeatWind>)]
+    pub seat_wind: SeatWind,
+
+    /// Score the winning tile as a ron instead of a tsumo
+    #[arg(long)]
+    pub ron: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ReplayArgs {
+    /// Master seed of the simulation the game was part of
+    #[arg(long, required_unless_present = "game_seed")]
+    pub seed: Option<u64>,
+
+    /// Index of the game in that simulation, starting from 0
+    #[arg(long, default_value_t = 0, requires = "seed")]
+    pub game: usize,
+
+    /// Seed of the game itself, as recorded next to its result
+    #[arg(long, conflicts_with = "seed")]
+    pub game_seed: Option<u64>,
+
+    /// Only print the final scores
+    #[arg(short, long)]
+    pub quiet: bool,
+
+    /// Write the game's events to this file as JSON lines
+    #[arg(long)]
+    pub events: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub table: TableArgs,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct TournamentArgs {
+    /// Strategies taking part, every built-in strategy by default
+    #[arg(long, value_delimiter = ',', value_parser = parse_strategy_name)]
+    pub strategies: Vec<String>,
+
+    /// Games played at every table, with the line-up rotating a seat every game
+    #[arg(short = 'n', long, default_value_t = 100)]
+    pub games: usize,
+
+    /// How tables are drawn up
+    #[arg(long, value_enum, default_value_t = Schedule::RoundRobin)]
+    pub schedule: Schedule,
+
+    /// Rounds of a Swiss tournament
+    #[arg(long, default_value_t = 10)]
+    pub rounds: usize,
+
+    /// Rules to play by: hanchan or tonpuusen
+    #[arg(long, default_value = "hanchan", value_parser = parse_ruleset)]
+    pub ruleset: Ruleset,
+
+    /// Master seed, random when not given
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Also write the leaderboard to this file as CSV
+    #[arg(short, long)]
+    pub report: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct TenhouExportArgs {
+    /// Event log written with `--events`
+    pub events: PathBuf,
+
+    /// Index of the game to export, every game in the log by default
+    #[arg(long)]
+    pub game: Option<usize>,
+
+    /// File to write, `game-<index>.json` by default. When exporting every game the index
+    /// is added to the file name.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ImportLogArgs {
+    /// Tenhou mjlog (XML) or Tenhou.net/6 JSON files, one game each
+    #[arg(required = true)]
+    pub logs: Vec<PathBuf>,
+
+    /// Write the imported games to this file as JSON lines
+    #[arg(long)]
+    pub events: Option<PathBuf>,
+
+    /// List every rule violation and score difference, not only the first few of each log
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct AnalyzeLogArgs {
+    /// Event logs (`.jsonl`) or Tenhou mjlog and Tenhou.net/6 JSON files
+    #[arg(required = true)]
+    pub logs: Vec<PathBuf>,
+
+    /// Seat whose decisions are compared, 0 being whoever started as East
+    #[arg(long, default_value_t = 0, value_parser = parse_seat)]
+    pub seat: usize,
+
+    /// Strategy to ask
+    #[arg(long, default_value = "efficiency", value_parser = parse_strategy_name)]
+    pub strategy: String,
+
+    /// List every disagreement, not only the first 20
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+fn parse_ruleset(name: &str) -> Result<Ruleset, String> {
+    Ruleset::by_name(name).ok_or(format!(
+        "unknown ruleset '{}', expected hanchan or tonpuusen",
+        name
+    ))
+}
+
+fn parse_strategy_name(name: &str) -> Result<String, String> {
+    match strategy_by_name(name) {
+        Some(_) => Ok(name.to_string()),
+        None => Err(format!(
+            "unknown strategy '{}', expected one of {}",
+            name,
+            STRATEGY_NAMES.join(", ")
+        )),
+    }
+}
+
+fn parse_seats(names: &str) -> Result<[String; 4], String> {
+    let seats = names
+        .split(',')
+        .map(|name| parse_strategy_name(name.trim()))
+        .collect::<Result<Vec<String>, String>>()?;
+    let count = seats.len();
+    seats
+        .try_into()
+        .map_err(|_| format!("expected four strategies, got {}", count))
+}
+
+fn parse_confidence(confidence: &str) -> Result<f64, String> {
+    match confidence.parse() {
+        Ok(confidence) if confidence > 0.0 && confidence < 1.0 => Ok(confidence),
+        _ => Err(format!(
+            "expected a confidence between 0 and 1, got '{}'",
+            confidence
+        )),
+    }
+}
+
+fn parse_check_every(games: &str) -> Result<usize, String> {
+    match games.parse() {
+        Ok(games) if games > 0 => Ok(games),
+        _ => Err(format!("expected at least one game, got '{}'", games)),
+    }
+}
+
+fn parse_seat(seat: &str) -> Result<usize, String> {
+    match seat.parse() {
+        Ok(seat) if seat < 4 => Ok(seat),
+        _ => Err(format!("expected a seat from 0 to 3, got '{}'", seat)),
+    }
+}
+
+#[test]
+fn test_cli_definition() {
+    use clap::CommandFactory;
+    Cli::command().debug_assert();
+}
+
+#[test]
+fn test_parse_simulate() {
+    let cli = Cli::parse_from([
+        "mahjong-simulator",
+        "simulate",
+        "--games",
+        "10",
+        "--seats",
+        "standard,completor,completor,standard",
+        "--ruleset",
+        "tonpuusen",
+    ]);
+    let Some(Command::Simulate(args)) = cli.command else {
+        panic!("expected simulate");
+    };
+    assert_eq!(args.games, 10);
+    assert_eq!(args.table.ruleset, Ruleset::tonpuusen());
+    assert_eq!(args.table.seats[0], "standard");
+
+    let cli = Cli::parse_from(["mahjong-simulator"]);
+    assert!(cli.command.is_none());
+    assert_eq!(cli.simulate.games, 1000);
+    assert_eq!(cli.simulate.table.seats[2], "kanchan-completor");
+
+    for bad_seats in ["standard,completor", "standard,completor,completor,nobody"] {
+        let result = Cli::try_parse_from(["mahjong-simulator", "--seats", bad_seats]);
+        assert!(result.is_err());
+    }
+}
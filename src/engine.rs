@@ -0,0 +1,448 @@
+use crate::event_log::*;
+use crate::render::*;
+use crate::scoring::*;
+use crate::seeding::game_rng;
+use crate::types::mahjong_tile::*;
+use crate::types::notation::format_tiles;
+use crate::types::*;
+use core::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// The game loop: deal, then draw and discard in turn until someone wins by tsumo or the
+// wall runs out. Ron, chi, kan and riichi aren't played yet, and a pon is asked of the
+// discarder's strategy.
+
+/// Plays one game from `seed` with a strategy in each seat, East first, recording its events
+/// into `log` and showing the table to `watch` after every discard. Scores come back in seat
+/// order with uma added.
+pub fn simulate_game(
+    ruleset: &Ruleset,
+    seats: &[Arc<dyn Strategy>],
+    seed: u64,
+    mut watch: Option<&mut dyn FnMut(&TableView)>,
+    log: &mut EventLog,
+) -> GameResult {
+    let mut rng = game_rng(seed);
+    let mut players = initialize_players(ruleset, seats);
+
+    let mut round = 0;
+    'rounds: while round < ruleset.rounds {
+        for player in players.iter().take(3 + 1) {
+            if player.points < 0 && ruleset.tobi {
+                break 'rounds;
+            }
+        }
+        let mut player_tiles = PlayerTiles::default();
+
+        let (mut wall, wall_dead, dora_indicators) = initialize_wall(&mut rng);
+
+        (
+            wall,
+            player_tiles.hand[0],
+            player_tiles.hand[1],
+            player_tiles.hand[2],
+            player_tiles.hand[3],
+        ) = draw_hands(wall);
+
+        let mut board_tiles = BoardTiles {
+            wall,
+            wall_dead,
+            dora_indicators,
+            dora_index: 0,
+        };
+
+        flip_dora_indicator(&mut board_tiles, &mut player_tiles);
+
+        for i in 0..=3 {
+            player_tiles.hand[i].sort();
+        }
+        let dealer = players
+            .iter()
+            .position(|player| player.seat_wind == SeatWind::East)
+            .unwrap();
+        log.record(|| GameEvent::Deal {
+            round,
+            dealer,
+            points: players.iter().map(|player| player.points).collect(),
+            hands: player_tiles
+                .hand
+                .iter()
+                .map(|hand| format_tiles(hand))
+                .collect(),
+        });
+        log.record(|| GameEvent::DoraFlip {
+            indicator: board_tiles.dora_indicators[board_tiles.dora_index].to_string(),
+        });
+
+        let mut skip_draw = false;
+        let mut current_player_index = dealer;
+        'round: loop {
+            let next_player_index = (current_player_index + 1) % 4;
+            // Current player draws a tile
+            player_tiles.hand[current_player_index].sort();
+
+            if skip_draw {
+                skip_draw = false;
+            } else if board_tiles.wall.is_empty() {
+                let points_before: Vec<i32> = players.iter().map(|player| player.points).collect();
+                log.record(|| GameEvent::ExhaustiveDraw {
+                    tenpai: (0..4)
+                        .filter(|&index| check_tenpai(&player_tiles.hand[index]).0)
+                        .collect(),
+                });
+                scoring_tenpai(&mut player_tiles, &mut players);
+                record_payments(log, &points_before, &players);
+                break 'round;
+            } else {
+                draw_tile(
+                    &mut board_tiles.wall,
+                    &mut player_tiles.hand[current_player_index],
+                );
+                log.record(|| GameEvent::Draw {
+                    seat: current_player_index,
+                    tile: player_tiles.hand[current_player_index]
+                        .last()
+                        .unwrap()
+                        .to_string(),
+                });
+            }
+
+            let strategy_input = StrategyInput {
+                hand: player_tiles.hand[current_player_index].clone(),
+                discards: player_tiles.discards.clone(),
+                seat_wind: players[current_player_index].seat_wind.clone(),
+                round_number: round,
+                params: players[current_player_index].strategy.params(),
+            };
+
+            // Current player may tsumo
+            if is_complete(&player_tiles.hand[current_player_index])
+                && players[current_player_index]
+                    .strategy
+                    .tsumo(&strategy_input)
+            {
+                let points_before: Vec<i32> = players.iter().map(|player| player.points).collect();
+                let hand = Hand {
+                    closed: player_tiles.hand[current_player_index].clone(),
+                    called: player_tiles.open_hand[current_player_index]
+                        .chunks(3)
+                        .map(|meld| meld.to_vec())
+                        .collect(),
+                };
+                let score = scoring_tsumo(&mut player_tiles, &mut players, current_player_index);
+                log.record(|| GameEvent::Win {
+                    seat: current_player_index,
+                    from: None,
+                    winning_tile: hand.closed.last().unwrap().to_string(),
+                    hand: hand.to_string(),
+                    score,
+                });
+                record_payments(log, &points_before, &players);
+                break 'round;
+            }
+            // Current player may kan
+            // Current player discards a tile
+            // Placeholder - Need to pass relevant vectors to strategies (hand, discards, dora indicator..)
+            //let strategy_input = true;
+
+            move_tile(
+                &mut player_tiles.hand[current_player_index],
+                &mut player_tiles.discards[current_player_index],
+                players[current_player_index]
+                    .strategy
+                    .discard(&strategy_input),
+            );
+            let discarded = *player_tiles.discards[current_player_index].last().unwrap();
+            log.record(|| GameEvent::Discard {
+                seat: current_player_index,
+                tile: discarded.to_string(),
+            });
+            if let Some(watch) = watch.as_mut() {
+                watch(&TableView::from_game(
+                    round,
+                    &players,
+                    &player_tiles,
+                    &board_tiles,
+                ));
+            }
+
+            // Other players may ron
+            // Other players may pon
+            for i in 0..=3 {
+                if i != current_player_index
+                    && can_pon(&player_tiles.hand[i], &discarded)
+                    && players[current_player_index]
+                        .strategy
+                        .call_pon(&strategy_input)
+                {
+                    //println!("some guy pon'd a {:?}", discarded);
+                    player_tiles.hand[i].sort();
+                    remove_pon_tiles(&mut player_tiles.hand[i], &discarded);
+                    for _ in 1..=3 {
+                        player_tiles.open_hand[i].push(discarded);
+                    }
+                    let pond = &player_tiles.discards[current_player_index];
+                    player_tiles.called[current_player_index].push(pond.len() - 1);
+                    log.record(|| GameEvent::Call {
+                        seat: i,
+                        from: current_player_index,
+                        kind: CallKind::Pon,
+                        tiles: format_tiles(&[discarded; 3]),
+                    });
+                    break;
+                }
+            }
+
+            // Next player may chi
+            /* Need to change how this works -- strategy has to answer what straight to combine the stolen tile with so a boolean answer wont be enough
+            if !skip_chi
+                && can_chi(&game_state.players[next_player_index].hand, &discarded)
+                && (game_state.players[current_player_index].strategy.call_chi)(game_state.clone())
+            {
+                draw_tile(
+                    &mut game_state.players[current_player_index].discards,
+                    &mut game_state.players[next_player_index].hand,
+                );
+                skip_draw = true;
+                //change player's hand to open and move the chi'd set to a open section of the hand
+            };
+            */
+            // Pass turn to the next player
+            //println!("hand is open: {:?}", game_state.players[current_player_index].hand_is_open());
+            current_player_index = next_player_index;
+        }
+        // The next round starts when the deal passes on, Tenhou's way of counting them
+        if players[dealer].seat_wind != SeatWind::East {
+            round += 1;
+        }
+    }
+
+    let mut uma_vector = [0; 4];
+    if ruleset.uma {
+        let mut sorted_players = players.to_vec();
+        sorted_players.sort_by_key(|p| Reverse(p.points));
+
+        let mut tied_players: HashMap<i32, Vec<usize>> = HashMap::new();
+
+        for (i, p) in sorted_players.iter().enumerate() {
+            let rank = i as i32 + 1;
+            let uma_points: i32 = 15000 - 10000 * (rank - 1);
+            uma_vector[p.id - 1] = uma_points;
+
+            let ids = tied_players.entry(p.points).or_default();
+            ids.push(p.id);
+        }
+
+        let result: Vec<Vec<usize>> = tied_players.values().cloned().collect();
+        for res_vec in &result {
+            if res_vec.len() == 1 {
+                continue;
+            }
+            let mut uma_sum = 0;
+            for tied_player_id in res_vec {
+                uma_sum += &uma_vector[*tied_player_id - 1];
+            }
+            uma_sum /= res_vec.len() as i32;
+            for tied_player_id in res_vec {
+                uma_vector[*tied_player_id - 1] = uma_sum;
+            }
+        }
+    }
+
+    log.record(|| GameEvent::GameEnd {
+        points: players.iter().map(|player| player.points).collect(),
+        uma: uma_vector.to_vec(),
+    });
+    GameResult {
+        player_1_score: players[0].points + uma_vector[0],
+        player_2_score: players[1].points + uma_vector[1],
+        player_3_score: players[2].points + uma_vector[2],
+        player_4_score: players[3].points + uma_vector[3],
+        seed,
+        seats: vec![0, 1, 2, 3],
+        stats: Vec::new(),
+    }
+}
+
+fn record_payments(log: &mut EventLog, points_before: &[i32], players: &[Player]) {
+    log.record(|| GameEvent::Payment {
+        deltas: players
+            .iter()
+            .zip(points_before)
+            .map(|(player, before)| player.points - before)
+            .collect(),
+    });
+}
+
+fn initialize_players(ruleset: &Ruleset, seats: &[Arc<dyn Strategy>]) -> Vec<Player> {
+    let winds = [
+        SeatWind::East,
+        SeatWind::South,
+        SeatWind::West,
+        SeatWind::North,
+    ];
+    seats
+        .iter()
+        .zip(winds)
+        .enumerate()
+        .map(|(index, (strategy, seat_wind))| Player {
+            points: ruleset.starting_points,
+            seat_wind,
+            strategy: strategy.clone(),
+            id: index + 1,
+        })
+        .collect()
+}
+
+fn flip_dora_indicator(board_tiles: &mut BoardTiles, player_tiles: &mut PlayerTiles) {
+    let dora = dora_for(&board_tiles.dora_indicators[board_tiles.dora_index]);
+    let (dora_suit, dora_value) = (dora.suit, dora.value);
+
+    change_dora_bool(&mut board_tiles.wall, dora_suit, dora_value);
+    change_dora_bool(&mut board_tiles.wall_dead, dora_suit, dora_value);
+    change_dora_bool(&mut board_tiles.dora_indicators, dora_suit, dora_value);
+    for i in 0..=3 {
+        change_dora_bool(&mut player_tiles.hand[i], dora_suit, dora_value);
+        change_dora_bool(&mut player_tiles.open_hand[i], dora_suit, dora_value);
+    }
+}
+
+fn change_dora_bool(tile_list: &mut [MahjongTile], dora_suit: Suit, dora_value: u8) {
+    for tile in tile_list
+        .iter_mut()
+        .filter(|tile| tile.suit == dora_suit && tile.value == dora_value)
+    {
+        tile.is_dora = true;
+    }
+}
+
+fn scoring_tenpai(player_tiles: &mut PlayerTiles, players: &mut Vec<Player>) {
+    let mut tenpai_players = 0;
+    let mut noten_players = 0;
+    for i in 0..=3 {
+        let (got_tenpai, _) = check_tenpai(&player_tiles.hand[i]);
+        if got_tenpai {
+            tenpai_players += 1;
+        } else {
+            noten_players += 1;
+        }
+    }
+
+    let mut change_winds = true;
+
+    if tenpai_players == 4 {
+        change_winds = false;
+    }
+
+    if tenpai_players != 4 && noten_players != 4 {
+        let winner_payout = 3000 / tenpai_players;
+        for (index, player) in players.iter_mut().enumerate().take(3 + 1) {
+            let (got_tenpai, _) = check_tenpai(&player_tiles.hand[index]);
+            if got_tenpai {
+                player.points += winner_payout;
+                if player.seat_wind == SeatWind::East {
+                    change_winds = false;
+                }
+            } else if noten_players == 2 {
+                player.points -= winner_payout;
+            } else if noten_players == 1 {
+                player.points -= winner_payout * tenpai_players;
+            } else {
+                player.points -= winner_payout / noten_players;
+            }
+        }
+    }
+
+    if change_winds {
+        for player in players {
+            player.next_wind();
+        }
+    }
+}
+
+fn scoring_tsumo(
+    player_tiles: &mut PlayerTiles,
+    players: &mut Vec<Player>,
+    winning_player_index: usize,
+) -> HandScore {
+    let is_dealer_win = players[winning_player_index].seat_wind == SeatWind::East;
+
+    let called: Vec<Vec<MahjongTile>> = player_tiles.open_hand[winning_player_index]
+        .chunks(3)
+        .map(|meld| meld.to_vec())
+        .collect();
+    let score = score_hand(
+        &player_tiles.hand[winning_player_index],
+        &called,
+        true,
+        &players[winning_player_index].seat_wind,
+    );
+    let paid = payments(score.base_points, is_dealer_win, true, 0, 0);
+
+    for (index, player) in players.iter_mut().enumerate().take(3 + 1) {
+        if index == winning_player_index {
+            player.points += paid.total;
+        } else if player.seat_wind == SeatWind::East {
+            player.points -= paid.tsumo_dealer;
+        } else {
+            player.points -= paid.tsumo_non_dealer;
+        }
+    }
+
+    if !is_dealer_win {
+        for player in players {
+            player.next_wind();
+        }
+    }
+    score
+}
+
+#[test]
+fn test_closures_play_a_game() {
+    // Throw whatever was just drawn
+    let tsumogiri = |input: &StrategyInput| input.hand.len() - 1;
+    let seats: Vec<Arc<dyn Strategy>> = (0..4)
+        .map(|_| Arc::new(tsumogiri) as Arc<dyn Strategy>)
+        .collect();
+    let mut log = EventLog::enabled();
+    let result = simulate_game(&Ruleset::tonpuusen(), &seats, 7, None, &mut log);
+    let scores = [
+        result.player_1_score,
+        result.player_2_score,
+        result.player_3_score,
+        result.player_4_score,
+    ];
+    assert_eq!(scores.iter().sum::<i32>(), 4 * 25000);
+    assert!(matches!(log.events.last(), Some(GameEvent::GameEnd { .. })));
+}
+
+#[test]
+fn test_watch_marks_called_discards() {
+    // Throws whatever it drew and pons whatever it can
+    struct Ponner;
+    impl Strategy for Ponner {
+        fn discard(&self, input: &StrategyInput) -> usize {
+            input.hand.len() - 1
+        }
+
+        fn call_pon(&self, _input: &StrategyInput) -> bool {
+            true
+        }
+    }
+    let seats: Vec<Arc<dyn Strategy>> = (0..4)
+        .map(|_| Arc::new(Ponner) as Arc<dyn Strategy>)
+        .collect();
+    let mut marked = 0;
+    let mut watch = |table: &TableView| {
+        let called = table.seats.iter().flat_map(|seat| &seat.pond);
+        marked = marked.max(called.filter(|tile| tile.is_called).count());
+    };
+    let mut log = EventLog::enabled();
+    simulate_game(&Ruleset::tonpuusen(), &seats, 7, Some(&mut watch), &mut log);
+    assert!(log
+        .events
+        .iter()
+        .any(|event| matches!(event, GameEvent::Call { .. })));
+    assert!(marked > 0);
+}
@@ -0,0 +1,227 @@
+use crate::types::*;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Error, ErrorKind, Result, Write};
+use std::path::Path;
+
+// Seats are indices into the game's players, 0 being whoever started as East. Tiles are
+// written in MPSZ notation.
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum GameEvent {
+    GameStart {
+        seed: u64,
+        ruleset: String,
+        strategies: Vec<String>,
+    },
+    Deal {
+        round: u8,
+        dealer: usize,
+        points: Vec<i32>,
+        hands: Vec<String>,
+    },
+    DoraFlip {
+        indicator: String,
+    },
+    Draw {
+        seat: usize,
+        tile: String,
+    },
+    Discard {
+        seat: usize,
+        tile: String,
+    },
+    Call {
+        seat: usize,
+        from: usize,
+        kind: CallKind,
+        tiles: String,
+    },
+    /// The simulator doesn't declare riichi yet, imported logs do
+    Riichi {
+        seat: usize,
+    },
+    Win {
+        seat: usize,
+        /// Who dealt in, `None` for a tsumo
+        from: Option<usize>,
+        hand: String,
+        winning_tile: String,
+        score: HandScore,
+    },
+    ExhaustiveDraw {
+        tenpai: Vec<usize>,
+    },
+    /// A round ended without a winner before the wall ran out, e.g. `four_riichi`
+    AbortiveDraw {
+        reason: String,
+    },
+    Payment {
+        deltas: Vec<i32>,
+    },
+    GameEnd {
+        points: Vec<i32>,
+        uma: Vec<i32>,
+    },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallKind {
+    Chi,
+    Pon,
+    OpenKan,
+    ClosedKan,
+    AddedKan,
+}
+
+/// Events of one game. Recording is a no-op unless the log is enabled, and events are
+/// only built when they will be kept.
+#[derive(Debug, Clone, Default)]
+pub struct EventLog {
+    enabled: bool,
+    pub events: Vec<GameEvent>,
+}
+
+impl EventLog {
+    pub fn enabled() -> EventLog {
+        EventLog {
+            enabled: true,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn disabled() -> EventLog {
+        EventLog::default()
+    }
+
+    pub fn record(&mut self, event: impl FnOnce() -> GameEvent) {
+        if self.enabled {
+            self.events.push(event());
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct GameLine<'a> {
+    game: usize,
+    #[serde(flatten)]
+    event: &'a GameEvent,
+}
+
+/// One JSON object per line, each tagged with the index of its game in the run.
+pub fn write_event_logs(path: &Path, logs: &[EventLog]) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for (game, log) in logs.iter().enumerate() {
+        write_game_events(&mut writer, game, log)?;
+    }
+    writer.flush()
+}
+
+/// Appends one game's lines, for writing a run as its games finish.
+pub fn write_game_events(writer: &mut impl Write, game: usize, log: &EventLog) -> Result<()> {
+    for event in &log.events {
+        serde_json::to_writer(&mut *writer, &GameLine { game, event })?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Where each whole game ends in a file written by `write_event_logs`, in bytes. A run that
+/// died can leave part of a game, or half a line, after the last one.
+pub fn game_ends(path: &Path) -> Result<Vec<u64>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let (mut ends, mut offset) = (Vec::new(), 0);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let read = reader.read_line(&mut line)?;
+        if read == 0 || !line.ends_with('\n') {
+            return Ok(ends);
+        }
+        offset += read as u64;
+        if line.contains("\"event\":\"game_end\"") {
+            ends.push(offset);
+        }
+    }
+}
+
+/// Reads a file written by `write_event_logs` back into one log per game.
+pub fn read_event_logs(path: &Path) -> Result<Vec<EventLog>> {
+    let mut logs: Vec<EventLog> = Vec::new();
+    for (number, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let invalid = |error: serde_json::Error| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("line {}: {}", number + 1, error),
+            )
+        };
+        let mut value: serde_json::Value = serde_json::from_str(&line).map_err(invalid)?;
+        let game = value
+            .as_object_mut()
+            .and_then(|object| object.remove("game"))
+            .and_then(|game| game.as_u64())
+            .ok_or(Error::new(
+                ErrorKind::InvalidData,
+                format!("line {}: event has no game index", number + 1),
+            ))? as usize;
+        let event: GameEvent = serde_json::from_value(value).map_err(invalid)?;
+        if logs.len() <= game {
+            logs.resize_with(game + 1, EventLog::enabled);
+        }
+        logs[game].events.push(event);
+    }
+    Ok(logs)
+}
+
+#[test]
+fn test_event_json() {
+    let mut log = EventLog::disabled();
+    log.record(|| unreachable!("disabled logs never build events"));
+    assert!(log.events.is_empty());
+
+    let mut log = EventLog::enabled();
+    log.record(|| GameEvent::Discard {
+        seat: 2,
+        tile: "0p".to_string(),
+    });
+    log.record(|| GameEvent::Win {
+        seat: 1,
+        from: None,
+        hand: "123m456p789s11z555z".to_string(),
+        winning_tile: "5z".to_string(),
+        score: HandScore {
+            han: 2,
+            fu: 40,
+            yaku: vec![
+                Yaku {
+                    name: "menzen tsumo".to_string(),
+                    han: 1,
+                },
+                Yaku {
+                    name: "yakuhai".to_string(),
+                    han: 1,
+                },
+            ],
+            base_points: 640,
+        },
+    });
+    let path = std::env::temp_dir().join(format!("events-test-{}.jsonl", std::process::id()));
+    write_event_logs(&path, &[EventLog::enabled(), log.clone()]).unwrap();
+    let text = std::fs::read_to_string(&path).unwrap();
+    let read_back = read_event_logs(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(read_back[1].events, log.events);
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(
+        lines[0],
+        r#"{"game":1,"event":"discard","seat":2,"tile":"0p"}"#
+    );
+    assert!(lines[1].starts_with(r#"{"game":1,"event":"win","seat":1,"from":null,"#));
+    assert!(lines[1].contains(r#""yaku":[{"name":"menzen tsumo","han":1}"#));
+}
@@ -0,0 +1,640 @@
+use crate::output::{ChartFormat, OutputFormat};
+use crate::significance::StoppingRule;
+use crate::strategies::*;
+use crate::types::*;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+// An experiment file names the rules, the four seats and the outputs, e.g.
+//
+//     games = 5000
+//     seed = 42
+//     rotation = "rotate"
+//
+//     [ruleset]
+//     preset = "tonpuusen"
+//     starting_points = 30000
+//
+//     [[seats]]
+//     strategy = "efficiency"
+//     params = { dora_weight = [0.5, 1.0, 2.0] }
+//
+//     [stop]
+//     confidence = 0.95
+//     compare = ["efficiency", "standard"]
+//     check_every = 500
+//
+//     [output]
+//     path = "dora_weight.parquet"
+//     format = "parquet"
+//     events = "dora_weight.jsonl"
+//     stats = "dora_weight-stats.csv"
+//     charts = "charts"
+//
+// A list of parameter values is a sweep: the file expands into one run per combination.
+// With a [stop] section, games is the most a run plays; it ends as soon as the compared
+// strategies differ in mean score.
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    UnknownRuleset(String),
+    UnknownStrategy(String),
+    UnknownParameter(String, String),
+    EmptySweep(String),
+    SeatCount(usize),
+    NoGames,
+    NoRounds,
+    Confidence(f64),
+    NoChecks,
+    Compare(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(error) => write!(f, "{}", error),
+            ConfigError::Parse(error) => write!(f, "{}", error),
+            ConfigError::UnknownRuleset(name) => write!(f, "unknown ruleset preset '{}'", name),
+            ConfigError::UnknownStrategy(name) => write!(
+                f,
+                "unknown strategy '{}', expected one of {}",
+                name,
+                STRATEGY_NAMES.join(", ")
+            ),
+            ConfigError::UnknownParameter(strategy, param) => {
+                write!(f, "strategy '{}' has no parameter '{}'", strategy, param)
+            }
+            ConfigError::EmptySweep(param) => write!(f, "sweep over '{}' has no values", param),
+            ConfigError::SeatCount(count) => write!(f, "expected four seats, got {}", count),
+            ConfigError::NoGames => write!(f, "games must be at least 1"),
+            ConfigError::NoRounds => write!(f, "rounds must be at least 1"),
+            ConfigError::Confidence(confidence) => {
+                write!(f, "confidence must be between 0 and 1, got {}", confidence)
+            }
+            ConfigError::NoChecks => write!(f, "check_every must be at least 1"),
+            ConfigError::Compare(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum Rotation {
+    /// Every strategy keeps its seat
+    #[default]
+    None,
+    /// The line-up moves one seat further every game
+    Rotate,
+    /// Every set of walls is played four times, once with each rotation of the line-up
+    Duplicate,
+    /// Every set of walls is played 24 times, once with each seating of the line-up
+    DuplicateAll,
+}
+
+impl Rotation {
+    /// How many games are played from each set of walls.
+    pub fn games_per_wall(&self) -> usize {
+        match self {
+            Rotation::None | Rotation::Rotate => 1,
+            Rotation::Duplicate => 4,
+            Rotation::DuplicateAll => 24,
+        }
+    }
+
+    /// The line-up position sitting in each seat in the `index`th game.
+    pub fn seating(&self, index: usize) -> Vec<usize> {
+        match self {
+            Rotation::None => (0..4).collect(),
+            Rotation::Rotate | Rotation::Duplicate => {
+                (0..4).map(|seat| (seat + index) % 4).collect()
+            }
+            Rotation::DuplicateAll => (0..4)
+                .permutations(4)
+                .nth(index % 24)
+                .expect("four seats have 24 seatings"),
+        }
+    }
+}
+
+/// The `index`th of `count` equal slices of a run's wall sets, counting from 1. Shards of
+/// one run can play in separate processes and be merged into the whole run afterwards.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Shard {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl Shard {
+    /// The wall sets this shard plays out of `walls`.
+    pub fn walls(&self, walls: usize) -> Range<usize> {
+        walls * (self.index - 1) / self.count..walls * self.index / self.count
+    }
+
+    /// `results.csv` becomes `results.2-of-8.csv` for the second of eight shards.
+    pub fn path(&self, path: &Path) -> PathBuf {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match path.extension() {
+            Some(extension) => format!(
+                "{}.{}-of-{}.{}",
+                stem,
+                self.index,
+                self.count,
+                extension.to_string_lossy()
+            ),
+            None => format!("{}.{}-of-{}", stem, self.index, self.count),
+        };
+        path.with_file_name(name)
+    }
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Shard, String> {
+        let invalid = || format!("expected a shard like 2/8, got '{}'", text);
+        let (index, count) = text.split_once('/').ok_or_else(invalid)?;
+        let index: usize = index.trim().parse().map_err(|_| invalid())?;
+        let count: usize = count.trim().parse().map_err(|_| invalid())?;
+        if index == 0 || index > count {
+            return Err(format!("shard {} of {} doesn't exist", index, count));
+        }
+        Ok(Shard { index, count })
+    }
+}
+
+impl TryFrom<String> for Shard {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Shard, String> {
+        text.parse()
+    }
+}
+
+impl From<Shard> for String {
+    fn from(shard: Shard) -> String {
+        shard.to_string()
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+/// One fully resolved run. Results are reported in the order of `seats` whatever the
+/// rotation, and the whole struct is echoed into the output metadata, which reads back into
+/// the same struct.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Experiment {
+    pub games: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    pub rotation: Rotation,
+    /// Only this slice of the run is played
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shard: Option<Shard>,
+    /// Stop before all the games once the strategies clearly differ
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop: Option<StoppingRule>,
+    pub ruleset: Ruleset,
+    pub seats: Vec<SeatConfig>,
+    pub output: OutputConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SeatConfig {
+    pub strategy: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, f64>,
+}
+
+impl SeatConfig {
+    /// The strategy's name, followed by any parameters that differ from its defaults.
+    pub fn label(&self) -> String {
+        let defaults = default_params(&self.strategy);
+        let changed: Vec<String> = self
+            .params
+            .iter()
+            .filter(|(name, value)| defaults.get(*name) != Some(value))
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        if changed.is_empty() {
+            self.strategy.clone()
+        } else {
+            format!("{}({})", self.strategy, changed.join(","))
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OutputConfig {
+    pub path: PathBuf,
+    pub format: OutputFormat,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<PathBuf>,
+    /// Directory charts are drawn into
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub charts: Option<PathBuf>,
+    #[serde(default)]
+    pub chart_format: ChartFormat,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ExperimentFile {
+    games: Option<usize>,
+    seed: Option<u64>,
+    #[serde(default)]
+    rotation: Rotation,
+    #[serde(default)]
+    ruleset: RulesetSection,
+    seats: Vec<SeatSection>,
+    stop: Option<StoppingRule>,
+    #[serde(default)]
+    output: OutputSection,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct RulesetSection {
+    preset: Option<String>,
+    rounds: Option<u8>,
+    uma: Option<bool>,
+    tobi: Option<bool>,
+    starting_points: Option<i32>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct SeatSection {
+    strategy: String,
+    #[serde(default)]
+    params: BTreeMap<String, ParamValue>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum ParamValue {
+    Single(f64),
+    Sweep(Vec<f64>),
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct OutputSection {
+    path: Option<PathBuf>,
+    format: Option<OutputFormat>,
+    events: Option<PathBuf>,
+    stats: Option<PathBuf>,
+    charts: Option<PathBuf>,
+    chart_format: Option<ChartFormat>,
+}
+
+impl Experiment {
+    /// Reads and validates an experiment file, expanding any parameter sweeps.
+    pub fn load(path: &Path) -> Result<Vec<Experiment>, ConfigError> {
+        let text = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        Experiment::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Vec<Experiment>, ConfigError> {
+        let file: ExperimentFile = toml::from_str(text).map_err(ConfigError::Parse)?;
+
+        let mut ruleset = match &file.ruleset.preset {
+            Some(name) => {
+                Ruleset::by_name(name).ok_or(ConfigError::UnknownRuleset(name.clone()))?
+            }
+            None => Ruleset::default(),
+        };
+        ruleset.rounds = file.ruleset.rounds.unwrap_or(ruleset.rounds);
+        ruleset.uma = file.ruleset.uma.unwrap_or(ruleset.uma);
+        ruleset.tobi = file.ruleset.tobi.unwrap_or(ruleset.tobi);
+        ruleset.starting_points = file
+            .ruleset
+            .starting_points
+            .unwrap_or(ruleset.starting_points);
+        if ruleset.rounds == 0 {
+            return Err(ConfigError::NoRounds);
+        }
+
+        let games = file.games.unwrap_or(1000);
+        if games == 0 {
+            return Err(ConfigError::NoGames);
+        }
+        if file.seats.len() != 4 {
+            return Err(ConfigError::SeatCount(file.seats.len()));
+        }
+        if let Some(stop) = &file.stop {
+            if !(stop.confidence > 0.0 && stop.confidence < 1.0) {
+                return Err(ConfigError::Confidence(stop.confidence));
+            }
+            if stop.check_every == 0 {
+                return Err(ConfigError::NoChecks);
+            }
+        }
+
+        // Every seat starts from its strategy's defaults; each sweep multiplies the runs
+        let mut seat_params: Vec<Vec<BTreeMap<String, f64>>> = Vec::new();
+        for seat in &file.seats {
+            if strategy_by_name(&seat.strategy).is_none() {
+                return Err(ConfigError::UnknownStrategy(seat.strategy.clone()));
+            }
+            let mut variants = vec![default_params(&seat.strategy)];
+            for (name, value) in &seat.params {
+                if !variants[0].contains_key(name) {
+                    return Err(ConfigError::UnknownParameter(
+                        seat.strategy.clone(),
+                        name.clone(),
+                    ));
+                }
+                let values = match value {
+                    ParamValue::Single(value) => vec![*value],
+                    ParamValue::Sweep(values) if values.is_empty() => {
+                        return Err(ConfigError::EmptySweep(name.clone()));
+                    }
+                    ParamValue::Sweep(values) => values.clone(),
+                };
+                variants = variants
+                    .iter()
+                    .flat_map(|params| {
+                        values.iter().map(move |&value| {
+                            let mut params = params.clone();
+                            params.insert(name.clone(), value);
+                            params
+                        })
+                    })
+                    .collect();
+            }
+            seat_params.push(variants);
+        }
+
+        let format = file.output.format.unwrap_or(OutputFormat::Dat);
+        let path = file
+            .output
+            .path
+            .clone()
+            .unwrap_or(default_output(games, format));
+        let runs: Vec<Vec<BTreeMap<String, f64>>> = seat_params
+            .into_iter()
+            .map(|variants| variants.into_iter())
+            .multi_cartesian_product()
+            .collect();
+        let run_count = runs.len();
+        let experiments: Vec<Experiment> = runs
+            .into_iter()
+            .enumerate()
+            .map(|(run, params)| Experiment {
+                games,
+                seed: file.seed,
+                rotation: file.rotation,
+                shard: None,
+                stop: file.stop.clone(),
+                ruleset: ruleset.clone(),
+                seats: file
+                    .seats
+                    .iter()
+                    .zip(params)
+                    .map(|(seat, params)| SeatConfig {
+                        strategy: seat.strategy.clone(),
+                        params,
+                    })
+                    .collect(),
+                output: OutputConfig {
+                    path: if run_count > 1 {
+                        numbered_path(&path, run)
+                    } else {
+                        path.clone()
+                    },
+                    format,
+                    events: match &file.output.events {
+                        Some(events) if run_count > 1 => Some(numbered_path(events, run)),
+                        events => events.clone(),
+                    },
+                    stats: match &file.output.stats {
+                        Some(stats) if run_count > 1 => Some(numbered_path(stats, run)),
+                        stats => stats.clone(),
+                    },
+                    charts: file.output.charts.clone(),
+                    chart_format: file.output.chart_format.unwrap_or_default(),
+                },
+            })
+            .collect();
+        for experiment in &experiments {
+            if let Some(stop) = &experiment.stop {
+                stop.pairs(&experiment.seats)
+                    .map_err(ConfigError::Compare)?;
+            }
+        }
+        Ok(experiments)
+    }
+
+    pub fn lineup(&self) -> Vec<Arc<dyn Strategy>> {
+        self.seats
+            .iter()
+            .map(|seat| -> Arc<dyn Strategy> {
+                Arc::new(BuiltinStrategy {
+                    params: Arc::new(seat.params.clone()),
+                    ..strategy_by_name(&seat.strategy).expect("strategy names are validated")
+                })
+            })
+            .collect()
+    }
+
+    /// This run limited to one shard, writing to files named after it.
+    pub fn sharded(&self, shard: Shard) -> Experiment {
+        let output = &self.output;
+        Experiment {
+            shard: Some(shard),
+            output: OutputConfig {
+                path: shard.path(&output.path),
+                events: output.events.as_deref().map(|path| shard.path(path)),
+                stats: output.stats.as_deref().map(|path| shard.path(path)),
+                ..output.clone()
+            },
+            ..self.clone()
+        }
+    }
+
+    /// Indices of the games this run plays, out of every game of the unsharded run.
+    pub fn game_range(&self) -> Range<usize> {
+        let per_wall = self.rotation.games_per_wall();
+        let walls = match self.shard {
+            Some(shard) => shard.walls(self.games),
+            None => 0..self.games,
+        };
+        walls.start * per_wall..walls.end * per_wall
+    }
+
+    /// The configuration as TOML, for the head of a results file.
+    pub fn metadata(&self) -> String {
+        toml::to_string(self).expect("experiments always serialise")
+    }
+}
+
+/// The parameters a strategy plays with unless told otherwise.
+pub fn default_params(strategy: &str) -> BTreeMap<String, f64> {
+    strategy_parameters(strategy)
+        .iter()
+        .map(|&(name, value)| (name.to_string(), value))
+        .collect()
+}
+
+/// `5000_games.dat` and the like, where a run writes without an output path.
+pub fn default_output(games: usize, format: OutputFormat) -> PathBuf {
+    format!("{}_games.{}", games, format.extension()).into()
+}
+
+/// `results.csv` becomes `results-2.csv` for the third run of a sweep.
+pub fn numbered_path(path: &Path, run: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, run, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, run),
+    };
+    path.with_file_name(name)
+}
+
+#[test]
+fn test_parse_experiment_with_sweep() {
+    let experiments = Experiment::parse(
+        r#"
+        games = 50
+        seed = 7
+        rotation = "rotate"
+
+        [ruleset]
+        preset = "tonpuusen"
+        starting_points = 30000
+
+        [[seats]]
+        strategy = "efficiency"
+        params = { dora_weight = [0.5, 2.0] }
+
+        [[seats]]
+        strategy = "efficiency"
+        params = { dora_weight = [0.0, 1.0, 3.0] }
+
+        [[seats]]
+        strategy = "completor"
+
+        [[seats]]
+        strategy = "standard"
+
+        [output]
+        path = "out/sweep.csv"
+        format = "csv"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(experiments.len(), 6);
+    let first = &experiments[0];
+    assert_eq!(first.ruleset.rounds, 4);
+    assert_eq!(first.ruleset.starting_points, 30000);
+    assert_eq!(first.rotation, Rotation::Rotate);
+    assert_eq!(Rotation::Rotate.seating(5), vec![1, 2, 3, 0]);
+    assert_eq!(Rotation::DuplicateAll.seating(23), vec![3, 2, 1, 0]);
+    assert_eq!(first.seats[0].params["dora_weight"], 0.5);
+    assert_eq!(experiments[5].seats[0].params["dora_weight"], 2.0);
+    assert_eq!(experiments[5].seats[1].params["dora_weight"], 3.0);
+    assert_eq!(experiments[5].output.path, PathBuf::from("out/sweep-5.csv"));
+    assert!(first.metadata().contains("dora_weight = 0.5"));
+    assert_eq!(first.seats[0].label(), "efficiency(dora_weight=0.5)");
+    assert_eq!(experiments[1].seats[1].label(), "efficiency");
+}
+
+#[test]
+fn test_experiment_validation() {
+    let seats = |strategies: &[&str]| {
+        strategies
+            .iter()
+            .map(|name| format!("[[seats]]\nstrategy = \"{}\"\n", name))
+            .collect::<String>()
+    };
+    let four = seats(&["completor", "completor", "standard", "standard"]);
+    assert!(Experiment::parse(&four).is_ok());
+    assert!(matches!(
+        Experiment::parse(&seats(&["completor"])),
+        Err(ConfigError::SeatCount(1))
+    ));
+    assert!(matches!(
+        Experiment::parse(&seats(&["completor", "completor", "standard", "cheater"])),
+        Err(ConfigError::UnknownStrategy(_))
+    ));
+    assert!(matches!(
+        Experiment::parse(&format!("[ruleset]\npreset = \"sanma\"\n{}", four)),
+        Err(ConfigError::UnknownRuleset(_))
+    ));
+    assert!(matches!(
+        Experiment::parse(&format!("games = 0\n{}", four)),
+        Err(ConfigError::NoGames)
+    ));
+    assert!(matches!(
+        Experiment::parse(&format!("{}params = {{ dora_weight = 2 }}", four)),
+        Err(ConfigError::UnknownParameter(_, _))
+    ));
+    assert!(matches!(
+        Experiment::parse(&format!("gmaes = 10\n{}", four)),
+        Err(ConfigError::Parse(_))
+    ));
+    let stopped = Experiment::parse(&format!("{}[stop]\nconfidence = 0.99\n", four)).unwrap();
+    assert_eq!(stopped[0].stop.as_ref().unwrap().check_every, 1000);
+    assert_eq!(
+        toml::from_str::<Experiment>(&stopped[0].metadata()).unwrap(),
+        stopped[0]
+    );
+    assert!(matches!(
+        Experiment::parse(&format!("{}[stop]\nconfidence = 95\n", four)),
+        Err(ConfigError::Confidence(_))
+    ));
+    assert!(matches!(
+        Experiment::parse(&format!(
+            "{}[stop]\nconfidence = 0.95\ncompare = [\"efficiency\"]\n",
+            four
+        )),
+        Err(ConfigError::Compare(_))
+    ));
+}
+
+#[test]
+fn test_shards() {
+    assert_eq!("2/8".parse(), Ok(Shard { index: 2, count: 8 }));
+    assert!("0/8".parse::<Shard>().is_err());
+    assert!("9/8".parse::<Shard>().is_err());
+    assert!("2-8".parse::<Shard>().is_err());
+
+    let experiment = Experiment::parse(
+        "games = 10\nrotation = \"duplicate\"\n[output]\npath = \"runs/results.csv\"\n\
+         [[seats]]\nstrategy = \"completor\"\n[[seats]]\nstrategy = \"completor\"\n\
+         [[seats]]\nstrategy = \"standard\"\n[[seats]]\nstrategy = \"standard\"\n",
+    )
+    .unwrap()
+    .remove(0);
+    assert_eq!(experiment.game_range(), 0..40);
+    // Shards split on wall sets, so duplicate games stay together
+    let ranges: Vec<Range<usize>> = (1..=3)
+        .map(|index| experiment.sharded(Shard { index, count: 3 }).game_range())
+        .collect();
+    assert_eq!(ranges, vec![0..12, 12..24, 24..40]);
+
+    let shard = experiment.sharded("2/3".parse().unwrap());
+    assert_eq!(shard.output.path, PathBuf::from("runs/results.2-of-3.csv"));
+    assert!(shard.metadata().contains("shard = \"2/3\""));
+    let read_back: Experiment = toml::from_str(&shard.metadata()).unwrap();
+    assert_eq!(read_back, shard);
+}
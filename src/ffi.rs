@@ -0,0 +1,318 @@
+// C interface to the hand evaluator and scorer, built with the `ffi` feature into the
+// crate's cdylib. `include/mahjong_simulator.h` declares it and is regenerated by cbindgen
+// on every `ffi` build.
+//
+// Hands are MPSZ notation: the closed tiles, the tile won on last, and each called meld as
+// its own string, e.g. `"123m11z345s"` with the melds `"555z"` and `"789s"`. Every
+// function returns an `MjStatus` and writes its result through the last pointer only on
+// `MjStatus::Ok`. Tile kinds are numbered 0 to 8 for 1m to 9m, 9 to 17 for the pinzu,
+// 18 to 26 for the souzu and 27 to 33 for the winds East to North and the dragons haku,
+// hatsu and chun.
+
+use crate::scoring::{payments, score_hand_with_dora};
+use crate::types::mahjong_tile::dora_for;
+use crate::types::notation::parse_tiles;
+use crate::types::tile_counts::{is_agari, shanten, to_counts, wait_mask};
+use crate::types::{Hand, SeatWind};
+use std::ffi::{c_char, CStr};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// Raised whenever a function or struct of this interface changes incompatibly.
+pub const MJ_ABI_VERSION: u32 = 1;
+/// Most yaku a score lists.
+pub const MJ_MAX_YAKU: usize = 16;
+/// Bytes a yaku name takes, its terminating NUL included.
+pub const MJ_YAKU_NAME_SIZE: usize = 32;
+
+/// Whether a call worked, and why not.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MjStatus {
+    Ok = 0,
+    /// A pointer that may not be NULL was
+    NullArgument = 1,
+    /// A string isn't UTF-8, MPSZ notation or a meld
+    InvalidNotation = 2,
+    /// The hand holds the wrong number of tiles for the call
+    WrongTileCount = 3,
+    /// The hand to score isn't complete
+    NotComplete = 4,
+    /// A seat wind isn't 0 to 3
+    InvalidSeatWind = 5,
+    /// A bug on our side; the call had no effect
+    Internal = 6,
+}
+
+/// How a hand was won.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct MjWinContext {
+    /// Won on a self-drawn tile rather than a discard
+    pub tsumo: bool,
+    /// 0 for East, which deals, to 3 for North
+    pub seat_wind: u8,
+    /// Dora indicators in MPSZ notation, or NULL when none count
+    pub dora_indicators: *const c_char,
+    /// Counters on the table, each worth 300 points to the winner
+    pub honba: u32,
+    /// Riichi deposits the winner collects, 1000 points each
+    pub riichi_sticks: u32,
+}
+
+/// The scoring rules that differ between tables.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MjRuleset {
+    /// Red fives, written 0m, 0p and 0s, are one dora each
+    pub red_fives: bool,
+    /// 13 han or more is a yakuman rather than a sanbaiman
+    pub kazoe_yakuman: bool,
+}
+
+/// A yaku, or dora, and its han.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct MjYaku {
+    /// NUL-terminated
+    pub name: [c_char; MJ_YAKU_NAME_SIZE],
+    pub han: i32,
+}
+
+/// What the winner is paid, honba and riichi sticks included.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct MjPayments {
+    /// Paid by the discarder on a ron
+    pub ron: i32,
+    /// Paid by the dealer on someone else's tsumo
+    pub tsumo_dealer: i32,
+    /// Paid by each other player on a tsumo
+    pub tsumo_non_dealer: i32,
+    /// Everything the winner collects
+    pub total: i32,
+}
+
+/// The score of a winning hand.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct MjScore {
+    pub han: i32,
+    pub fu: i32,
+    pub base_points: i32,
+    /// Entries of `yaku` in use
+    pub yaku_count: u32,
+    pub yaku: [MjYaku; MJ_MAX_YAKU],
+    pub payments: MjPayments,
+}
+
+/// The version of this interface the library implements, `MJ_ABI_VERSION` when it matches
+/// the header.
+#[no_mangle]
+pub extern "C" fn mj_abi_version() -> u32 {
+    MJ_ABI_VERSION
+}
+
+/// A static, NUL-terminated description of a status.
+#[no_mangle]
+pub extern "C" fn mj_status_message(status: MjStatus) -> *const c_char {
+    let message: &CStr = match status {
+        MjStatus::Ok => c"ok",
+        MjStatus::NullArgument => c"a required pointer is NULL",
+        MjStatus::InvalidNotation => c"not valid MPSZ notation",
+        MjStatus::WrongTileCount => c"wrong number of tiles in the hand",
+        MjStatus::NotComplete => c"the hand is not complete",
+        MjStatus::InvalidSeatWind => c"the seat wind is not 0 to 3",
+        MjStatus::Internal => c"internal error",
+    };
+    message.as_ptr()
+}
+
+/// The rules the simulator plays by: red fives count and 13 han is a yakuman.
+#[no_mangle]
+pub extern "C" fn mj_ruleset_default() -> MjRuleset {
+    MjRuleset {
+        red_fives: true,
+        kazoe_yakuman: true,
+    }
+}
+
+/// Reads a C string as UTF-8.
+///
+/// # Safety
+/// `text` is NULL or a NUL-terminated string.
+unsafe fn read_str<'a>(text: *const c_char) -> Result<&'a str, MjStatus> {
+    if text.is_null() {
+        return Err(MjStatus::NullArgument);
+    }
+    CStr::from_ptr(text)
+        .to_str()
+        .map_err(|_| MjStatus::InvalidNotation)
+}
+
+/// Reads the closed tiles and the melds, and checks the hand holds `sizes` tiles.
+///
+/// # Safety
+/// As for the public functions taking them.
+unsafe fn read_hand(
+    closed: *const c_char,
+    melds: *const *const c_char,
+    meld_count: usize,
+    sizes: &[usize],
+) -> Result<Hand, MjStatus> {
+    let mut notation = read_str(closed)?.to_string();
+    if meld_count > 0 && melds.is_null() {
+        return Err(MjStatus::NullArgument);
+    }
+    for index in 0..meld_count {
+        let meld = read_str(*melds.add(index))?;
+        if meld.contains(['[', ']']) {
+            return Err(MjStatus::InvalidNotation);
+        }
+        notation.push_str(&format!("[{}]", meld));
+    }
+    let hand: Hand = notation.parse().map_err(|_| MjStatus::InvalidNotation)?;
+    if !sizes.contains(&hand.tile_count()) {
+        return Err(MjStatus::WrongTileCount);
+    }
+    Ok(hand)
+}
+
+/// Runs `body`, turning a panic into `MjStatus::Internal`, and writes its result to `out`.
+///
+/// # Safety
+/// `out` is NULL or valid for writes.
+unsafe fn answer<T>(out: *mut T, body: impl FnOnce() -> Result<T, MjStatus>) -> MjStatus {
+    if out.is_null() {
+        return MjStatus::NullArgument;
+    }
+    match catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(value)) => {
+            out.write(value);
+            MjStatus::Ok
+        }
+        Ok(Err(status)) => status,
+        Err(_) => MjStatus::Internal,
+    }
+}
+
+/// Tiles away from tenpai of a hand of 13 or 14 tiles: 0 is tenpai and -1 complete.
+///
+/// # Safety
+/// `closed` is a NUL-terminated string, `melds` points to `meld_count` of them (or may be
+/// NULL when `meld_count` is 0), and `shanten_out` is valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn mj_shanten(
+    closed: *const c_char,
+    melds: *const *const c_char,
+    meld_count: usize,
+    shanten_out: *mut i8,
+) -> MjStatus {
+    answer(shanten_out, || {
+        let hand = read_hand(closed, melds, meld_count, &[13, 14])?;
+        Ok(shanten(&to_counts(&hand.closed)))
+    })
+}
+
+/// The tile kinds completing a hand of 13 tiles, bit `i` for kind `i`; 0 when it isn't
+/// tenpai.
+///
+/// # Safety
+/// As for `mj_shanten`, with `waits_out` valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn mj_waits(
+    closed: *const c_char,
+    melds: *const *const c_char,
+    meld_count: usize,
+    waits_out: *mut u64,
+) -> MjStatus {
+    answer(waits_out, || {
+        let hand = read_hand(closed, melds, meld_count, &[13])?;
+        Ok(wait_mask(&to_counts(&hand.closed)))
+    })
+}
+
+/// Scores a complete hand of 14 tiles, the last closed tile being the one won on. Yaku past
+/// `MJ_MAX_YAKU` are left out of the list but not the han.
+///
+/// # Safety
+/// As for `mj_shanten`; `context` and `ruleset` point to valid structs, the indicators in
+/// `context` are NULL or a NUL-terminated string, and `score_out` is valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn mj_score_hand(
+    closed: *const c_char,
+    melds: *const *const c_char,
+    meld_count: usize,
+    context: *const MjWinContext,
+    ruleset: *const MjRuleset,
+    score_out: *mut MjScore,
+) -> MjStatus {
+    answer(score_out, || {
+        let (Some(context), Some(ruleset)) = (context.as_ref(), ruleset.as_ref()) else {
+            return Err(MjStatus::NullArgument);
+        };
+        let seat_wind = match context.seat_wind {
+            0 => SeatWind::East,
+            1 => SeatWind::South,
+            2 => SeatWind::West,
+            3 => SeatWind::North,
+            _ => return Err(MjStatus::InvalidSeatWind),
+        };
+        let mut hand = read_hand(closed, melds, meld_count, &[14])?;
+        if !is_agari(&to_counts(&hand.closed)) {
+            return Err(MjStatus::NotComplete);
+        }
+        let dora = if context.dora_indicators.is_null() {
+            Vec::new()
+        } else {
+            let indicators = read_str(context.dora_indicators)?;
+            let indicators = parse_tiles(indicators).map_err(|_| MjStatus::InvalidNotation)?;
+            indicators.iter().map(dora_for).collect()
+        };
+        for tile in hand
+            .closed
+            .iter_mut()
+            .chain(hand.called.iter_mut().flatten())
+        {
+            tile.is_red &= ruleset.red_fives;
+        }
+
+        let mut score =
+            score_hand_with_dora(&hand.closed, &hand.called, context.tsumo, &seat_wind, &dora);
+        if !ruleset.kazoe_yakuman {
+            score.base_points = score.base_points.min(6000);
+        }
+        let paid = payments(
+            score.base_points,
+            seat_wind == SeatWind::East,
+            context.tsumo,
+            context.honba as i32,
+            context.riichi_sticks as i32,
+        );
+        let mut yaku = [MjYaku {
+            name: [0; MJ_YAKU_NAME_SIZE],
+            han: 0,
+        }; MJ_MAX_YAKU];
+        for (entry, scored) in yaku.iter_mut().zip(&score.yaku) {
+            for (byte, &letter) in entry.name[..MJ_YAKU_NAME_SIZE - 1]
+                .iter_mut()
+                .zip(scored.name.as_bytes())
+            {
+                *byte = letter as c_char;
+            }
+            entry.han = scored.han;
+        }
+        Ok(MjScore {
+            han: score.han,
+            fu: score.fu,
+            base_points: score.base_points,
+            yaku_count: score.yaku.len().min(MJ_MAX_YAKU) as u32,
+            yaku,
+            payments: MjPayments {
+                ron: paid.ron,
+                tsumo_dealer: paid.tsumo_dealer,
+                tsumo_non_dealer: paid.tsumo_non_dealer,
+                total: paid.total,
+            },
+        })
+    })
+}
@@ -0,0 +1,503 @@
+use crate::event_log::*;
+use crate::tenhou::{abortive_draw_reason, from_tenhou, yaku_by_id};
+use crate::types::mahjong_tile::*;
+use crate::types::notation::format_tiles;
+use crate::types::tile_counts::*;
+use crate::types::*;
+use std::fmt;
+
+// Tenhou's mjlog files are flat XML: an INIT tag deals each round, T/U/V/W followed by a tile
+// id draw for seats 0-3 and D/E/F/G discard, N is a call, and AGARI and RYUUKYOKU end the
+// round. Tile ids run from 0 to 135, four to a kind in the same order as our tile counts, and
+// ids 16, 52 and 88 are the red fives when the game plays with them.
+
+#[derive(Debug)]
+pub enum ImportError {
+    Json(serde_json::Error),
+    Malformed(String),
+    Unsupported(String),
+    UnknownFormat,
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::Json(error) => write!(f, "invalid JSON: {}", error),
+            ImportError::Malformed(what) => write!(f, "malformed log: {}", what),
+            ImportError::Unsupported(what) => write!(f, "{} are not supported", what),
+            ImportError::UnknownFormat => {
+                write!(f, "not a Tenhou mjlog or Tenhou.net/6 JSON log")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+fn malformed(what: impl Into<String>) -> ImportError {
+    ImportError::Malformed(what.into())
+}
+
+/// Reads a Tenhou log, telling mjlog XML and Tenhou.net/6 JSON apart by their first character.
+pub fn import_log(text: &str) -> Result<Vec<GameEvent>, ImportError> {
+    match text.trim_start().chars().next() {
+        Some('<') => from_mjlog(text),
+        Some('{') => from_tenhou(&serde_json::from_str(text).map_err(ImportError::Json)?),
+        _ => Err(ImportError::UnknownFormat),
+    }
+}
+
+struct Tag<'a> {
+    name: &'a str,
+    attributes: Vec<(&'a str, String)>,
+}
+
+impl Tag<'_> {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn numbers(&self, name: &str) -> Result<Vec<f64>, ImportError> {
+        let value = self
+            .get(name)
+            .ok_or_else(|| malformed(format!("{} has no {}", self.name, name)))?;
+        value
+            .split(',')
+            .filter(|number| !number.is_empty())
+            .map(|number| {
+                number
+                    .trim()
+                    .parse()
+                    .map_err(|_| malformed(format!("{} {}=\"{}\"", self.name, name, value)))
+            })
+            .collect()
+    }
+
+    fn number(&self, name: &str) -> Result<usize, ImportError> {
+        match self.numbers(name)?.as_slice() {
+            [number] => Ok(*number as usize),
+            _ => Err(malformed(format!("{} {} is not a number", self.name, name))),
+        }
+    }
+}
+
+fn xml_tags(xml: &str) -> Result<Vec<Tag<'_>>, ImportError> {
+    let mut tags = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        let end = rest[start..]
+            .find('>')
+            .ok_or_else(|| malformed("unclosed tag"))?
+            + start;
+        let inner = &rest[start + 1..end];
+        rest = &rest[end + 1..];
+        if inner.starts_with(['?', '!', '/']) {
+            continue;
+        }
+        let inner = inner.trim_end_matches('/');
+        let (name, mut attributes_text) =
+            inner.split_once(char::is_whitespace).unwrap_or((inner, ""));
+        let mut attributes = Vec::new();
+        loop {
+            attributes_text = attributes_text.trim_start();
+            if attributes_text.is_empty() {
+                break;
+            }
+            let (key, after) = attributes_text
+                .split_once("=\"")
+                .ok_or_else(|| malformed(format!("attribute without a value in {}", name)))?;
+            let (value, after) = after
+                .split_once('"')
+                .ok_or_else(|| malformed(format!("unclosed attribute in {}", name)))?;
+            let value = value
+                .replace("&quot;", "\"")
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&amp;", "&");
+            attributes.push((key.trim(), value));
+            attributes_text = after;
+        }
+        tags.push(Tag { name, attributes });
+    }
+    Ok(tags)
+}
+
+/// Player names are percent encoded UTF-8.
+fn percent_decode(text: &str) -> String {
+    let mut bytes = Vec::new();
+    let mut rest = text.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        let hex = after
+            .get(..2)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(decoded) if byte == b'%' => {
+                bytes.push(decoded);
+                rest = &after[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = after;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn mjlog_tile(id: usize, red_fives: bool) -> Result<MahjongTile, ImportError> {
+    if id >= 4 * TILE_KINDS {
+        return Err(malformed(format!("unknown tile id {}", id)));
+    }
+    let mut tile = index_to_tile(id / 4);
+    tile.is_red = red_fives && matches!(id, 16 | 52 | 88);
+    Ok(tile)
+}
+
+fn mjlog_tiles(ids: &[f64], red_fives: bool) -> Result<Vec<MahjongTile>, ImportError> {
+    let mut tiles = ids
+        .iter()
+        .map(|&id| mjlog_tile(id as usize, red_fives))
+        .collect::<Result<Vec<_>, _>>()?;
+    tiles.sort();
+    Ok(tiles)
+}
+
+/// Tile ids of a call packed into the `m` attribute of an N tag, with the caller's seat
+/// counted from the seat it came from.
+fn decode_meld(who: usize, m: usize) -> Result<(CallKind, usize, Vec<usize>), ImportError> {
+    let from = (who + (m & 3)) % 4;
+    if m & 0x4 != 0 {
+        let pattern = (m >> 10) / 3;
+        let base = (pattern / 7) * 9 + pattern % 7;
+        let ids = (0..3)
+            .map(|offset| (base + offset) * 4 + ((m >> (3 + 2 * offset)) & 3))
+            .collect();
+        Ok((CallKind::Chi, from, ids))
+    } else if m & 0x18 != 0 {
+        let kind = (m >> 9) / 3;
+        let unused = (m >> 5) & 3;
+        let ids = (0..4)
+            .filter(|&copy| m & 0x10 != 0 || copy != unused)
+            .map(|copy| kind * 4 + copy)
+            .collect();
+        match m & 0x8 != 0 {
+            true => Ok((CallKind::Pon, from, ids)),
+            false => Ok((CallKind::AddedKan, who, ids)),
+        }
+    } else if m & 0x20 != 0 {
+        Err(ImportError::Unsupported("three player games".to_string()))
+    } else {
+        let kind = (m >> 8) / 4;
+        let ids = (0..4).map(|copy| kind * 4 + copy).collect();
+        match m & 3 {
+            0 => Ok((CallKind::ClosedKan, who, ids)),
+            _ => Ok((CallKind::OpenKan, from, ids)),
+        }
+    }
+}
+
+fn deltas(tag: &Tag) -> Result<Vec<i32>, ImportError> {
+    Ok(tag
+        .numbers("sc")?
+        .chunks(2)
+        .map(|pair| (pair.get(1).copied().unwrap_or(0.0) * 100.0) as i32)
+        .collect())
+}
+
+fn mjlog_win(tag: &Tag, red_fives: bool) -> Result<GameEvent, ImportError> {
+    let seat = tag.number("who")?;
+    let from = tag.number("fromWho")?;
+    let winning_id = tag.number("machi")?;
+    let winning_tile = mjlog_tile(winning_id, red_fives)?;
+    let mut ids = tag.numbers("hai")?;
+    let position = ids
+        .iter()
+        .position(|&id| id as usize == winning_id)
+        .ok_or_else(|| malformed("winning tile is not in the winning hand"))?;
+    ids.remove(position);
+    let mut hand = Hand {
+        closed: mjlog_tiles(&ids, red_fives)?,
+        called: Vec::new(),
+    };
+    hand.closed.push(winning_tile);
+    if tag.get("m").is_some() {
+        for m in tag.numbers("m")? {
+            let (_, _, ids) = decode_meld(seat, m as usize)?;
+            let ids: Vec<f64> = ids.iter().map(|&id| id as f64).collect();
+            hand.called.push(mjlog_tiles(&ids, red_fives)?);
+        }
+    }
+
+    let mut yaku = Vec::new();
+    if tag.get("yaku").is_some() {
+        for pair in tag.numbers("yaku")?.chunks(2) {
+            if let [id, han] = *pair {
+                if han > 0.0 {
+                    yaku.push(Yaku {
+                        name: yaku_by_id(id as usize),
+                        han: han as i32,
+                    });
+                }
+            }
+        }
+    }
+    if tag.get("yakuman").is_some() {
+        for id in tag.numbers("yakuman")? {
+            yaku.push(Yaku {
+                name: yaku_by_id(id as usize),
+                han: 13,
+            });
+        }
+    }
+    let han: i32 = yaku.iter().map(|yaku| yaku.han).sum();
+    let ten = tag.numbers("ten")?;
+    let [fu, _, limit] = ten[..] else {
+        return Err(malformed(format!("AGARI ten=\"{:?}\"", ten)));
+    };
+    let fu = fu as i32;
+    let base_points = match limit as i32 {
+        0 => (fu << (2 + han.clamp(0, 16))).min(2000),
+        1 => 2000,
+        2 => 3000,
+        3 => 4000,
+        4 => 6000,
+        _ => 8000,
+    };
+    Ok(GameEvent::Win {
+        seat,
+        from: (from != seat).then_some(from),
+        hand: hand.to_string(),
+        winning_tile: winning_tile.to_string(),
+        score: HandScore {
+            han,
+            fu,
+            yaku,
+            base_points,
+        },
+    })
+}
+
+/// The final `owari` standings that close the game's last AGARI or RYUUKYOKU.
+fn mjlog_end(tag: &Tag, starting_points: i32) -> Result<Option<GameEvent>, ImportError> {
+    if tag.get("owari").is_none() {
+        return Ok(None);
+    }
+    let owari = tag.numbers("owari")?;
+    let points: Vec<i32> = owari
+        .chunks(2)
+        .map(|pair| (pair[0] * 100.0) as i32)
+        .collect();
+    let uma = owari
+        .chunks(2)
+        .zip(&points)
+        .map(|(pair, points)| {
+            (pair.get(1).copied().unwrap_or(0.0) * 1000.0).round() as i32 + starting_points - points
+        })
+        .collect();
+    Ok(Some(GameEvent::GameEnd { points, uma }))
+}
+
+/// Reads one game from Tenhou's mjlog XML.
+pub fn from_mjlog(xml: &str) -> Result<Vec<GameEvent>, ImportError> {
+    let mut events = Vec::new();
+    let mut red_fives = true;
+    let mut ruleset = "hanchan";
+    let mut names = vec![String::new(); 4];
+    let mut starting_points = None;
+
+    for tag in xml_tags(xml)? {
+        match tag.name {
+            "GO" => {
+                let kind = tag.number("type")?;
+                if kind & 0x10 != 0 {
+                    return Err(ImportError::Unsupported("three player games".to_string()));
+                }
+                red_fives = kind & 0x02 == 0;
+                ruleset = if kind & 0x08 != 0 {
+                    "hanchan"
+                } else {
+                    "tonpuusen"
+                };
+            }
+            // Players reconnecting repeat the tag with only their own name
+            "UN" if tag.get("n0").is_some() && starting_points.is_none() => {
+                for (seat, name) in names.iter_mut().enumerate() {
+                    *name = percent_decode(tag.get(&format!("n{}", seat)).unwrap_or(""));
+                }
+            }
+            "INIT" => {
+                let seed = tag.numbers("seed")?;
+                if seed.len() < 6 {
+                    return Err(malformed("INIT seed has fewer than six values"));
+                }
+                let points: Vec<i32> = tag
+                    .numbers("ten")?
+                    .iter()
+                    .map(|&points| (points * 100.0) as i32)
+                    .collect();
+                if starting_points.is_none() {
+                    starting_points = Some(points.first().copied().unwrap_or(25000));
+                    events.push(GameEvent::GameStart {
+                        seed: 0,
+                        ruleset: ruleset.to_string(),
+                        strategies: names.clone(),
+                    });
+                }
+                let hands = (0..4)
+                    .map(|seat| {
+                        Ok(format_tiles(&mjlog_tiles(
+                            &tag.numbers(&format!("hai{}", seat))?,
+                            red_fives,
+                        )?))
+                    })
+                    .collect::<Result<_, ImportError>>()?;
+                events.push(GameEvent::Deal {
+                    round: seed[0] as u8,
+                    dealer: tag.number("oya")?,
+                    points,
+                    hands,
+                });
+                events.push(GameEvent::DoraFlip {
+                    indicator: mjlog_tile(seed[5] as usize, red_fives)?.to_string(),
+                });
+            }
+            "N" => {
+                let seat = tag.number("who")?;
+                let (kind, from, ids) = decode_meld(seat, tag.number("m")?)?;
+                let ids: Vec<f64> = ids.iter().map(|&id| id as f64).collect();
+                events.push(GameEvent::Call {
+                    seat,
+                    from,
+                    kind,
+                    tiles: format_tiles(&mjlog_tiles(&ids, red_fives)?),
+                });
+            }
+            "REACH" if tag.get("step") == Some("1") => events.push(GameEvent::Riichi {
+                seat: tag.number("who")?,
+            }),
+            "DORA" => events.push(GameEvent::DoraFlip {
+                indicator: mjlog_tile(tag.number("hai")?, red_fives)?.to_string(),
+            }),
+            "AGARI" => {
+                events.push(mjlog_win(&tag, red_fives)?);
+                events.push(GameEvent::Payment {
+                    deltas: deltas(&tag)?,
+                });
+                events.extend(mjlog_end(&tag, starting_points.unwrap_or(25000))?);
+            }
+            "RYUUKYOKU" => {
+                // Nagashi mangan is paid at an exhaustive draw like tenpai is
+                match tag.get("type").filter(|&kind| kind != "nm") {
+                    Some(kind) => events.push(GameEvent::AbortiveDraw {
+                        reason: abortive_draw_reason(kind).to_string(),
+                    }),
+                    None => events.push(GameEvent::ExhaustiveDraw {
+                        tenpai: (0..4)
+                            .filter(|seat| tag.get(&format!("hai{}", seat)).is_some())
+                            .collect(),
+                    }),
+                }
+                events.push(GameEvent::Payment {
+                    deltas: deltas(&tag)?,
+                });
+                events.extend(mjlog_end(&tag, starting_points.unwrap_or(25000))?);
+            }
+            name => {
+                let mut chars = name.chars();
+                let (Some(action), Ok(id)) = (chars.next(), chars.as_str().parse::<usize>()) else {
+                    continue;
+                };
+                let tile = mjlog_tile(id, red_fives)?.to_string();
+                if let Some(seat) = "TUVW".find(action) {
+                    events.push(GameEvent::Draw { seat, tile });
+                } else if let Some(seat) = "DEFG".find(action) {
+                    events.push(GameEvent::Discard { seat, tile });
+                }
+            }
+        }
+    }
+    if starting_points.is_none() {
+        return Err(malformed("no rounds in log"));
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+const MJLOG: &str = r#"<mjloggm ver="2.3"><SHUFFLE seed="mt19937ar-sha512-n288-base64,x" ref=""/>
+<GO type="169" lobby="0"/><UN n0="%41%6C%69%63%65" n1="B" n2="C" n3="D" dan="0,0,0,0" rate="1500,1500,1500,1500" sx="M,M,M,M"/>
+<TAIKYOKU oya="0"/>
+<INIT seed="0,0,0,1,2,120" ten="250,250,250,250" oya="0" hai0="0,1,2,4,8,12,16,20,24,28,32,33,34" hai1="36,40,44,48,52,56,60,64,68,72,76,80,84" hai2="37,41,45,49,53,57,61,65,69,73,77,81,85" hai3="38,39,42,46,50,54,58,62,66,70,74,78,82"/>
+<T108/><D108/><U109/><E36/><N who="3" m="13866"/><G82/>
+<T3/><AGARI ba="0,0" hai="0,1,2,3,4,8,12,16,20,24,28,32,33,34" machi="3" ten="40,48000,5" yaku="" yakuman="46" doraHai="120" who="0" fromWho="0" sc="250,480,250,-160,250,-160,250,-160" owari="730,63.0,90,-21.0,90,-21.0,90,-21.0"/>
+</mjloggm>"#;
+
+#[test]
+fn test_decode_meld() {
+    // Pon of 1p called from across, leaving out the copy with id 37
+    let (kind, from, ids) = decode_meld(3, 13866).unwrap();
+    assert_eq!((kind, from, ids), (CallKind::Pon, 1, vec![36, 38, 39]));
+    // Chi of 1m-2m-3m from the left, the first copy of each
+    let (kind, from, ids) = decode_meld(1, 0x4 | 3).unwrap();
+    assert_eq!((kind, from, ids), (CallKind::Chi, 0, vec![0, 4, 8]));
+    // Closed kan of 1z
+    let (kind, from, ids) = decode_meld(3, 108 << 8).unwrap();
+    assert_eq!(
+        (kind, from, ids),
+        (CallKind::ClosedKan, 3, vec![108, 109, 110, 111])
+    );
+}
+
+#[test]
+fn test_import_mjlog() {
+    let events = import_log(MJLOG).unwrap();
+    assert_eq!(
+        events[0],
+        GameEvent::GameStart {
+            seed: 0,
+            ruleset: "hanchan".to_string(),
+            strategies: vec!["Alice".into(), "B".into(), "C".into(), "D".into()],
+        }
+    );
+    let GameEvent::Deal { hands, .. } = &events[1] else {
+        panic!("expected a deal, got {:?}", events[1]);
+    };
+    assert_eq!(hands[0], "1112340678999m");
+    assert_eq!(
+        events[2],
+        GameEvent::DoraFlip {
+            indicator: "4z".to_string()
+        }
+    );
+    assert_eq!(
+        events[7],
+        GameEvent::Call {
+            seat: 3,
+            from: 1,
+            kind: CallKind::Pon,
+            tiles: "111p".to_string(),
+        }
+    );
+    let GameEvent::Win {
+        seat, from, score, ..
+    } = &events[events.len() - 3]
+    else {
+        panic!("expected a win");
+    };
+    assert_eq!((*seat, *from), (0, None));
+    assert_eq!(score.base_points, 8000);
+    assert_eq!(score.yaku[0].name, "junsei chuuren poutou");
+    assert_eq!(
+        events.last(),
+        Some(&GameEvent::GameEnd {
+            points: vec![73000, 9000, 9000, 9000],
+            uma: vec![15000, -5000, -5000, -5000],
+        })
+    );
+    let report = crate::log_replay::replay_log(&events);
+    assert!(report.violations.is_empty(), "{:?}", report.violations);
+    assert_eq!((report.actions, report.wins), (7, 1));
+}
@@ -0,0 +1,72 @@
+//! Riichi mahjong tiles, hand analysis and scoring, the game engine the simulator plays and
+//! the strategies that play it.
+//!
+//! ```
+//! use mahjong_simulator::scoring::score_hand;
+//! use mahjong_simulator::types::tile_counts::{shanten, to_counts, waits};
+//! use mahjong_simulator::types::{Hand, SeatWind};
+//!
+//! let hand: Hand = "123m456p789s11222z".parse().unwrap();
+//! let counts = to_counts(&hand.closed[..13]);
+//! assert_eq!(shanten(&counts), 0);
+//! assert_eq!(waits(&counts).len(), 2);
+//!
+//! // Won by drawing the last 2z
+//! let score = score_hand(&hand.closed, &[], true, &SeatWind::East);
+//! assert_eq!((score.han, score.fu), (1, 40));
+//! ```
+//!
+//! A bot is anything implementing [`types::Strategy`]; [`engine::simulate_game`] seats four
+//! of them at a table.
+
+/// PNG and SVG charts of a run, built with the `charts` feature.
+#[cfg(feature = "charts")]
+pub mod charts;
+/// Plays whole games between four strategies.
+pub mod engine;
+/// What happened in a game, as JSON lines.
+pub mod event_log;
+/// Experiment files and the runs they expand into, built with the `experiments` feature.
+#[cfg(feature = "experiments")]
+pub mod experiment;
+/// The C interface to the hand evaluator and scorer, built with the `ffi` feature.
+#[cfg(feature = "ffi")]
+pub mod ffi;
+/// Reads Tenhou mjlog files into event logs.
+pub mod import;
+/// Replays a recorded game from one seat and compares a strategy's choices with it.
+pub mod log_analysis;
+/// Checks recorded games against the rules.
+pub mod log_replay;
+/// Results files of a run, written and read back.
+#[cfg(feature = "experiments")]
+pub mod output;
+/// The Python module, built with the `python` feature.
+#[cfg(feature = "python")]
+mod python;
+/// Draws tiles, hands and tables for the terminal.
+pub mod render;
+/// Plays an experiment into its results files, resumes it and merges its shards.
+#[cfg(feature = "experiments")]
+pub mod run;
+/// Han, fu and base points of a winning hand.
+pub mod scoring;
+/// Per-game seeds derived from a master seed.
+pub mod seeding;
+/// Confidence intervals, paired t-tests and the stopping rule of a sequential run.
+#[cfg(feature = "experiments")]
+pub mod significance;
+/// Per-player statistics read off game events.
+pub mod stats;
+/// The strategies that come with the simulator.
+pub mod strategies;
+/// Converts between event logs and Tenhou.net/6 JSON.
+pub mod tenhou;
+/// Elo and dan ratings of strategies over round-robin and Swiss tournaments.
+#[cfg(feature = "experiments")]
+pub mod tournament;
+/// Tiles, hands, notation, shanten and waits, and the types games are played with.
+pub mod types;
+/// The JavaScript API, built with the `wasm` feature.
+#[cfg(feature = "wasm")]
+mod wasm;
@@ -0,0 +1,370 @@
+use crate::event_log::*;
+use crate::log_replay::TableState;
+use crate::types::mahjong_tile::*;
+use crate::types::notation::format_tiles;
+use crate::types::tile_counts::*;
+use crate::types::*;
+use std::collections::BTreeMap;
+use std::fmt;
+
+// Walks a recorded game from one seat and asks a strategy about every choice that seat
+// made: each discard, whether to tsumo or declare riichi when it could, and whether to pon
+// or chi a discard it could call. Discards made after riichi are forced and not counted.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Decision {
+    Discard,
+    Tsumo,
+    Riichi,
+    Pon,
+    Chi,
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Decision::Discard => "discard",
+            Decision::Tsumo => "tsumo",
+            Decision::Riichi => "riichi",
+            Decision::Pon => "pon",
+            Decision::Chi => "chi",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Disagreement {
+    pub game: usize,
+    pub round: usize,
+    /// How many discards the seat had made in the round, plus one
+    pub turn: usize,
+    pub decision: Decision,
+    /// The seat's hand in MPSZ notation, with the tile just drawn last
+    pub hand: String,
+    pub dora: String,
+    pub played: String,
+    pub suggested: String,
+}
+
+impl fmt::Display for Disagreement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "game {} round {} turn {}, {} with {} (dora {}): played {}, strategy {}",
+            self.game,
+            self.round,
+            self.turn,
+            self.decision,
+            self.hand,
+            self.dora,
+            self.played,
+            self.suggested
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DecisionReport {
+    /// Agreed and total decisions of each kind
+    pub decisions: BTreeMap<Decision, (usize, usize)>,
+    pub disagreements: Vec<Disagreement>,
+}
+
+impl DecisionReport {
+    pub fn agreed(&self) -> usize {
+        self.decisions.values().map(|(agreed, _)| agreed).sum()
+    }
+
+    pub fn total(&self) -> usize {
+        self.decisions.values().map(|(_, total)| total).sum()
+    }
+
+    pub fn add(&mut self, other: &DecisionReport) {
+        for (decision, (agreed, total)) in &other.decisions {
+            let entry = self.decisions.entry(*decision).or_default();
+            entry.0 += agreed;
+            entry.1 += total;
+        }
+        self.disagreements
+            .extend(other.disagreements.iter().cloned());
+    }
+}
+
+fn yes_no(answer: bool) -> String {
+    if answer { "yes" } else { "no" }.to_string()
+}
+
+struct Walk<'a> {
+    seat: usize,
+    strategy: &'a dyn Strategy,
+    game: usize,
+    round: usize,
+    round_number: u8,
+    turn: usize,
+    discards: Vec<Vec<MahjongTile>>,
+    in_riichi: bool,
+    report: DecisionReport,
+}
+
+impl Walk<'_> {
+    /// What the strategy sees: the closed hand sorted with a just drawn tile last, dora
+    /// marked, and every seat's discards.
+    fn input(&self, state: &TableState) -> StrategyInput {
+        let mut hand = state.hands[self.seat].clone();
+        let drawn = match state.last_draw {
+            Some((seat, tile)) if seat == self.seat => {
+                let position = hand.iter().rposition(|other| *other == tile);
+                position.map(|position| hand.remove(position))
+            }
+            _ => None,
+        };
+        hand.sort();
+        hand.extend(drawn);
+        for tile in hand.iter_mut() {
+            tile.is_dora = state.dora.contains(tile);
+        }
+        let seat_wind = match (self.seat + 4 - state.dealer) % 4 {
+            0 => SeatWind::East,
+            1 => SeatWind::South,
+            2 => SeatWind::West,
+            _ => SeatWind::North,
+        };
+        StrategyInput {
+            hand,
+            discards: self.discards.clone(),
+            seat_wind,
+            round_number: self.round_number,
+            params: self.strategy.params(),
+        }
+    }
+
+    fn record(
+        &mut self,
+        state: &TableState,
+        decision: Decision,
+        input: &StrategyInput,
+        played: String,
+        suggested: String,
+    ) {
+        let entry = self.report.decisions.entry(decision).or_default();
+        entry.1 += 1;
+        if played == suggested {
+            entry.0 += 1;
+            return;
+        }
+        let hand = Hand {
+            closed: input.hand.clone(),
+            called: state.melds[self.seat]
+                .iter()
+                .map(|(_, meld)| meld.clone())
+                .collect(),
+        };
+        self.report.disagreements.push(Disagreement {
+            game: self.game,
+            round: self.round,
+            turn: self.turn,
+            decision,
+            hand: hand.to_string(),
+            dora: format_tiles(&state.dora),
+            played,
+            suggested,
+        });
+    }
+
+    /// Decisions the seat faces before `event` is played.
+    fn before(&mut self, state: &TableState, event: &GameEvent, previous: Option<&GameEvent>) {
+        match event {
+            GameEvent::Riichi { seat } if *seat == self.seat => {
+                let input = self.input(state);
+                let suggested = yes_no(self.strategy.riichi(&input));
+                self.record(state, Decision::Riichi, &input, yes_no(true), suggested);
+            }
+            GameEvent::Discard { seat, tile } if *seat == self.seat && !self.in_riichi => {
+                let input = self.input(state);
+                let declared =
+                    matches!(previous, Some(GameEvent::Riichi { seat }) if *seat == self.seat);
+                if !declared && self.could_riichi(state, &input) {
+                    let suggested = yes_no(self.strategy.riichi(&input));
+                    self.record(state, Decision::Riichi, &input, yes_no(false), suggested);
+                }
+                let choice = self.strategy.discard(&input);
+                let suggested = match input.hand.get(choice) {
+                    Some(tile) => tile.to_string(),
+                    None => format!("tile {} of {}", choice, input.hand.len()),
+                };
+                // Red and plain fives count as the same choice
+                let played = match tile.parse::<MahjongTile>() {
+                    Ok(played) if input.hand.get(choice) == Some(&played) => suggested.clone(),
+                    _ => tile.clone(),
+                };
+                self.record(state, Decision::Discard, &input, played, suggested);
+            }
+            _ => {}
+        }
+    }
+
+    /// Decisions the seat faces once `event` has been played, answered by `next`.
+    fn after(&mut self, state: &TableState, event: &GameEvent, next: Option<&GameEvent>) {
+        match event {
+            GameEvent::Draw { seat, .. } if *seat == self.seat => {
+                if !is_agari(&to_counts(&state.hands[self.seat])) {
+                    return;
+                }
+                let input = self.input(state);
+                let won = matches!(next, Some(GameEvent::Win { seat, from: None, .. }) if *seat == self.seat);
+                let suggested = yes_no(self.strategy.tsumo(&input));
+                self.record(state, Decision::Tsumo, &input, yes_no(won), suggested);
+            }
+            GameEvent::Discard { seat, tile } if *seat != self.seat && !self.in_riichi => {
+                let Ok(tile) = tile.parse::<MahjongTile>() else {
+                    return;
+                };
+                let own = self.seat;
+                let called = |kind: CallKind| {
+                    matches!(next, Some(GameEvent::Call { seat, kind: call, .. })
+                        if *seat == own && *call == kind)
+                };
+                let hand = &state.hands[self.seat];
+                if can_pon(hand, &tile) {
+                    let input = self.input(state);
+                    let suggested = yes_no(self.strategy.call_pon(&input));
+                    let played = yes_no(called(CallKind::Pon));
+                    self.record(state, Decision::Pon, &input, played, suggested);
+                }
+                if (seat + 1) % 4 == self.seat && can_chi(hand, &tile) {
+                    let input = self.input(state);
+                    let suggested = yes_no(self.strategy.call_chi(&input));
+                    let played = yes_no(called(CallKind::Chi));
+                    self.record(state, Decision::Chi, &input, played, suggested);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Riichi needs a closed hand that some discard leaves tenpai.
+    fn could_riichi(&self, state: &TableState, input: &StrategyInput) -> bool {
+        state.melds[self.seat]
+            .iter()
+            .all(|(kind, _)| *kind == CallKind::ClosedKan)
+            && shanten(&to_counts(&input.hand)) <= 0
+    }
+}
+
+/// Asks `strategy` about every decision `seat` made in one game and compares its answers
+/// with what was played.
+pub fn analyze_decisions(
+    events: &[GameEvent],
+    game: usize,
+    seat: usize,
+    strategy: &dyn Strategy,
+) -> DecisionReport {
+    let mut walk = Walk {
+        seat,
+        strategy,
+        game,
+        round: 0,
+        round_number: 0,
+        turn: 1,
+        discards: vec![Vec::new(); 4],
+        in_riichi: false,
+        report: DecisionReport::default(),
+    };
+    let mut state = TableState::default();
+    let mut broken = false;
+    for (index, event) in events.iter().enumerate() {
+        if let GameEvent::Deal { round, .. } = event {
+            walk.round += 1;
+            walk.round_number = *round;
+            walk.turn = 1;
+            walk.discards = vec![Vec::new(); 4];
+            walk.in_riichi = false;
+            broken = false;
+        }
+        // A round that breaks the rules can't be followed any further
+        if broken {
+            continue;
+        }
+        let previous = index.checked_sub(1).map(|previous| &events[previous]);
+        walk.before(&state, event, previous);
+        if state.apply(event).is_err() {
+            broken = true;
+            continue;
+        }
+        walk.after(&state, event, events.get(index + 1));
+        if let GameEvent::Discard { seat, tile } = event {
+            if let Ok(tile) = tile.parse() {
+                walk.discards[*seat].push(tile);
+            }
+            if *seat == walk.seat {
+                walk.turn += 1;
+                walk.in_riichi |=
+                    matches!(previous, Some(GameEvent::Riichi { seat }) if *seat == walk.seat);
+            }
+        }
+    }
+    walk.report
+}
+
+#[test]
+fn test_analyze_decisions() {
+    use crate::strategies::strategy_by_name;
+    let events = vec![
+        GameEvent::Deal {
+            round: 0,
+            dealer: 0,
+            points: vec![25000; 4],
+            hands: [
+                "123m456p789s1122z",
+                "1112345678999m",
+                "19p19s123455667z",
+                "3344556677889p",
+            ]
+            .iter()
+            .map(|hand| hand.to_string())
+            .collect(),
+        },
+        GameEvent::DoraFlip {
+            indicator: "9s".to_string(),
+        },
+        GameEvent::Draw {
+            seat: 0,
+            tile: "7z".to_string(),
+        },
+        GameEvent::Discard {
+            seat: 0,
+            tile: "7z".to_string(),
+        },
+        GameEvent::Draw {
+            seat: 1,
+            tile: "2z".to_string(),
+        },
+        GameEvent::Discard {
+            seat: 1,
+            tile: "2z".to_string(),
+        },
+        GameEvent::Call {
+            seat: 0,
+            from: 1,
+            kind: CallKind::Pon,
+            tiles: "222z".to_string(),
+        },
+        GameEvent::Discard {
+            seat: 0,
+            tile: "1m".to_string(),
+        },
+    ];
+    let strategy = strategy_by_name("efficiency").unwrap();
+    let report = analyze_decisions(&events, 0, 0, &strategy);
+    // Both discards agree. Built-in strategies never call and always declare riichi, and
+    // the first discard left a closed tenpai hand that wasn't declared.
+    assert_eq!(report.decisions[&Decision::Discard], (2, 2));
+    assert_eq!(report.decisions[&Decision::Riichi], (0, 1));
+    assert_eq!(report.decisions[&Decision::Pon], (0, 1));
+    assert_eq!((report.agreed(), report.total()), (2, 4));
+    assert_eq!(
+        report.disagreements[1].to_string(),
+        "game 0 round 1 turn 2, pon with 123m456p789s1122z (dora 1s): played yes, strategy no"
+    );
+}
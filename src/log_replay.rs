@@ -0,0 +1,551 @@
+use crate::event_log::*;
+use crate::scoring::score_hand_with_dora;
+use crate::types::mahjong_tile::*;
+use crate::types::notation::parse_tiles;
+use crate::types::tile_counts::*;
+use crate::types::*;
+
+// A replay plays a game's events back against the rules: every draw, discard and call has
+// to be possible from the hands as they were dealt and played, and every win has to be a
+// complete hand. Wins are then scored again with `score_hand` and compared with the log.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Turn {
+    Draw(usize),
+    Discard(usize),
+    Over,
+}
+
+/// Hands, melds and turn order of the round being replayed.
+#[derive(Debug, Clone)]
+pub struct TableState {
+    pub hands: Vec<Vec<MahjongTile>>,
+    pub melds: Vec<Vec<(CallKind, Vec<MahjongTile>)>>,
+    pub dealer: usize,
+    /// Tiles made dora by the indicators flipped so far
+    pub dora: Vec<MahjongTile>,
+    pub last_draw: Option<(usize, MahjongTile)>,
+    /// The tile on the table that can still be called or ronned, and who it came from
+    pub last_discard: Option<(usize, MahjongTile)>,
+    seen: TileCounts,
+    turn: Turn,
+    declared_riichi: [bool; 4],
+}
+
+impl Default for TableState {
+    fn default() -> TableState {
+        TableState {
+            hands: vec![Vec::new(); 4],
+            melds: vec![Vec::new(); 4],
+            dealer: 0,
+            dora: Vec::new(),
+            last_draw: None,
+            last_discard: None,
+            seen: [0; TILE_KINDS],
+            turn: Turn::Over,
+            declared_riichi: [false; 4],
+        }
+    }
+}
+
+fn parse(notation: &str) -> Result<Vec<MahjongTile>, String> {
+    parse_tiles(notation).map_err(|error| error.to_string())
+}
+
+fn parse_one(notation: &str) -> Result<MahjongTile, String> {
+    notation
+        .parse::<MahjongTile>()
+        .map_err(|error| error.to_string())
+}
+
+/// Takes a copy of `tile` out of the hand, the red one if the tile is red.
+fn take_tile(hand: &mut Vec<MahjongTile>, tile: &MahjongTile) -> bool {
+    let position = hand
+        .iter()
+        .position(|other| other == tile && other.is_red == tile.is_red)
+        .or_else(|| hand.iter().position(|other| other == tile));
+    match position {
+        Some(position) => {
+            hand.remove(position);
+            true
+        }
+        None => false,
+    }
+}
+
+fn is_sequence(meld: &[MahjongTile]) -> bool {
+    let mut meld = meld.to_vec();
+    meld.sort();
+    meld.len() == 3
+        && !matches!(meld[0].suit, Suit::Kaze | Suit::Sangen)
+        && meld.iter().all(|tile| tile.suit == meld[0].suit)
+        && meld[1].value == meld[0].value + 1
+        && meld[2].value == meld[0].value + 2
+}
+
+fn is_set(meld: &[MahjongTile], size: usize) -> bool {
+    meld.len() == size && meld.iter().all(|tile| *tile == meld[0])
+}
+
+impl TableState {
+    fn expect(&self, turn: Turn, action: &str) -> Result<(), String> {
+        if self.turn == turn {
+            return Ok(());
+        }
+        let seat = match turn {
+            Turn::Draw(seat) | Turn::Discard(seat) => seat,
+            Turn::Over => 0,
+        };
+        Err(format!("seat {} {} out of turn", seat, action))
+    }
+
+    fn see(&mut self, tile: &MahjongTile) -> Result<(), String> {
+        let index = tile_index(tile);
+        self.seen[index] += 1;
+        if self.seen[index] > 4 {
+            return Err(format!("a fifth {} turned up", tile));
+        }
+        Ok(())
+    }
+
+    /// Plays one event on the table, or says which rule it breaks.
+    pub fn apply(&mut self, event: &GameEvent) -> Result<(), String> {
+        match event {
+            GameEvent::Deal { dealer, hands, .. } => {
+                *self = TableState {
+                    dealer: *dealer,
+                    turn: Turn::Draw(*dealer),
+                    ..TableState::default()
+                };
+                if hands.len() != 4 {
+                    return Err(format!("{} hands were dealt", hands.len()));
+                }
+                for (seat, hand) in hands.iter().enumerate() {
+                    self.hands[seat] = parse(hand)?;
+                    if self.hands[seat].len() != 13 {
+                        return Err(format!(
+                            "seat {} was dealt {} tiles",
+                            seat,
+                            self.hands[seat].len()
+                        ));
+                    }
+                    for tile in self.hands[seat].clone() {
+                        self.see(&tile)?;
+                    }
+                }
+            }
+            GameEvent::DoraFlip { indicator } => {
+                let indicator = parse_one(indicator)?;
+                self.see(&indicator)?;
+                self.dora.push(dora_for(&indicator));
+            }
+            GameEvent::Draw { seat, tile } => {
+                let tile = parse_one(tile)?;
+                self.expect(Turn::Draw(*seat), &format!("drew {}", tile))?;
+                self.see(&tile)?;
+                self.hands[*seat].push(tile);
+                self.last_draw = Some((*seat, tile));
+                self.last_discard = None;
+                self.turn = Turn::Discard(*seat);
+            }
+            GameEvent::Discard { seat, tile } => {
+                let tile = parse_one(tile)?;
+                self.expect(Turn::Discard(*seat), &format!("discarded {}", tile))?;
+                if !take_tile(&mut self.hands[*seat], &tile) {
+                    return Err(format!(
+                        "seat {} discarded {} without holding it",
+                        seat, tile
+                    ));
+                }
+                if self.declared_riichi[*seat] {
+                    self.declared_riichi[*seat] = false;
+                    if wait_mask(&to_counts(&self.hands[*seat])) == 0 {
+                        return Err(format!("seat {} declared riichi without tenpai", seat));
+                    }
+                }
+                self.last_draw = None;
+                self.last_discard = Some((*seat, tile));
+                self.turn = Turn::Draw((seat + 1) % 4);
+            }
+            GameEvent::Riichi { seat } => {
+                self.expect(Turn::Discard(*seat), "declared riichi")?;
+                if self.melds[*seat]
+                    .iter()
+                    .any(|(kind, _)| *kind != CallKind::ClosedKan)
+                {
+                    return Err(format!("seat {} declared riichi with an open hand", seat));
+                }
+                self.declared_riichi[*seat] = true;
+            }
+            GameEvent::Call {
+                seat,
+                from,
+                kind,
+                tiles,
+            } => self.call(*seat, *from, *kind, &parse(tiles)?)?,
+            GameEvent::Win {
+                seat,
+                from,
+                winning_tile,
+                ..
+            } => {
+                let tile = parse_one(winning_tile)?;
+                self.winning_hand(*seat, *from, &tile)?;
+                self.turn = Turn::Over;
+            }
+            GameEvent::ExhaustiveDraw { .. } | GameEvent::AbortiveDraw { .. } => {
+                self.turn = Turn::Over
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn call(
+        &mut self,
+        seat: usize,
+        from: usize,
+        kind: CallKind,
+        meld: &[MahjongTile],
+    ) -> Result<(), String> {
+        let shown = crate::types::notation::format_tiles(meld);
+        match kind {
+            CallKind::Chi | CallKind::Pon | CallKind::OpenKan => {
+                let Some((discarder, called)) = self.last_discard else {
+                    return Err(format!(
+                        "seat {} called {} with nothing to call",
+                        seat, shown
+                    ));
+                };
+                if discarder != from || from == seat {
+                    return Err(format!(
+                        "seat {} called {} from seat {}, but seat {} discarded last",
+                        seat, shown, from, discarder
+                    ));
+                }
+                let valid = match kind {
+                    CallKind::Chi => from == (seat + 3) % 4 && is_sequence(meld),
+                    CallKind::Pon => is_set(meld, 3),
+                    _ => is_set(meld, 4),
+                };
+                if !valid {
+                    return Err(format!(
+                        "seat {} can't call {} from seat {}",
+                        seat, shown, from
+                    ));
+                }
+                let mut rest = meld.to_vec();
+                if !take_tile(&mut rest, &called) {
+                    return Err(format!(
+                        "seat {} called {} without the discarded {}",
+                        seat, shown, called
+                    ));
+                }
+                for tile in &rest {
+                    if !take_tile(&mut self.hands[seat], tile) {
+                        return Err(format!(
+                            "seat {} called {} without holding {}",
+                            seat, shown, tile
+                        ));
+                    }
+                }
+                self.last_discard = None;
+                self.turn = match kind {
+                    CallKind::OpenKan => Turn::Draw(seat),
+                    _ => Turn::Discard(seat),
+                };
+            }
+            CallKind::ClosedKan => {
+                self.expect(Turn::Discard(seat), &format!("called {}", shown))?;
+                if !is_set(meld, 4) {
+                    return Err(format!("seat {} can't call {} as a kan", seat, shown));
+                }
+                for tile in meld {
+                    if !take_tile(&mut self.hands[seat], tile) {
+                        return Err(format!(
+                            "seat {} called {} without holding {}",
+                            seat, shown, tile
+                        ));
+                    }
+                }
+                self.turn = Turn::Draw(seat);
+            }
+            CallKind::AddedKan => {
+                self.expect(Turn::Discard(seat), &format!("called {}", shown))?;
+                let pon = self.melds[seat]
+                    .iter()
+                    .position(|(kind, tiles)| *kind == CallKind::Pon && tiles[0] == meld[0]);
+                let Some(pon) = pon.filter(|_| is_set(meld, 4)) else {
+                    return Err(format!(
+                        "seat {} added to {} without a pon of it",
+                        seat, shown
+                    ));
+                };
+                if !take_tile(&mut self.hands[seat], &meld[0]) {
+                    return Err(format!(
+                        "seat {} called {} without holding {}",
+                        seat, shown, meld[0]
+                    ));
+                }
+                self.melds[seat].remove(pon);
+                // The added tile can be robbed with a ron before the replacement draw
+                self.last_discard = Some((seat, meld[0]));
+                self.turn = Turn::Draw(seat);
+            }
+        }
+        self.last_draw = None;
+        self.melds[seat].push((kind, meld.to_vec()));
+        Ok(())
+    }
+
+    /// The closed part of a winning hand with the winning tile last.
+    fn winning_hand(
+        &self,
+        seat: usize,
+        from: Option<usize>,
+        tile: &MahjongTile,
+    ) -> Result<Vec<MahjongTile>, String> {
+        let mut closed = self.hands[seat].clone();
+        match from {
+            None => {
+                self.expect(Turn::Discard(seat), "declared tsumo")?;
+                if self.last_draw != Some((seat, *tile)) {
+                    return Err(format!(
+                        "seat {} won by tsumo on {} without drawing it",
+                        seat, tile
+                    ));
+                }
+                take_tile(&mut closed, tile);
+            }
+            Some(from) => {
+                if self.last_discard != Some((from, *tile)) || from == seat {
+                    return Err(format!(
+                        "seat {} won by ron on {} which seat {} didn't just discard",
+                        seat, tile, from
+                    ));
+                }
+            }
+        }
+        closed.sort();
+        closed.push(*tile);
+        if !is_agari(&to_counts(&closed)) {
+            return Err(format!("seat {} won with an incomplete hand", seat));
+        }
+        Ok(closed)
+    }
+
+    /// Our own score for a win the state has already accepted.
+    pub fn rescore(&self, seat: usize, from: Option<usize>, tile: &MahjongTile) -> HandScore {
+        let mut closed = self.hands[seat].clone();
+        if from.is_none() {
+            take_tile(&mut closed, tile);
+        }
+        closed.sort();
+        closed.push(*tile);
+        let open: Vec<Vec<MahjongTile>> = self.melds[seat]
+            .iter()
+            .filter(|(kind, _)| *kind != CallKind::ClosedKan)
+            .map(|(_, meld)| meld.clone())
+            .collect();
+        let seat_wind = match (seat + 4 - self.dealer) % 4 {
+            0 => SeatWind::East,
+            1 => SeatWind::South,
+            2 => SeatWind::West,
+            _ => SeatWind::North,
+        };
+        score_hand_with_dora(&closed, &open, from.is_none(), &seat_wind, &self.dora)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReplayReport {
+    pub rounds: usize,
+    pub actions: usize,
+    /// Rules the log broke, at most one per round since the rest of it can't be followed
+    pub violations: Vec<String>,
+    pub wins: usize,
+    /// Wins our scoring gives different base points than the log
+    pub score_differences: Vec<String>,
+}
+
+impl ReplayReport {
+    pub fn add(&mut self, other: &ReplayReport) {
+        self.rounds += other.rounds;
+        self.actions += other.actions;
+        self.violations.extend(other.violations.iter().cloned());
+        self.wins += other.wins;
+        self.score_differences
+            .extend(other.score_differences.iter().cloned());
+    }
+}
+
+/// Replays one game's events, checking every action and scoring every win again.
+pub fn replay_log(events: &[GameEvent]) -> ReplayReport {
+    let mut report = ReplayReport::default();
+    let mut state = TableState::default();
+    let mut broken = false;
+    for event in events {
+        if let GameEvent::Deal { .. } = event {
+            report.rounds += 1;
+            broken = false;
+        }
+        if broken {
+            continue;
+        }
+        if let Err(error) = state.apply(event) {
+            report
+                .violations
+                .push(format!("round {}: {}", report.rounds, error));
+            broken = true;
+            continue;
+        }
+        match event {
+            GameEvent::Draw { .. }
+            | GameEvent::Discard { .. }
+            | GameEvent::Call { .. }
+            | GameEvent::Riichi { .. } => report.actions += 1,
+            GameEvent::Win {
+                seat,
+                from,
+                winning_tile,
+                score,
+                ..
+            } => {
+                report.wins += 1;
+                let tile = parse_one(winning_tile).expect("the state parsed it already");
+                let ours = state.rescore(*seat, *from, &tile);
+                if ours.base_points != score.base_points {
+                    report.score_differences.push(format!(
+                        "round {}: seat {} won for {} base points, we score {} ({} han {} fu)",
+                        report.rounds, seat, score.base_points, ours.base_points, ours.han, ours.fu
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+fn play(events: &[GameEvent]) -> ReplayReport {
+    let mut all = vec![GameEvent::Deal {
+        round: 0,
+        dealer: 0,
+        points: vec![25000; 4],
+        hands: [
+            "234m456p789s1122z",
+            "1112345678999m",
+            "19p19s123455667z",
+            "3344556677889p",
+        ]
+        .iter()
+        .map(|hand| hand.to_string())
+        .collect(),
+    }];
+    all.extend(events.iter().cloned());
+    replay_log(&all)
+}
+
+#[test]
+fn test_replay_rules() {
+    let draw = |seat: usize, tile: &str| GameEvent::Draw {
+        seat,
+        tile: tile.to_string(),
+    };
+    let discard = |seat: usize, tile: &str| GameEvent::Discard {
+        seat,
+        tile: tile.to_string(),
+    };
+    let report = play(&[
+        draw(0, "2z"),
+        discard(0, "2m"),
+        GameEvent::Call {
+            seat: 1,
+            from: 0,
+            kind: CallKind::Chi,
+            tiles: "222m".to_string(),
+        },
+    ]);
+    assert_eq!(report.actions, 2);
+    assert_eq!(
+        report.violations,
+        vec!["round 1: seat 1 can't call 222m from seat 0"]
+    );
+
+    let report = play(&[draw(0, "2z"), discard(0, "5z")]);
+    assert_eq!(
+        report.violations,
+        vec!["round 1: seat 0 discarded 5z without holding it"]
+    );
+    let report = play(&[draw(1, "2z")]);
+    assert_eq!(
+        report.violations,
+        vec!["round 1: seat 1 drew 2z out of turn"]
+    );
+
+    // Seat 3 pons the 9p, discards, and seat 1 wins on it afterwards
+    let report = play(&[
+        draw(0, "9p"),
+        discard(0, "9p"),
+        GameEvent::Call {
+            seat: 3,
+            from: 0,
+            kind: CallKind::Pon,
+            tiles: "999p".to_string(),
+        },
+        discard(3, "9p"),
+        GameEvent::Win {
+            seat: 1,
+            from: Some(3),
+            hand: "1112345678999m9p".to_string(),
+            winning_tile: "9p".to_string(),
+            score: HandScore {
+                han: 0,
+                fu: 0,
+                yaku: Vec::new(),
+                base_points: 0,
+            },
+        },
+    ]);
+    assert_eq!(
+        report.violations,
+        vec!["round 1: seat 3 called 999p without holding 9p"]
+    );
+}
+
+#[test]
+fn test_replay_rescores_wins() {
+    let report = play(&[
+        GameEvent::DoraFlip {
+            indicator: "9s".to_string(),
+        },
+        GameEvent::Draw {
+            seat: 0,
+            tile: "9m".to_string(),
+        },
+        GameEvent::Discard {
+            seat: 0,
+            tile: "9m".to_string(),
+        },
+        GameEvent::Win {
+            seat: 1,
+            from: Some(0),
+            hand: "1112345678999m9m".to_string(),
+            winning_tile: "9m".to_string(),
+            score: HandScore {
+                han: 13,
+                fu: 0,
+                yaku: Vec::new(),
+                base_points: 8000,
+            },
+        },
+    ]);
+    assert!(report.violations.is_empty());
+    assert_eq!(report.wins, 1);
+    // Our scoring has no yakuman, so nine gates only counts as a chinitsu haneman
+    assert_eq!(
+        report.score_differences,
+        vec!["round 1: seat 1 won for 8000 base points, we score 3000 (6 han 40 fu)"]
+    );
+}
@@ -1,657 +1,591 @@
-use core::cmp::Reverse;
-use rayon::prelude::*;
-use std::collections::HashMap;
+use clap::{Parser, ValueEnum};
+use cli::*;
+use mahjong_simulator::engine::simulate_game;
+use mahjong_simulator::event_log::*;
+use mahjong_simulator::experiment::*;
+use mahjong_simulator::import::import_log;
+use mahjong_simulator::log_analysis::*;
+use mahjong_simulator::log_replay::*;
+use mahjong_simulator::output::*;
+use mahjong_simulator::render::*;
+use mahjong_simulator::run::*;
+use mahjong_simulator::scoring::score_hand;
+use mahjong_simulator::seeding::*;
+use mahjong_simulator::stats::*;
+use mahjong_simulator::strategies::*;
+use mahjong_simulator::tenhou::to_tenhou;
+use mahjong_simulator::tournament::*;
+use mahjong_simulator::types::suit_table::SuitTables;
+use mahjong_simulator::types::tile_counts::*;
+use mahjong_simulator::types::wait::*;
+use mahjong_simulator::types::*;
+use progress::*;
+use report::*;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
 use std::time::Instant;
-mod types;
-use num_traits::pow;
-use std::fs::File;
-use std::io::{BufWriter, Result, Write};
-use types::mahjong_tile::*;
-use types::*;
-const ROUNDS: u8 = 4 * 2;
-const GAMES: usize = 1000;
-const UMA: bool = true;
-const TOBI: bool = false;
-fn main() {
-    let start_time = Instant::now();
-    let game_results: Vec<GameResult> = (0..=GAMES).par_bridge().map(|_| simulate_game()).collect();
+mod cli;
+mod progress;
+mod report;
 
-    match write_game_results("1000_games.dat", &game_results) {
-        Ok(()) => {}
-        Err(error) => {
-            println!("Error occured while writing game results: {}", error);
-        }
+fn main() {
+    let cli = Cli::parse();
+    if let Some(threads) = cli.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .expect("thread pool is only configured once");
+    }
+    // Merging and exporting don't look at hands
+    if !matches!(
+        cli.command,
+        Some(Command::Merge(_)) | Some(Command::TenhouExport(_))
+    ) {
+        cache_suit_tables();
+    }
+    match cli.command {
+        None => run_simulate(&cli.simulate),
+        Some(Command::Simulate(args)) => run_simulate(&args),
+        Some(Command::AnalyzeHand(args)) => run_analyze_hand(&args),
+        Some(Command::Replay(args)) => run_replay(&args),
+        Some(Command::Tournament(args)) => run_tournament(&args),
+        Some(Command::Merge(args)) => run_merge(&args),
+        Some(Command::TenhouExport(args)) => run_tenhou_export(&args),
+        Some(Command::ImportLog(args)) => run_import_log(&args),
+        Some(Command::AnalyzeLog(args)) => run_analyze_log(&args),
     }
-
-    println!("Program took {:.2?} to execute", start_time.elapsed());
 }
 
-fn simulate_game() -> GameResult {
-    let mut players = initialize_players();
-
-    let mut round = 0;
-    'rounds: while round < ROUNDS {
-        for player in players.iter().take(3 + 1) {
-            if player.points < 0 && TOBI {
-                break 'rounds;
-            }
-        }
-        let mut player_tiles = PlayerTiles::default();
-
-        let (mut wall, wall_dead, dora_indicators) = initialize_wall();
-
-        (
-            wall,
-            player_tiles.hand[0],
-            player_tiles.hand[1],
-            player_tiles.hand[2],
-            player_tiles.hand[3],
-        ) = draw_hands(wall);
-
-        let mut board_tiles = BoardTiles {
-            wall,
-            wall_dead,
-            dora_indicators,
-            dora_index: 0,
-        };
-
-        flip_dora_indicator(&mut board_tiles, &mut player_tiles);
-
-        for i in 0..=3 {
-            player_tiles.hand[i].sort();
-        }
-
-        let mut round_ongoing = true;
-        let mut skip_draw = false;
-        let mut skip_chi = false;
-        let mut current_player_index: usize = (round % 4).into();
-        'round: while round_ongoing {
-            let next_player_index = (current_player_index + 1) % 4;
-            // Current player draws a tile
-            player_tiles.hand[current_player_index].sort();
-
-            if skip_draw {
-                skip_draw = false;
-            } else if board_tiles.wall.is_empty() {
-                let player_1_wind = players[0].seat_wind.clone();
-                scoring_tenpai(&mut player_tiles, &mut players);
-                if players[0].seat_wind != player_1_wind {
-                    round += 1;
-                }
-                round_ongoing = false;
-                break 'round;
-            } else {
-                draw_tile(
-                    &mut board_tiles.wall,
-                    &mut player_tiles.hand[current_player_index],
-                );
-            }
-
-            let strategy_input = StrategyInput {
-                hand: player_tiles.hand[current_player_index].clone(),
-                discards: player_tiles.discards.clone(),
-                seat_wind: players[current_player_index].seat_wind.clone(),
-                round_number: round,
-            };
-
-            // Current player may tsumo
-            if is_complete(&player_tiles.hand[current_player_index])
-                && (players[current_player_index].strategy.tsumo)(strategy_input.clone())
-            {
-                scoring_tsumo(&mut player_tiles, &mut players, current_player_index);
-                break 'round;
-            }
-            // Current player may kan
-            // Current player discards a tile
-            // Placeholder - Need to pass relevant vectors to strategies (hand, discards, dora indicator..)
-            //let strategy_input = true;
-
-            move_tile(
-                &mut player_tiles.hand[current_player_index],
-                &mut player_tiles.discards[current_player_index],
-                (players[current_player_index].strategy.discard)(strategy_input.clone()),
-            );
-            let discarded = *player_tiles.discards[current_player_index].last().unwrap();
-
-            // Other players may ron
-            // Other players may pon
-            for i in 0..=3 {
-                if i != current_player_index
-                    && can_pon(&player_tiles.hand[i], &discarded)
-                    && (players[current_player_index].strategy.call_pon)(strategy_input.clone())
-                {
-                    //println!("some guy pon'd a {:?}", discarded);
-                    player_tiles.hand[i].sort();
-                    remove_pon_tiles(&mut player_tiles.hand[i], &discarded);
-                    for _ in 1..=3 {
-                        player_tiles.open_hand[i].push(discarded);
-                    }
-                    skip_chi = true;
-                    break;
-                }
-            }
-
-            // Next player may chi
-            /* Need to change how this works -- strategy has to answer what straight to combine the stolen tile with so a boolean answer wont be enough
-            if !skip_chi
-                && can_chi(&game_state.players[next_player_index].hand, &discarded)
-                && (game_state.players[current_player_index].strategy.call_chi)(game_state.clone())
-            {
-                draw_tile(
-                    &mut game_state.players[current_player_index].discards,
-                    &mut game_state.players[next_player_index].hand,
-                );
-                skip_draw = true;
-                //change player's hand to open and move the chi'd set to a open section of the hand
-            };
-            */
-            skip_chi = false;
-            // Pass turn to the next player
-            //print_hand(&game_state.players[current_player_index].hand);
-            //print_hand(&game_state.players[current_player_index].open_hand);
-            //println!("hand is open: {:?}", game_state.players[current_player_index].hand_is_open());
-            current_player_index = next_player_index;
-        }
+/// Sets up the suit tables hands are evaluated with, from the disk cache or generated and
+/// cached for the next run.
+fn cache_suit_tables() {
+    if let Err(error) = SuitTables::init(&SuitTables::cache_path()) {
+        println!("Error occured while caching suit tables: {}", error);
     }
+}
 
-    let mut uma_vector = vec![0; 4];
-    if UMA {
-        let mut sorted_players = players.to_vec();
-        sorted_players.sort_by_key(|p| Reverse(p.points));
-
-        let mut tied_players: HashMap<i32, Vec<usize>> = HashMap::new();
-
-        for (i, p) in sorted_players.iter().enumerate() {
-            let rank = i as i32 + 1;
-            let uma_points: i32 = 15000 - 10000 * (rank - 1);
-            uma_vector[p.id - 1] = uma_points;
-
-            let ids = tied_players.entry(p.points).or_insert(Vec::new());
-            ids.push(p.id);
-        }
-
-        let result: Vec<Vec<usize>> = tied_players.values().cloned().collect();
-        for res_vec in &result {
-            if res_vec.len() == 1 {
-                continue;
-            }
-            let mut uma_sum = 0;
-            for tied_player_id in res_vec {
-                uma_sum += &uma_vector[*tied_player_id - 1];
-            }
-            uma_sum /= res_vec.len() as i32;
-            for tied_player_id in res_vec {
-                uma_vector[*tied_player_id - 1] = uma_sum;
+fn run_simulate(args: &SimulateArgs) {
+    let mut experiments = match &args.config {
+        Some(path) => match Experiment::load(path) {
+            Ok(experiments) => experiments,
+            Err(error) => {
+                println!("Error in experiment file {}: {}", path.display(), error);
+                std::process::exit(1);
             }
+        },
+        None => vec![args.experiment()],
+    };
+    if let Some(shard) = args.shard {
+        experiments = experiments
+            .iter()
+            .map(|experiment| experiment.sharded(shard))
+            .collect();
+    }
+    // A resumed run keeps the seed it was started with
+    if args.resume && experiments[0].seed.is_none() {
+        if let Ok(started) = read_experiment(&experiments[0].output.path) {
+            experiments[0].seed = started.seed;
         }
     }
-
-    GameResult {
-        player_1_score: players[0].points + uma_vector[0],
-        player_2_score: players[1].points + uma_vector[1],
-        player_3_score: players[2].points + uma_vector[2],
-        player_4_score: players[3].points + uma_vector[3],
+    if args.shard.is_some() && experiments[0].stop.is_some() {
+        println!("Error: a run that stops once the strategies differ can't be sharded");
+        std::process::exit(1);
+    }
+    if args.shard.is_some() && experiments[0].seed.is_none() {
+        println!("Error: every shard of a run needs the same --seed");
+        std::process::exit(1);
+    }
+    // Runs of a sweep share their walls so only the parameters differ between them
+    let master_seed = experiments[0].seed.unwrap_or_else(random_seed);
+    for experiment in &mut experiments {
+        experiment.seed.get_or_insert(master_seed);
+    }
+    catch_interrupt();
+    for experiment in &experiments {
+        if interrupted() {
+            break;
+        }
+        play_run(experiment, args.resume);
     }
 }
 
-fn initialize_players() -> Vec<Player> {
-    let completor = Strategy {
-        discard: completor,
-        call_chi: never_open_hand,
-        call_pon: never_open_hand,
-        ..Default::default()
-    };
-    let kanc_completor = Strategy {
-        discard: kanchan_completor,
-        call_chi: never_open_hand,
-        call_pon: never_open_hand,
-        ..Default::default()
-    };
-    let standard = Strategy {
-        discard: standard_discarder,
-        call_chi: never_open_hand,
-        call_pon: never_open_hand,
-        ..Default::default()
-    };
-    let a = Player {
-        strategy: completor.clone(),
-        id: 1,
-        ..Default::default()
-    };
-    let b = Player {
-        seat_wind: SeatWind::South,
-        strategy: completor.clone(),
-        id: 2,
-        ..Default::default()
-    };
-    let c = Player {
-        seat_wind: SeatWind::West,
-        strategy: kanc_completor.clone(),
-        id: 3,
-        ..Default::default()
-    };
-    let d = Player {
-        seat_wind: SeatWind::North,
-        strategy: standard.clone(),
-        id: 4,
-        ..Default::default()
+/// Plays one run of an experiment with a progress bar, and reports it.
+fn play_run(experiment: &Experiment, resume: bool) {
+    let start_time = Instant::now();
+    let run = match run_experiment(experiment, resume, &INTERRUPTED, &mut Progress::new()) {
+        Ok(run) => run,
+        Err(error) => {
+            print_run_error(&error);
+            return;
+        }
     };
-
-    vec![a, b, c, d]
+    for error in &run.errors {
+        print_run_error(error);
+    }
+    if run.interrupted() {
+        println!(
+            "Interrupted after {} of {} games",
+            run.totals.games, run.planned
+        );
+    }
+    println!(
+        "{} games written to {} in {:.2?}",
+        run.totals.games,
+        experiment.output.path.display(),
+        start_time.elapsed()
+    );
+    let labels: Vec<String> = experiment.seats.iter().map(SeatConfig::label).collect();
+    report_run(experiment, &labels, &run.totals);
+    if let (Some(rule), Some(look)) = (&experiment.stop, run.last_look()) {
+        print_look(look, rule, run.planned);
+    }
 }
 
-fn never_open_hand(_strat: StrategyInput) -> bool {
-    false
+fn print_run_error(error: &RunError) {
+    match error {
+        RunError::Stop(message) => println!("Error: {}", message),
+        RunError::Resume(error) => println!("Error occured while resuming the run: {}", error),
+        RunError::Results(error) => {
+            println!("Error occured while writing game results: {}", error)
+        }
+        RunError::Events(error) => println!("Error occured while writing event log: {}", error),
+    }
 }
 
-fn completor(strat: StrategyInput) -> usize {
-    let mut own_hand = strat.hand.clone();
-    own_hand.sort();
-    let partial_hand = get_partial_completion(&own_hand);
-    if partial_hand.is_empty() {
-        //println!("Partial hand is empty, hand was complete!");
-        return 13;
+/// Prints the summary and significance tables of a run, and writes its statistics and
+/// charts where the experiment asks for them.
+fn report_run(experiment: &Experiment, labels: &[String], totals: &RunTotals) {
+    let output = &experiment.output;
+    if let Some(path) = &output.stats {
+        if let Err(error) =
+            write_strategy_stats(path, &totals.strategy_stats, &experiment.metadata())
+        {
+            println!("Error occured while writing statistics: {}", error);
+        }
     }
-    if partial_hand.len() == 1 {
-        return find_tile_in_hand(&strat.hand, &partial_hand[0]);
+    print_summary(&totals.strategy_stats);
+    if let Some(directory) = &output.charts {
+        write_run_charts(
+            directory,
+            experiment,
+            labels,
+            &totals.history,
+            &totals.strategy_stats,
+        );
     }
-
-    for tile in &partial_hand {
-        if tile.suit == Suit::Sangen || tile.suit == Suit::Kaze {
-            return find_tile_in_hand(&strat.hand, tile);
-        }
+    print_significance(&totals.by_game);
+    if experiment.rotation.games_per_wall() > 1 {
+        print_paired_differences(&totals.by_set);
     }
-    let mut skip_following = false;
-    for tile_index in 0..partial_hand.len() - 1 {
-        let tile = &partial_hand[tile_index];
-        let right = &partial_hand[tile_index + 1];
-        if skip_following {
-            skip_following = false;
-            continue;
+}
+
+fn run_merge(args: &MergeArgs) {
+    let start_time = Instant::now();
+    let merged = merge_shards(&args.inputs, |output| OutputConfig {
+        path: args.output.clone(),
+        events: None,
+        stats: args.stats.clone(),
+        charts: args.charts.clone(),
+        chart_format: args.chart_format,
+        ..output.clone()
+    });
+    let (experiment, totals) = match merged {
+        Ok(merged) => merged,
+        Err(MergeError::Read(path, error)) => {
+            println!("Error occured while reading {}: {}", path.display(), error);
+            return;
         }
-        if tile.value + 1 == right.value && tile.suit == right.suit {
-            //keep the tile and the next tile
-            skip_following = true;
-            continue;
+        Err(MergeError::Write(error)) => {
+            println!("Error occured while writing game results: {}", error);
+            return;
         }
-        return find_tile_in_hand(&strat.hand, tile);
-    }
-    for tile in &partial_hand {
-        if tile.value == 1 || tile.value == 9 {
-            return find_tile_in_hand(&strat.hand, tile);
+        Err(error) => {
+            println!("Error: {}", error);
+            return;
         }
-    }
-    find_tile_in_hand(&strat.hand, &partial_hand[partial_hand.len() - 1])
+    };
+    println!(
+        "{} games from {} shards merged into {} in {:.2?}",
+        totals.games,
+        args.inputs.len(),
+        experiment.output.path.display(),
+        start_time.elapsed()
+    );
+    let labels: Vec<String> = experiment.seats.iter().map(SeatConfig::label).collect();
+    report_run(&experiment, &labels, &totals);
 }
 
-fn kanchan_completor(strat: StrategyInput) -> usize {
-    let mut own_hand = strat.hand.clone();
-    own_hand.sort();
-    let partial_hand = get_partial_completion(&own_hand);
-    if partial_hand.is_empty() {
-        //println!("Partial hand is empty, hand was complete!");
-        return 13;
-    }
-    if partial_hand.len() == 1 {
-        return find_tile_in_hand(&strat.hand, &partial_hand[0]);
-    }
-
-    for tile in &partial_hand {
-        if tile.suit == Suit::Sangen || tile.suit == Suit::Kaze {
-            return find_tile_in_hand(&strat.hand, tile);
+fn run_analyze_hand(args: &AnalyzeHandArgs) {
+    let hand = &args.hand;
+    let renderer = Renderer::for_terminal();
+    println!("{}", renderer.hand(hand));
+    let counts = to_counts(&hand.closed);
+    let tiles = hand.tile_count();
+    if tiles != 13 && tiles != 14 {
+        println!("A hand holds 13 or 14 tiles, this one has {}", tiles);
+        return;
+    }
+
+    if is_agari(&counts) {
+        let winning_tile = hand.closed[hand.closed.len() - 1];
+        let mut shapes: Vec<WaitShape> = win_shapes(&counts, tile_index(&winning_tile))
+            .iter()
+            .map(|shape_match| shape_match.shape)
+            .collect();
+        shapes.sort();
+        shapes.dedup();
+        println!("Complete hand, won on {} ({:?})", winning_tile, shapes);
+        let score = score_hand(&hand.closed, &hand.called, !args.ron, &args.seat_wind);
+        for yaku in &score.yaku {
+            println!("  {:<14} {} han", yaku.name, yaku.han);
+        }
+        println!(
+            "{} han {} fu, base points: {}",
+            score.han, score.fu, score.base_points
+        );
+        return;
+    }
+
+    let current_shanten = shanten(&counts);
+    println!("Shanten: {}", current_shanten);
+    if tiles == 13 {
+        for wait in waits_with_shapes(&counts) {
+            println!("Waits on {}", wait);
         }
+        return;
     }
-    let mut skip_following = false;
-    for tile_index in 0..partial_hand.len() - 1 {
-        let tile = &partial_hand[tile_index];
-        let right = &partial_hand[tile_index + 1];
-
-        if (tile.value + 1 == right.value || tile.value + 2 == right.value)
-            && tile.suit == right.suit
-        {
-            //keep the tile and the next tile
-            skip_following = true;
-            continue;
-        } else if skip_following {
-            skip_following = false;
+    // 14 tiles: list the discards that keep the hand closest to tenpai
+    let mut after_discard = counts;
+    for (index, tile) in hand.closed.iter().enumerate() {
+        if hand.closed[..index].contains(tile) {
             continue;
         }
-        return find_tile_in_hand(&strat.hand, tile);
-    }
-    for tile in &partial_hand {
-        if tile.value == 1 || tile.value == 9 {
-            return find_tile_in_hand(&strat.hand, tile);
+        after_discard[tile_index(tile)] -= 1;
+        if shanten(&after_discard) == current_shanten {
+            let waits = waits_with_shapes(&after_discard);
+            if waits.is_empty() {
+                println!("Discard {}", tile);
+            } else {
+                println!("Discard {}, waiting on {}", tile, waits.join(", "));
+            }
         }
+        after_discard[tile_index(tile)] += 1;
     }
-    find_tile_in_hand(&strat.hand, &partial_hand[partial_hand.len() - 1])
 }
 
-fn standard_discarder(strat: StrategyInput) -> usize {
-    let mut own_hand = strat.hand.clone();
-    own_hand.sort();
-    let partial_hand = get_partial_completion(&own_hand);
-    if partial_hand.is_empty() {
-        println!("Partial hand is empty, hand was complete!");
-        print_hand(&own_hand);
-        return 13;
-    }
-    find_tile_in_hand(&strat.hand, &partial_hand[partial_hand.len() - 1])
+fn waits_with_shapes(counts: &TileCounts) -> Vec<String> {
+    classify_waits(counts)
+        .iter()
+        .map(|wait| {
+            let mut shapes: Vec<WaitShape> = wait.shapes.iter().map(|m| m.shape).collect();
+            shapes.sort();
+            shapes.dedup();
+            format!("{} ({:?})", wait.tile, shapes)
+        })
+        .collect()
 }
 
-fn flip_dora_indicator(board_tiles: &mut BoardTiles, player_tiles: &mut PlayerTiles) {
-    let dora_indicator: &MahjongTile = &board_tiles.dora_indicators[board_tiles.dora_index];
-    let dora_suit: Suit = dora_indicator.suit;
-
-    let suit_modulo = match dora_suit {
-        Suit::Manzu | Suit::Pinzu | Suit::Souzu => 9,
-        Suit::Kaze => 4,
-        Suit::Sangen => 3,
+#[cfg(feature = "charts")]
+fn write_run_charts(
+    directory: &Path,
+    experiment: &Experiment,
+    labels: &[String],
+    history: &ScoreHistory,
+    strategy_stats: &BTreeMap<String, StrategyStats>,
+) {
+    let data = mahjong_simulator::charts::ChartData {
+        seats: labels.to_vec(),
+        history,
+        starting_points: experiment.ruleset.starting_points,
+        strategies: strategy_stats,
     };
-
-    let dora_value: u8 = (dora_indicator.value) % (suit_modulo) + 1;
-
-    change_dora_bool(&mut board_tiles.wall, dora_suit, dora_value);
-    change_dora_bool(&mut board_tiles.wall_dead, dora_suit, dora_value);
-    change_dora_bool(&mut board_tiles.dora_indicators, dora_suit, dora_value);
-    for i in 0..=3 {
-        change_dora_bool(&mut player_tiles.hand[i], dora_suit, dora_value);
-        change_dora_bool(&mut player_tiles.open_hand[i], dora_suit, dora_value);
+    let output = &experiment.output;
+    let stem = output
+        .path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+    match mahjong_simulator::charts::write_charts(directory, &stem, output.chart_format, &data) {
+        Ok(paths) => println!("{} charts drawn in {}", paths.len(), directory.display()),
+        Err(error) => println!("Error occured while drawing charts: {}", error),
     }
 }
 
-fn change_dora_bool(tile_list: &mut [MahjongTile], dora_suit: Suit, dora_value: u8) {
-    for tile in tile_list
-        .iter_mut()
-        .filter(|tile| tile.suit == dora_suit && tile.value == dora_value)
-    {
-        tile.is_dora = true;
-    }
+#[cfg(not(feature = "charts"))]
+fn write_run_charts(
+    _directory: &Path,
+    _experiment: &Experiment,
+    _labels: &[String],
+    _history: &ScoreHistory,
+    _strategy_stats: &BTreeMap<String, StrategyStats>,
+) {
+    println!("Charts need a build with the `charts` feature");
 }
 
-fn scoring_tenpai(player_tiles: &mut PlayerTiles, players: &mut Vec<Player>) {
-    let mut tenpai_players = 0;
-    let mut noten_players = 0;
-    for i in 0..=3 {
-        let (got_tenpai, _) = check_tenpai(&player_tiles.hand[i]);
-        if got_tenpai {
-            tenpai_players += 1;
-        } else {
-            noten_players += 1;
+fn run_replay(args: &ReplayArgs) {
+    let seats = lineup(&args.table.seats);
+    let seed = match args.game_seed {
+        Some(seed) => seed,
+        None => game_seed(args.seed.unwrap(), args.game as u64),
+    };
+    let renderer = Renderer::for_terminal();
+    let mut show = |table: &TableView| println!("{}\n", renderer.table(table));
+    let mut log = match args.events {
+        Some(_) => EventLog::enabled(),
+        None => EventLog::disabled(),
+    };
+    log.record(|| GameEvent::GameStart {
+        seed,
+        ruleset: args.table.ruleset.name.clone(),
+        strategies: args.table.seats.to_vec(),
+    });
+    let result = simulate_game(
+        &args.table.ruleset,
+        &seats,
+        seed,
+        (!args.quiet).then_some(&mut show),
+        &mut log,
+    );
+    if let Some(path) = &args.events {
+        if let Err(error) = write_event_logs(path, &[log]) {
+            println!("Error occured while writing event log: {}", error);
         }
     }
+    println!(
+        "Game seed {}, final scores: {}, {}, {}, {}",
+        seed,
+        result.player_1_score,
+        result.player_2_score,
+        result.player_3_score,
+        result.player_4_score
+    );
+}
 
-    let mut change_winds = true;
-
-    if tenpai_players == 4 {
-        change_winds = false;
+fn run_tournament(args: &TournamentArgs) {
+    let mut pool: Vec<String> = args.strategies.clone();
+    if pool.is_empty() {
+        pool = STRATEGY_NAMES.iter().map(|name| name.to_string()).collect();
     }
-
-    if tenpai_players != 4 && noten_players != 4 {
-        let winner_payout = 3000 / tenpai_players;
-        for (index, player) in players.iter_mut().enumerate().take(3 + 1) {
-            let (got_tenpai, _) = check_tenpai(&player_tiles.hand[index]);
-            if got_tenpai {
-                player.points += winner_payout;
-                if player.seat_wind == SeatWind::East {
-                    change_winds = false;
-                }
-            } else if noten_players == 2 {
-                player.points -= winner_payout;
-            } else if noten_players == 1 {
-                player.points -= winner_payout * tenpai_players;
-            } else {
-                player.points -= winner_payout / noten_players;
-            }
-        }
-    }
-
-    if change_winds {
-        for player in players {
-            player.next_wind();
-        }
+    pool.sort();
+    pool.dedup();
+    if pool.len() < 2 {
+        println!("A tournament needs at least two different strategies");
+        return;
     }
-}
-
-fn scoring_tsumo(
-    player_tiles: &mut PlayerTiles,
-    players: &mut Vec<Player>,
-    winning_player_index: usize,
-) {
-    let is_dealer_win = players[winning_player_index].seat_wind == SeatWind::East;
 
-    let base_points = calculate_hand_score(
-        &player_tiles.hand[winning_player_index],
-        &player_tiles.open_hand[winning_player_index],
-        true,
-        &players[winning_player_index].seat_wind,
+    let master_seed = args.seed.unwrap_or_else(random_seed);
+    let (standings, table_count) = play_tournament(
+        &pool,
+        (args.schedule, args.rounds, args.games),
+        &args.ruleset,
+        master_seed,
     );
-
-    for (index, player) in players.iter_mut().enumerate().take(3 + 1) {
-        if index == winning_player_index {
-            match is_dealer_win {
-                true => player.points += 3 * round_up_to_100(2 * base_points),
-                false => {
-                    player.points +=
-                        round_up_to_100(2 * base_points) + 2 * round_up_to_100(base_points);
-                }
-            }
-        } else if is_dealer_win || player.seat_wind == SeatWind::East {
-            player.points -= round_up_to_100(2 * base_points);
-        } else {
-            player.points -= round_up_to_100(base_points);
-        }
-    }
-
-    if !is_dealer_win {
-        for player in players {
-            player.next_wind();
+    let rounds = match args.schedule {
+        Schedule::RoundRobin => 1,
+        Schedule::Swiss => args.rounds,
+    };
+    println!(
+        "{} tables, {} games each, {}, master seed {}",
+        table_count, args.games, args.ruleset.name, master_seed
+    );
+    print_leaderboard(&standings);
+    if let Some(path) = &args.report {
+        let metadata = format!(
+            "strategies = {:?}\nschedule = {:?}\nrounds = {}\ngames = {}\nruleset = {:?}\nseed = {}",
+            pool,
+            args.schedule.to_possible_value().unwrap().get_name(),
+            rounds,
+            args.games,
+            args.ruleset.name,
+            master_seed
+        );
+        if let Err(error) = write_leaderboard(path, &standings, &metadata) {
+            println!(
+                "Error occured while writing the tournament report: {}",
+                error
+            );
         }
     }
 }
 
-fn calculate_hand_score(
-    hand: &[MahjongTile],
-    open_hand: &[MahjongTile],
-    tsumo: bool,
-    seat_wind: &SeatWind,
-) -> i32 {
-    let hand_copy = hand.to_vec();
-    let (melds, _) = find_pairs_melds(&hand_copy);
-
-    let mut han_score = 0;
-    let mut fu_score;
-
-    if open_hand.is_empty() && tsumo {
-        han_score += 1;
-        fu_score = 20;
-    } else if open_hand.is_empty() && !tsumo {
-        fu_score = 30;
-    } else {
-        fu_score = 20;
-    }
-    let mut tanyao = true;
-    let mut honitsu = true;
-    let mut chinitsu = true;
-
-    let hand_suit = hand_copy[0].suit;
-    if hand_suit == Suit::Kaze || hand_suit == Suit::Sangen {
-        println!("Yakuman I guess:");
-        print_hand(&hand_copy);
-    }
-
-    for tile in &hand_copy {
-        han_score += if tile.is_dora { 1 } else { 0 };
-
-        if tile.value == 1
-            || tile.value == 9
-            || tile.suit == Suit::Kaze
-            || tile.suit == Suit::Sangen
-        {
-            tanyao = false;
-        }
-        if tile.suit != hand_suit {
-            chinitsu = false;
-            if tile.suit != Suit::Kaze || tile.suit != Suit::Sangen {
-                honitsu = false;
-            }
+fn run_tenhou_export(args: &TenhouExportArgs) {
+    let logs = match read_event_logs(&args.events) {
+        Ok(logs) => logs,
+        Err(error) => {
+            println!(
+                "Error occured while reading {}: {}",
+                args.events.display(),
+                error
+            );
+            std::process::exit(1);
         }
-    }
-    han_score += if tanyao { 1 } else { 0 };
-    han_score += if honitsu { 3 } else { 0 };
-    han_score += if chinitsu { 6 } else { 0 };
-
-    if melds.len() < 2 {
-        //chiitoi temp fix
-        let hand_score = 25 * pow(2, 2 + 2 + han_score);
-        if hand_score > 2000 {
-            return match han_score {
-                0..=5 => 2000,
-                6..=7 => 3000,
-                8..=10 => 4000,
-                11..=12 => 6000,
-                _ => 8000, // 13 or greater, not in EMA
-            };
+    };
+    let games: Vec<usize> = match args.game {
+        Some(game) if game < logs.len() => vec![game],
+        Some(game) => {
+            println!(
+                "The log only holds {} games, there is no game {}",
+                logs.len(),
+                game
+            );
+            std::process::exit(1);
         }
-        return hand_score;
-    }
-
-    let meld_list = construct_unique_meld_set(&hand_copy);
-
-    let winning_tile = &hand_copy[hand_copy.len() - 1];
-
-    let mut twopoint_wait_fu = false;
-    let mut zeropoint_wait_fu = false;
-
-    // let round_wind_number = match strat.round_number {
-    //     1..=4 => 1,
-    //     5..=8 => 2,
-    //     9..=12 => 3,
-    //     13..=16 => 4,
-    //     _ => 0,
-    // };
-
-    let seat_wind_number = match seat_wind {
-        SeatWind::East => 1,
-        SeatWind::South => 2,
-        SeatWind::West => 3,
-        SeatWind::North => 4,
+        None => (0..logs.len()).collect(),
     };
-    let mut triplet_count = 0;
-    for meld in &meld_list {
-        let mut is_triplet = false;
-        let mut is_straight = false;
-        if meld.len() == 3 {
-            is_triplet = meld[1].value - meld[0].value == 0;
-            is_straight = meld[1].value - meld[0].value == 1;
+    for game in games {
+        let path = match (&args.output, args.game) {
+            (Some(path), Some(_)) => path.clone(),
+            (Some(path), None) => numbered_path(path, game),
+            (None, _) => format!("game-{}.json", game).into(),
+        };
+        let written = to_tenhou(&logs[game].events)
+            .map_err(|error| error.to_string())
+            .and_then(|log| {
+                std::fs::write(&path, log.to_string()).map_err(|error| error.to_string())
+            });
+        match written {
+            Ok(()) => println!("Game {} written to {}", game, path.display()),
+            Err(error) => println!("Error occured while exporting game {}: {}", game, error),
         }
+    }
+}
 
-        if meld.len() == 2
-            && (meld[0].suit == Suit::Sangen
-                || (meld[0].suit == Suit::Kaze && meld[0].value == seat_wind_number))
-        {
-            fu_score += 2;
-            //Add round wind
-        }
-        if meld.contains(winning_tile) {
-            if meld.len() == 2 {
-                twopoint_wait_fu = true; //tanki
+fn run_import_log(args: &ImportLogArgs) {
+    // Messages listed per log unless --verbose asks for all of them
+    const SHOWN: usize = 5;
+    let mut logs = Vec::new();
+    let mut total = ReplayReport::default();
+    for path in &args.logs {
+        let imported = std::fs::read_to_string(path)
+            .map_err(|error| error.to_string())
+            .and_then(|text| import_log(&text).map_err(|error| error.to_string()));
+        let events = match imported {
+            Ok(events) => events,
+            Err(error) => {
+                println!(
+                    "Error occured while importing {}: {}",
+                    path.display(),
+                    error
+                );
                 continue;
             }
-            if is_straight {
-                let win_index = find_tile_in_hand(meld, winning_tile);
-                if win_index == 1
-                    || (win_index == 0 && winning_tile.value == 7)
-                    || (win_index == 2 && winning_tile.value == 3)
-                {
-                    twopoint_wait_fu = true; //kanchan and penchan
-                } else {
-                    zeropoint_wait_fu = true; //ryanmen
-                }
-            }
+        };
+        let report = replay_log(&events);
+        println!(
+            "{}: {} rounds, {} actions, {} rule violations, {} of {} wins scored the same",
+            path.display(),
+            report.rounds,
+            report.actions,
+            report.violations.len(),
+            report.wins - report.score_differences.len(),
+            report.wins
+        );
+        let messages = report.violations.iter().chain(&report.score_differences);
+        let shown = if args.verbose { usize::MAX } else { SHOWN };
+        for message in messages.clone().take(shown) {
+            println!("  {}", message);
         }
-
-        //open hand needs different method
-        if is_triplet {
-            triplet_count += 1;
-            let triplet_suit = meld[0].suit;
-            let triplet_value = meld[0].value;
-
-            if triplet_suit == Suit::Sangen
-                || triplet_suit == Suit::Kaze
-                || triplet_value == 1
-                || triplet_value == 9
-            {
-                fu_score += 8;
-            } else {
-                fu_score += 4;
-            }
-            if triplet_suit == Suit::Sangen
-                || (triplet_suit == Suit::Kaze && triplet_value == seat_wind_number)
-            {
-                //Add round wind
-                han_score += 1;
-            }
+        if messages.count() > shown {
+            println!("  ... (--verbose lists them all)");
         }
-    }
-    if triplet_count >= 3 {
-        han_score += 2; //san ankou and temp suuankou
-    }
-    if open_hand.is_empty() {
-        for i in 0..meld_list.len() {
-            for j in i + 1..meld_list.len() {
-                if meld_list[i] == meld_list[j] {
-                    han_score += 1; //Iipeikou
-                    break;
-                }
-            }
+        total.add(&report);
+        let mut log = EventLog::enabled();
+        log.events = events;
+        logs.push(log);
+    }
+    if args.logs.len() > 1 {
+        println!(
+            "Total: {} rounds, {} actions, {} rule violations, {} of {} wins scored the same",
+            total.rounds,
+            total.actions,
+            total.violations.len(),
+            total.wins - total.score_differences.len(),
+            total.wins
+        );
+    }
+    if let Some(path) = &args.events {
+        match write_event_logs(path, &logs) {
+            Ok(()) => println!("{} games written to {}", logs.len(), path.display()),
+            Err(error) => println!("Error occured while writing {}: {}", path.display(), error),
         }
     }
+}
 
-    let mut is_pinfu = false;
-
-    if zeropoint_wait_fu && fu_score == 20 {
-        han_score += 1; //Pinfu
-        is_pinfu = true;
-    } else if twopoint_wait_fu {
-        fu_score += 2; //from wait
+/// Games in an event log written by `--events`, or the one game in a Tenhou log.
+fn read_games(path: &Path) -> std::result::Result<Vec<Vec<GameEvent>>, String> {
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "jsonl")
+    {
+        let logs = read_event_logs(path).map_err(|error| error.to_string())?;
+        return Ok(logs.into_iter().map(|log| log.events).collect());
     }
+    let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+    Ok(vec![import_log(&text).map_err(|error| error.to_string())?])
+}
 
-    if !is_pinfu && tsumo {
-        fu_score += 2;
+fn run_analyze_log(args: &AnalyzeLogArgs) {
+    let strategy = lineup(std::slice::from_ref(&args.strategy)).remove(0);
+    let mut report = DecisionReport::default();
+    let mut games = 0;
+    for path in &args.logs {
+        match read_games(path) {
+            Ok(logs) => {
+                for events in &logs {
+                    report.add(&analyze_decisions(
+                        events,
+                        games,
+                        args.seat,
+                        strategy.as_ref(),
+                    ));
+                    games += 1;
+                }
+            }
+            Err(error) => println!("Error occured while reading {}: {}", path.display(), error),
+        }
     }
-
-    fu_score = round_up_to_10(fu_score);
-    let hand_score = fu_score * pow(2, 2 + han_score);
-    if hand_score > 2000 {
-        return match han_score {
-            0..=5 => 2000,
-            6..=7 => 3000,
-            8..=10 => 4000,
-            11..=12 => 6000,
-            _ => 8000, // 13 or greater, not in EMA
-        };
+    let percent = |agreed: usize, total: usize| 100.0 * agreed as f64 / total.max(1) as f64;
+    println!(
+        "Seat {} as {} over {} games: {} of {} decisions agreed ({:.1}%)",
+        args.seat,
+        args.strategy,
+        games,
+        report.agreed(),
+        report.total(),
+        percent(report.agreed(), report.total())
+    );
+    for (decision, (agreed, total)) in &report.decisions {
+        println!(
+            "  {}: {} of {} ({:.1}%)",
+            decision,
+            agreed,
+            total,
+            percent(*agreed, *total)
+        );
+    }
+    if report.disagreements.is_empty() {
+        return;
+    }
+    println!("Disagreements:");
+    let shown = if args.verbose { usize::MAX } else { 20 };
+    for disagreement in report.disagreements.iter().take(shown) {
+        println!("  {}", disagreement);
+    }
+    if report.disagreements.len() > shown {
+        println!(
+            "  ... {} more (--verbose lists them all)",
+            report.disagreements.len() - shown
+        );
     }
-    hand_score
-}
-
-fn round_up_to_100(number: i32) -> i32 {
-    (number + 99) / 100 * 100
 }
-fn round_up_to_10(number: i32) -> i32 {
-    (number + 9) / 10 * 10
-}
-
-fn write_game_results(filename: &str, gameresults: &Vec<GameResult>) -> Result<()> {
-    let file = File::create(filename)?;
-    let mut writer = BufWriter::new(file);
-    for game in gameresults {
-        writeln!(
-            &mut writer,
-            "{}, {}, {}, {}",
-            game.player_1_score, game.player_2_score, game.player_3_score, game.player_4_score
-        )?;
-    }
-    writer.flush()?;
 
-    Ok(())
+/// Strategies for the four seats from names the command line already validated.
+fn lineup(names: &[String]) -> Vec<Arc<dyn Strategy>> {
+    names
+        .iter()
+        .map(|name| -> Arc<dyn Strategy> {
+            Arc::new(strategy_by_name(name).expect("strategy names are validated"))
+        })
+        .collect()
 }
@@ -0,0 +1,1010 @@
+use crate::experiment::*;
+use crate::stats::{PlayerStats, StrategyStats};
+use crate::types::GameResult;
+#[cfg(feature = "parquet")]
+use parquet::file::writer::SerializedFileWriter;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Result, Seek, SeekFrom, Write};
+use std::path::Path;
+
+// Results files. `dat` and `csv` hold one line of scores per game in line-up order, the
+// other formats one row per player per game with the seat, strategy, scores, placement and
+// statistics. Every format carries the program version and the resolved experiment, and
+// reads back into games for resuming and merging runs.
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    /// One comma separated line of scores per game
+    Dat,
+    /// Like `dat` with a header row
+    Csv,
+    /// One row per player per game with seat, strategy, placement and statistics
+    PlayersCsv,
+    /// The `players-csv` rows and the experiment as one JSON document
+    Json,
+    /// The `players-csv` rows as Parquet, with the experiment in the file metadata
+    Parquet,
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Dat => "dat",
+            OutputFormat::Csv | OutputFormat::PlayersCsv => "csv",
+            OutputFormat::Json => "json",
+            OutputFormat::Parquet => "parquet",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum ChartFormat {
+    #[default]
+    Png,
+    Svg,
+}
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// `#` comment lines naming the program and version, then `metadata`. numpy's genfromtxt
+/// and pandas' `comment="#"` skip them.
+pub fn write_header(writer: &mut impl Write, metadata: &str) -> Result<()> {
+    writeln!(writer, "# mahjong-simulator {}", VERSION)?;
+    for line in metadata.lines() {
+        writeln!(writer, "{}", format!("# {}", line).trim_end())?;
+    }
+    Ok(())
+}
+
+/// One player's game, the row of the per-player formats.
+#[derive(Serialize)]
+struct PlayerRow<'a> {
+    game: usize,
+    seed: u64,
+    /// Position in the line-up
+    player: usize,
+    /// Seat in the game, 0 for whoever was East at the start
+    seat: usize,
+    strategy: &'a str,
+    /// Final points plus uma
+    score: i32,
+    uma: i32,
+    #[serde(flatten)]
+    stats: &'a PlayerStats,
+}
+
+fn player_rows<'a>(
+    first_game: usize,
+    results: &'a [GameResult],
+    labels: &'a [String],
+) -> impl Iterator<Item = PlayerRow<'a>> + 'a {
+    results.iter().enumerate().flat_map(move |(game, result)| {
+        let scores = [
+            result.player_1_score,
+            result.player_2_score,
+            result.player_3_score,
+            result.player_4_score,
+        ];
+        result
+            .stats
+            .iter()
+            .enumerate()
+            .map(move |(player, stats)| PlayerRow {
+                game: first_game + game,
+                seed: result.seed,
+                player,
+                seat: result.seats[player],
+                strategy: &labels[player],
+                score: scores[player],
+                uma: scores[player] - stats.final_points,
+                stats,
+            })
+    })
+}
+
+const PLAYER_COLUMNS: [&str; 20] = [
+    "game",
+    "seed",
+    "player",
+    "seat",
+    "strategy",
+    "final_points",
+    "uma",
+    "score",
+    "placement",
+    "rounds",
+    "tsumo_wins",
+    "ron_wins",
+    "deal_ins",
+    "win_points",
+    "deal_in_points",
+    "riichi",
+    "calls",
+    "exhaustive_draws",
+    "tenpai_at_draw",
+    "bust",
+];
+
+/// Quotes a label with parameters, which holds commas of its own.
+fn csv_field(text: &str) -> String {
+    if text.contains(',') {
+        format!("\"{}\"", text)
+    } else {
+        text.to_string()
+    }
+}
+
+enum Sink {
+    Text(BufWriter<File>),
+    #[cfg(feature = "parquet")]
+    Parquet(SerializedFileWriter<File>),
+}
+
+impl Sink {
+    /// The writer of the text formats, `None` for Parquet.
+    fn text(&mut self) -> Option<&mut BufWriter<File>> {
+        match self {
+            Sink::Text(writer) => Some(writer),
+            #[cfg(feature = "parquet")]
+            Sink::Parquet(_) => None,
+        }
+    }
+}
+
+/// Writes a results file game by game, so a long run keeps what it has played so far.
+/// Parquet buffers a row group at a time and is only readable once finished.
+pub struct ResultWriter {
+    format: OutputFormat,
+    labels: Vec<String>,
+    sink: Sink,
+    /// Index in the whole run of the next game
+    next_game: usize,
+    /// Games in the file so far
+    games: usize,
+    /// Games waiting for the next Parquet row group
+    #[cfg(feature = "parquet")]
+    pending: Vec<GameResult>,
+}
+
+impl ResultWriter {
+    pub fn create(
+        path: &Path,
+        format: OutputFormat,
+        experiment: &Experiment,
+    ) -> Result<ResultWriter> {
+        let labels: Vec<String> = experiment.seats.iter().map(SeatConfig::label).collect();
+        let sink = match format {
+            OutputFormat::Parquet => parquet_sink(path, experiment)?,
+            _ => {
+                let mut writer = BufWriter::new(File::create(path)?);
+                match format {
+                    OutputFormat::Json => {
+                        write!(&mut writer, "{{\"version\":")?;
+                        serde_json::to_writer(&mut writer, VERSION)?;
+                        write!(&mut writer, ",\"experiment\":")?;
+                        serde_json::to_writer(&mut writer, experiment)?;
+                        write!(&mut writer, ",\"players\":[")?;
+                    }
+                    _ => write_header(&mut writer, &experiment.metadata())?,
+                }
+                match format {
+                    OutputFormat::Csv => {
+                        writeln!(&mut writer, "player_1,player_2,player_3,player_4,seed")?
+                    }
+                    OutputFormat::PlayersCsv => {
+                        writeln!(&mut writer, "{}", PLAYER_COLUMNS.join(","))?
+                    }
+                    _ => {}
+                }
+                Sink::Text(writer)
+            }
+        };
+        Ok(ResultWriter {
+            format,
+            labels,
+            sink,
+            next_game: experiment.game_range().start,
+            games: 0,
+            #[cfg(feature = "parquet")]
+            pending: Vec::new(),
+        })
+    }
+
+    /// Carries on a text results file holding `games` whole games in its first `length`
+    /// bytes, dropping anything after them.
+    pub fn append(
+        path: &Path,
+        format: OutputFormat,
+        experiment: &Experiment,
+        games: usize,
+        length: u64,
+    ) -> Result<ResultWriter> {
+        if !matches!(
+            format,
+            OutputFormat::Dat | OutputFormat::Csv | OutputFormat::PlayersCsv
+        ) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "only dat, csv and players-csv results can be resumed",
+            ));
+        }
+        let mut file = OpenOptions::new().write(true).open(path)?;
+        file.set_len(length)?;
+        file.seek(SeekFrom::End(0))?;
+        Ok(ResultWriter {
+            format,
+            labels: experiment.seats.iter().map(SeatConfig::label).collect(),
+            sink: Sink::Text(BufWriter::new(file)),
+            next_game: experiment.game_range().start + games,
+            games,
+            #[cfg(feature = "parquet")]
+            pending: Vec::new(),
+        })
+    }
+
+    pub fn write(&mut self, result: &GameResult) -> Result<()> {
+        let (game, first) = (self.next_game, self.games == 0);
+        self.next_game += 1;
+        self.games += 1;
+        let Some(writer) = self.sink.text() else {
+            #[cfg(feature = "parquet")]
+            if let Sink::Parquet(writer) = &mut self.sink {
+                self.pending.push(result.clone());
+                if self.pending.len() * 4 >= ROW_GROUP_ROWS {
+                    let first_game = self.next_game - self.pending.len();
+                    write_row_group(writer, first_game, &self.pending, &self.labels)?;
+                    self.pending.clear();
+                }
+            }
+            return Ok(());
+        };
+        let rows = player_rows(game, std::slice::from_ref(result), &self.labels);
+        match self.format {
+            OutputFormat::Dat | OutputFormat::Csv => {
+                let separator = match self.format {
+                    OutputFormat::Csv => ",",
+                    _ => ", ",
+                };
+                writeln!(
+                    writer,
+                    "{1}{0}{2}{0}{3}{0}{4}{0}{5}",
+                    separator,
+                    result.player_1_score,
+                    result.player_2_score,
+                    result.player_3_score,
+                    result.player_4_score,
+                    result.seed
+                )?;
+            }
+            OutputFormat::PlayersCsv => {
+                for row in rows {
+                    let stats = row.stats;
+                    writeln!(
+                        writer,
+                        "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                        row.game,
+                        row.seed,
+                        row.player,
+                        row.seat,
+                        csv_field(row.strategy),
+                        stats.final_points,
+                        row.uma,
+                        row.score,
+                        stats.placement,
+                        stats.rounds,
+                        stats.tsumo_wins,
+                        stats.ron_wins,
+                        stats.deal_ins,
+                        stats.win_points,
+                        stats.deal_in_points,
+                        stats.riichi,
+                        stats.calls,
+                        stats.exhaustive_draws,
+                        stats.tenpai_at_draw,
+                        stats.bust
+                    )?;
+                }
+            }
+            OutputFormat::Json => {
+                for (index, row) in rows.enumerate() {
+                    if !first || index > 0 {
+                        write!(writer, ",")?;
+                    }
+                    serde_json::to_writer(&mut *writer, &row)?;
+                }
+            }
+            OutputFormat::Parquet => unreachable!("Parquet goes through its own sink"),
+        }
+        Ok(())
+    }
+
+    /// Pushes everything written so far to the file.
+    pub fn flush(&mut self) -> Result<()> {
+        match self.sink.text() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn finish(self) -> Result<()> {
+        match self.sink {
+            Sink::Text(mut writer) => {
+                if self.format == OutputFormat::Json {
+                    write!(&mut writer, "]}}")?;
+                }
+                writer.flush()
+            }
+            #[cfg(feature = "parquet")]
+            Sink::Parquet(mut writer) => {
+                if !self.pending.is_empty() {
+                    let first_game = self.next_game - self.pending.len();
+                    write_row_group(&mut writer, first_game, &self.pending, &self.labels)?;
+                }
+                writer.close().map_err(io::Error::other)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "parquet")]
+fn write_row_group(
+    writer: &mut SerializedFileWriter<File>,
+    first_game: usize,
+    results: &[GameResult],
+    labels: &[String],
+) -> Result<()> {
+    use parquet::data_type::{BoolType, ByteArray, ByteArrayType, Int32Type, Int64Type};
+
+    let rows: Vec<PlayerRow> = player_rows(first_game, results, labels).collect();
+    let mut group = writer.next_row_group().map_err(io::Error::other)?;
+    for name in PLAYER_COLUMNS {
+        let mut column = group
+            .next_column()
+            .map_err(io::Error::other)?
+            .ok_or_else(|| io::Error::other("Parquet schema is missing a column"))?;
+        let int64 = |value: fn(&PlayerRow) -> i64| rows.iter().map(value).collect::<Vec<_>>();
+        let int32 = |value: fn(&PlayerRow) -> i64| {
+            rows.iter().map(|row| value(row) as i32).collect::<Vec<_>>()
+        };
+        let written = match name {
+            "game" => {
+                column
+                    .typed::<Int64Type>()
+                    .write_batch(&int64(|row| row.game as i64), None, None)
+            }
+            "seed" => {
+                column
+                    .typed::<Int64Type>()
+                    .write_batch(&int64(|row| row.seed as i64), None, None)
+            }
+            "win_points" => column.typed::<Int64Type>().write_batch(
+                &int64(|row| row.stats.win_points),
+                None,
+                None,
+            ),
+            "deal_in_points" => column.typed::<Int64Type>().write_batch(
+                &int64(|row| row.stats.deal_in_points),
+                None,
+                None,
+            ),
+            "strategy" => column.typed::<ByteArrayType>().write_batch(
+                &rows
+                    .iter()
+                    .map(|row| ByteArray::from(row.strategy))
+                    .collect::<Vec<_>>(),
+                None,
+                None,
+            ),
+            "bust" => column.typed::<BoolType>().write_batch(
+                &rows.iter().map(|row| row.stats.bust).collect::<Vec<_>>(),
+                None,
+                None,
+            ),
+            _ => {
+                let value: fn(&PlayerRow) -> i64 = match name {
+                    "player" => |row| row.player as i64,
+                    "seat" => |row| row.seat as i64,
+                    "final_points" => |row| row.stats.final_points as i64,
+                    "uma" => |row| row.uma as i64,
+                    "score" => |row| row.score as i64,
+                    "placement" => |row| row.stats.placement as i64,
+                    "rounds" => |row| row.stats.rounds as i64,
+                    "tsumo_wins" => |row| row.stats.tsumo_wins as i64,
+                    "ron_wins" => |row| row.stats.ron_wins as i64,
+                    "deal_ins" => |row| row.stats.deal_ins as i64,
+                    "riichi" => |row| row.stats.riichi as i64,
+                    "calls" => |row| row.stats.calls as i64,
+                    "exhaustive_draws" => |row| row.stats.exhaustive_draws as i64,
+                    _ => |row| row.stats.tenpai_at_draw as i64,
+                };
+                column
+                    .typed::<Int32Type>()
+                    .write_batch(&int32(value), None, None)
+            }
+        };
+        written.map_err(io::Error::other)?;
+        column.close().map_err(io::Error::other)?;
+    }
+    group.close().map_err(io::Error::other)?;
+    Ok(())
+}
+
+#[cfg(feature = "parquet")]
+const ROW_GROUP_ROWS: usize = 1 << 18;
+
+#[cfg(feature = "parquet")]
+fn parquet_sink(path: &Path, experiment: &Experiment) -> Result<Sink> {
+    use parquet::basic::Compression;
+    use parquet::file::metadata::KeyValue;
+    use parquet::file::properties::WriterProperties;
+    use parquet::schema::parser::parse_message_type;
+    use std::sync::Arc;
+
+    let schema = parse_message_type(&format!(
+        "message players {{ {} }}",
+        PLAYER_COLUMNS
+            .iter()
+            .map(|name| match *name {
+                "game" | "seed" => format!("REQUIRED INT64 {} (INTEGER(64, false));", name),
+                "win_points" | "deal_in_points" => format!("REQUIRED INT64 {};", name),
+                "strategy" => format!("REQUIRED BYTE_ARRAY {} (STRING);", name),
+                "bust" => format!("REQUIRED BOOLEAN {};", name),
+                _ => format!("REQUIRED INT32 {};", name),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    ))
+    .map_err(io::Error::other)?;
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .set_key_value_metadata(Some(vec![
+            KeyValue::new("version".to_string(), VERSION.to_string()),
+            KeyValue::new("experiment".to_string(), experiment.metadata()),
+        ]))
+        .build();
+    let writer =
+        SerializedFileWriter::new(File::create(path)?, Arc::new(schema), Arc::new(properties))
+            .map_err(io::Error::other)?;
+    Ok(Sink::Parquet(writer))
+}
+
+#[cfg(not(feature = "parquet"))]
+fn parquet_sink(_path: &Path, _experiment: &Experiment) -> Result<Sink> {
+    Err(io::Error::other(
+        "Parquet output needs a build with the `parquet` feature",
+    ))
+}
+
+/// A player's row read back; the strategy and uma follow from the experiment and scores.
+#[derive(Deserialize)]
+struct PlayerRecord {
+    game: usize,
+    seed: u64,
+    player: usize,
+    seat: usize,
+    score: i32,
+    #[serde(flatten)]
+    stats: PlayerStats,
+}
+
+/// Puts the rows of one game back together, `None` unless they are the four players of the
+/// same game.
+fn game_from_records(records: &[PlayerRecord]) -> Option<GameResult> {
+    let first = records.first()?;
+    let mut ordered: Vec<&PlayerRecord> = records.iter().collect();
+    ordered.sort_by_key(|record| record.player);
+    let whole = ordered.len() == 4
+        && ordered
+            .iter()
+            .enumerate()
+            .all(|(player, record)| record.player == player && record.game == first.game);
+    if !whole {
+        return None;
+    }
+    Some(GameResult {
+        player_1_score: ordered[0].score,
+        player_2_score: ordered[1].score,
+        player_3_score: ordered[2].score,
+        player_4_score: ordered[3].score,
+        seed: first.seed,
+        seats: ordered.iter().map(|record| record.seat).collect(),
+        stats: ordered.iter().map(|record| record.stats.clone()).collect(),
+    })
+}
+
+/// What a results file holds.
+pub struct ResultsFile {
+    pub experiment: Experiment,
+    /// Whole games read
+    pub games: usize,
+    /// Bytes up to the end of the last whole game, where a resumed text file carries on
+    pub length: u64,
+}
+
+/// Reads a file written by `ResultWriter`, handing each of its first `limit` whole games to
+/// `each`. A text file may end part way through a game, as an interrupted run leaves it;
+/// games from `dat` and `csv` files come without statistics. The format is recognised from
+/// the file's first bytes.
+pub fn read_game_results(
+    path: &Path,
+    limit: usize,
+    each: impl FnMut(GameResult),
+) -> Result<ResultsFile> {
+    let mut file = File::open(path)?;
+    let mut magic = [0; 4];
+    let read = file.read(&mut magic)?;
+    file.seek(SeekFrom::Start(0))?;
+    match &magic[..read] {
+        b"PAR1" => read_parquet_results(file, limit, each),
+        [b'{', ..] => read_json_results(file, limit, each),
+        _ => read_text_results(file, limit, each),
+    }
+}
+
+/// The experiment in a results file's header.
+pub fn read_experiment(path: &Path) -> Result<Experiment> {
+    Ok(read_game_results(path, 0, |_| {})?.experiment)
+}
+
+fn invalid_data(message: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn parse_field<T: std::str::FromStr>(field: Option<&str>, line: usize) -> Result<T> {
+    field
+        .and_then(|field| field.trim().parse().ok())
+        .ok_or_else(|| invalid_data(format!("line {}: malformed game", line)))
+}
+
+fn read_text_results(
+    file: File,
+    limit: usize,
+    mut each: impl FnMut(GameResult),
+) -> Result<ResultsFile> {
+    let mut reader = BufReader::new(file);
+    let mut metadata = String::new();
+    let mut experiment: Option<Experiment> = None;
+    let (mut games, mut length, mut offset) = (0, 0, 0);
+    let mut records: Vec<PlayerRecord> = Vec::new();
+    // Told apart by their column headers, `dat` having none
+    let mut players = false;
+    let mut line = String::new();
+    for number in 1.. {
+        line.clear();
+        let read = reader.read_line(&mut line)?;
+        // A line without its newline was cut off mid-write
+        if read == 0 || !line.ends_with('\n') {
+            break;
+        }
+        offset += read as u64;
+        let text = line.trim_end();
+        if let Some(comment) = text.strip_prefix('#') {
+            if experiment.is_none() && !comment.starts_with(" mahjong-simulator") {
+                metadata.push_str(comment.strip_prefix(' ').unwrap_or(comment));
+                metadata.push('\n');
+            }
+            length = offset;
+            continue;
+        }
+        if experiment.is_none() {
+            experiment = Some(toml::from_str(&metadata).map_err(invalid_data)?);
+        }
+        if text.starts_with("player_1,") || text.starts_with("game,") {
+            players = text.starts_with("game,");
+            length = offset;
+            continue;
+        }
+        if games >= limit {
+            break;
+        }
+        let experiment = experiment.as_ref().expect("parsed above");
+        if players {
+            // The strategy may be quoted and hold commas, so it's whatever the other
+            // fields leave over
+            let mut head = text.splitn(5, ',');
+            let (game, seed, player, seat) = (
+                parse_field(head.next(), number)?,
+                parse_field(head.next(), number)?,
+                parse_field(head.next(), number)?,
+                parse_field(head.next(), number)?,
+            );
+            let mut tail = head.next().unwrap_or_default().rsplitn(16, ',');
+            let bust = parse_field(tail.next(), number)?;
+            let mut numbers: Vec<i64> = Vec::new();
+            for _ in 0..14 {
+                numbers.push(parse_field(tail.next(), number)?);
+            }
+            numbers.reverse();
+            let stats = PlayerStats {
+                final_points: numbers[0] as i32,
+                placement: numbers[3] as usize,
+                rounds: numbers[4] as u32,
+                tsumo_wins: numbers[5] as u32,
+                ron_wins: numbers[6] as u32,
+                deal_ins: numbers[7] as u32,
+                win_points: numbers[8],
+                deal_in_points: numbers[9],
+                riichi: numbers[10] as u32,
+                calls: numbers[11] as u32,
+                exhaustive_draws: numbers[12] as u32,
+                tenpai_at_draw: numbers[13] as u32,
+                bust,
+                ..PlayerStats::default()
+            };
+            records.push(PlayerRecord {
+                game,
+                seed,
+                player,
+                seat,
+                score: numbers[2] as i32,
+                stats,
+            });
+            if records.len() < 4 {
+                continue;
+            }
+            let result = game_from_records(&records)
+                .ok_or_else(|| invalid_data(format!("line {}: incomplete game", number)))?;
+            records.clear();
+            each(result);
+        } else {
+            let mut fields = text.split(',');
+            let scores: [i32; 4] = [
+                parse_field(fields.next(), number)?,
+                parse_field(fields.next(), number)?,
+                parse_field(fields.next(), number)?,
+                parse_field(fields.next(), number)?,
+            ];
+            let seating = experiment
+                .rotation
+                .seating(experiment.game_range().start + games);
+            each(GameResult {
+                player_1_score: scores[0],
+                player_2_score: scores[1],
+                player_3_score: scores[2],
+                player_4_score: scores[3],
+                seed: parse_field(fields.next(), number)?,
+                seats: (0..4)
+                    .map(|player| seating.iter().position(|&entry| entry == player).unwrap())
+                    .collect(),
+                stats: Vec::new(),
+            });
+        }
+        games += 1;
+        length = offset;
+    }
+    let experiment = match experiment {
+        Some(experiment) => experiment,
+        None => toml::from_str(&metadata).map_err(invalid_data)?,
+    };
+    Ok(ResultsFile {
+        experiment,
+        games,
+        length,
+    })
+}
+
+fn read_json_results(
+    file: File,
+    limit: usize,
+    mut each: impl FnMut(GameResult),
+) -> Result<ResultsFile> {
+    #[derive(Deserialize)]
+    struct JsonFile {
+        experiment: Experiment,
+        players: Vec<PlayerRecord>,
+    }
+    let length = file.metadata()?.len();
+    let json: JsonFile = serde_json::from_reader(BufReader::new(file))?;
+    let mut games = 0;
+    for records in json.players.chunks(4).take(limit) {
+        each(game_from_records(records).ok_or_else(|| invalid_data("incomplete game"))?);
+        games += 1;
+    }
+    Ok(ResultsFile {
+        experiment: json.experiment,
+        games,
+        length,
+    })
+}
+
+#[cfg(feature = "parquet")]
+fn read_parquet_results(
+    file: File,
+    limit: usize,
+    mut each: impl FnMut(GameResult),
+) -> Result<ResultsFile> {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::RowAccessor;
+
+    let length = file.metadata()?.len();
+    let reader = SerializedFileReader::new(file).map_err(invalid_data)?;
+    let metadata = reader
+        .metadata()
+        .file_metadata()
+        .key_value_metadata()
+        .and_then(|entries| entries.iter().find(|entry| entry.key == "experiment"))
+        .and_then(|entry| entry.value.clone())
+        .ok_or_else(|| invalid_data("Parquet file has no experiment in its metadata"))?;
+    let experiment: Experiment = toml::from_str(&metadata).map_err(invalid_data)?;
+    let mut games = 0;
+    let mut records: Vec<PlayerRecord> = Vec::new();
+    for row in reader.get_row_iter(None).map_err(invalid_data)? {
+        if games >= limit {
+            break;
+        }
+        let row = row.map_err(invalid_data)?;
+        let int = |column| row.get_int(column).map_err(invalid_data);
+        let long = |column| row.get_long(column).map_err(invalid_data);
+        let unsigned = |column| row.get_ulong(column).map_err(invalid_data);
+        records.push(PlayerRecord {
+            game: unsigned(0)? as usize,
+            seed: unsigned(1)?,
+            player: int(2)? as usize,
+            seat: int(3)? as usize,
+            score: int(7)?,
+            stats: PlayerStats {
+                final_points: int(5)?,
+                placement: int(8)? as usize,
+                rounds: int(9)? as u32,
+                tsumo_wins: int(10)? as u32,
+                ron_wins: int(11)? as u32,
+                deal_ins: int(12)? as u32,
+                win_points: long(13)?,
+                deal_in_points: long(14)?,
+                riichi: int(15)? as u32,
+                calls: int(16)? as u32,
+                exhaustive_draws: int(17)? as u32,
+                tenpai_at_draw: int(18)? as u32,
+                bust: row.get_bool(19).map_err(invalid_data)?,
+                ..PlayerStats::default()
+            },
+        });
+        if records.len() == 4 {
+            each(game_from_records(&records).ok_or_else(|| invalid_data("incomplete game"))?);
+            records.clear();
+            games += 1;
+        }
+    }
+    Ok(ResultsFile {
+        experiment,
+        games,
+        length,
+    })
+}
+
+#[cfg(not(feature = "parquet"))]
+fn read_parquet_results(
+    _file: File,
+    _limit: usize,
+    _each: impl FnMut(GameResult),
+) -> Result<ResultsFile> {
+    Err(io::Error::other(
+        "Parquet results need a build with the `parquet` feature",
+    ))
+}
+
+/// One CSV row per strategy, with the average shanten after each turn's discard in the
+/// last columns.
+pub fn write_strategy_stats(
+    path: &Path,
+    totals: &BTreeMap<String, StrategyStats>,
+    metadata: &str,
+) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_header(&mut writer, metadata)?;
+    let turns = totals
+        .values()
+        .map(|strategy| strategy.totals.shanten_by_turn.len())
+        .max()
+        .unwrap_or(0);
+    write!(
+        &mut writer,
+        "strategy,games,rounds,tsumo_wins,ron_wins,deal_ins,win_rate,deal_in_rate,\
+         average_win,average_deal_in,riichi_rate,call_rate,tenpai_rate,first,second,third,\
+         fourth,average_placement,busts"
+    )?;
+    for turn in 1..=turns {
+        write!(&mut writer, ",shanten_{}", turn)?;
+    }
+    writeln!(&mut writer)?;
+    for (label, strategy) in totals {
+        write!(
+            &mut writer,
+            "{},{},{},{},{},{},{:.4},{:.4},{:.1},{:.1},{:.4},{:.4},{:.4},{},{},{},{},{:.3},{}",
+            csv_field(label),
+            strategy.games,
+            strategy.totals.rounds,
+            strategy.totals.tsumo_wins,
+            strategy.totals.ron_wins,
+            strategy.totals.deal_ins,
+            strategy.win_rate(),
+            strategy.deal_in_rate(),
+            strategy.average_win(),
+            strategy.average_deal_in(),
+            strategy.riichi_rate(),
+            strategy.call_rate(),
+            strategy.tenpai_rate(),
+            strategy.placements[0],
+            strategy.placements[1],
+            strategy.placements[2],
+            strategy.placements[3],
+            strategy.average_placement(),
+            strategy.busts
+        )?;
+        let shanten = strategy.average_shanten();
+        for turn in 0..turns {
+            match shanten.get(turn) {
+                Some(average) => write!(&mut writer, ",{:.3}", average)?,
+                None => write!(&mut writer, ",")?,
+            }
+        }
+        writeln!(&mut writer)?;
+    }
+    writer.flush()
+}
+
+#[test]
+fn test_write_game_results() {
+    let experiment = Experiment::parse(
+        "seed = 7\n[[seats]]\nstrategy = \"completor\"\n[[seats]]\nstrategy = \"completor\"\n\
+         [[seats]]\nstrategy = \"standard\"\n[[seats]]\nstrategy = \"standard\"\n",
+    )
+    .unwrap()
+    .remove(0);
+    let stats = |placement, final_points| PlayerStats {
+        rounds: 8,
+        placement,
+        final_points,
+        ..PlayerStats::default()
+    };
+    let game = GameResult {
+        player_1_score: 45000,
+        player_2_score: -15000,
+        player_3_score: 17000,
+        player_4_score: 53000,
+        seed: 42,
+        seats: vec![1, 2, 3, 0],
+        stats: vec![
+            stats(2, 30000),
+            stats(4, 0),
+            stats(3, 20000),
+            stats(1, 50000),
+        ],
+    };
+    let results = vec![game.clone(), GameResult { seed: 43, ..game }];
+    let directory = std::env::temp_dir().join(format!("mahjong-output-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let written = |format: OutputFormat| {
+        let path = directory.join(format!("results.{}", format.extension()));
+        let mut writer = ResultWriter::create(&path, format, &experiment).unwrap();
+        for result in &results {
+            writer.write(result).unwrap();
+        }
+        writer.finish().unwrap();
+        path
+    };
+
+    let text = std::fs::read_to_string(written(OutputFormat::Csv)).unwrap();
+    assert!(text.starts_with(&format!("# mahjong-simulator {}\n", VERSION)));
+    assert!(
+        text.ends_with("player_1,player_2,player_3,player_4,seed\n45000,-15000,17000,53000,42\n45000,-15000,17000,53000,43\n")
+    );
+
+    let text = std::fs::read_to_string(written(OutputFormat::PlayersCsv)).unwrap();
+    let rows: Vec<&str> = text.lines().filter(|line| !line.starts_with('#')).collect();
+    assert_eq!(rows[0], PLAYER_COLUMNS.join(","));
+    assert_eq!(
+        rows[2],
+        "0,42,1,2,completor,0,-15000,-15000,4,8,0,0,0,0,0,0,0,0,0,false"
+    );
+    assert!(rows[8].starts_with("1,43,3,0,standard,50000,3000,53000,1,"));
+    assert_eq!(rows.len(), 9);
+
+    let text = std::fs::read_to_string(written(OutputFormat::Json)).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(json["experiment"]["seed"], 7);
+    assert_eq!(json["players"][3]["strategy"], "standard");
+    assert_eq!(json["players"][3]["uma"], 3000);
+    assert_eq!(json["players"][7]["game"], 1);
+
+    #[cfg(feature = "parquet")]
+    {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+        use parquet::record::RowAccessor;
+        let file = File::open(written(OutputFormat::Parquet)).unwrap();
+        let reader = SerializedFileReader::new(file).unwrap();
+        let metadata = reader.metadata().file_metadata();
+        assert_eq!(metadata.num_rows(), 8);
+        assert_eq!(metadata.schema_descr().num_columns(), PLAYER_COLUMNS.len());
+        let experiment_metadata = metadata
+            .key_value_metadata()
+            .unwrap()
+            .iter()
+            .find(|entry| entry.key == "experiment")
+            .and_then(|entry| entry.value.clone())
+            .unwrap();
+        assert!(experiment_metadata.contains("seed = 7"));
+        let row = reader.get_row_iter(None).unwrap().nth(3).unwrap().unwrap();
+        assert_eq!(row.get_string(4).unwrap(), "standard");
+        assert_eq!(row.get_int(7).unwrap(), 53000);
+    }
+
+    // Every format reads back into the same games, statistics only where they were written
+    for format in [
+        OutputFormat::Csv,
+        OutputFormat::PlayersCsv,
+        OutputFormat::Json,
+        #[cfg(feature = "parquet")]
+        OutputFormat::Parquet,
+    ] {
+        let mut games = Vec::new();
+        let file =
+            read_game_results(&written(format), usize::MAX, |game| games.push(game)).unwrap();
+        assert_eq!(file.experiment, experiment);
+        assert_eq!(file.games, 2);
+        assert_eq!(games[1].seed, 43);
+        assert_eq!(games[1].player_4_score, 53000);
+        if format != OutputFormat::Csv {
+            assert_eq!(games[1].seats, vec![1, 2, 3, 0]);
+            assert_eq!(games[1].stats, results[1].stats);
+        }
+    }
+
+    // A file cut off part way through a game keeps the games before it
+    let path = written(OutputFormat::PlayersCsv);
+    let text = std::fs::read_to_string(&path).unwrap();
+    std::fs::write(&path, &text[..text.len() - 30]).unwrap();
+    let file = read_game_results(&path, usize::MAX, |_| {}).unwrap();
+    assert_eq!(file.games, 1);
+    let mut writer = ResultWriter::append(
+        &path,
+        OutputFormat::PlayersCsv,
+        &experiment,
+        file.games,
+        file.length,
+    )
+    .unwrap();
+    writer.write(&results[1]).unwrap();
+    writer.finish().unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), text);
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_write_strategy_stats() {
+    let mut totals = BTreeMap::new();
+    let mut stats = StrategyStats::default();
+    stats.add(&PlayerStats {
+        rounds: 8,
+        placement: 1,
+        ..PlayerStats::default()
+    });
+    totals.insert("efficiency(a=1,b=2)".to_string(), stats.clone());
+    totals.insert("standard".to_string(), stats);
+    let path = std::env::temp_dir().join(format!("mahjong-strategies-{}.csv", std::process::id()));
+    write_strategy_stats(&path, &totals, "").unwrap();
+    let text = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let rows: Vec<&str> = text.lines().filter(|line| !line.starts_with('#')).collect();
+    assert!(rows[1].starts_with("\"efficiency(a=1,b=2)\",1,8,"));
+    assert!(rows[2].starts_with("standard,1,8,"));
+    assert_eq!(
+        rows[1].split(',').count(),
+        rows[0].split(',').count() + 1,
+        "only the quoted comma is extra"
+    );
+}
+
+#[test]
+fn test_csv_field() {
+    assert_eq!(csv_field("standard"), "standard");
+    assert_eq!(csv_field("efficiency(a=1,b=2)"), "\"efficiency(a=1,b=2)\"");
+}
@@ -0,0 +1,88 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use mahjong_simulator::run::RunProgress;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+use std::time::Duration;
+
+// Long runs show a progress bar with the running mean score of every strategy, and stop
+// cleanly on Ctrl-C: the games already started finish and everything played so far is
+// written. A second Ctrl-C quits at once.
+
+/// Set by the first Ctrl-C, and handed to runs so they stop starting games.
+pub static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static HANDLER: Once = Once::new();
+
+pub fn catch_interrupt() {
+    HANDLER.call_once(|| {
+        let result = ctrlc::set_handler(|| {
+            if INTERRUPTED.swap(true, Ordering::SeqCst) {
+                std::process::exit(130);
+            }
+            eprintln!("\nFinishing the games in progress, Ctrl-C again to quit now");
+        });
+        if let Err(error) = result {
+            println!("Error occured while catching Ctrl-C: {}", error);
+        }
+    });
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Progress of a run, drawn on stderr when it is a terminal.
+pub struct Progress {
+    bar: ProgressBar,
+    totals: BTreeMap<String, (f64, usize)>,
+}
+
+impl Progress {
+    pub fn new() -> Progress {
+        Progress {
+            bar: ProgressBar::hidden(),
+            totals: BTreeMap::new(),
+        }
+    }
+}
+
+impl RunProgress for Progress {
+    /// Starts the bar for `games` games of which `done` are already played.
+    fn start(&mut self, games: usize, done: usize) {
+        if done > 0 {
+            println!("Resuming after {} games", done);
+        }
+        self.bar = ProgressBar::new(games as u64).with_position(done as u64);
+        self.bar.set_style(
+            ProgressStyle::with_template(
+                "{wide_bar} {pos}/{len} games, {elapsed} elapsed, ETA {eta}\n{msg}",
+            )
+            .expect("progress template is valid"),
+        );
+        self.bar.enable_steady_tick(Duration::from_millis(200));
+    }
+
+    /// Counts a finished game, given each player's strategy and score.
+    fn add(&mut self, labels: &[String], scores: &[i32]) {
+        for (label, score) in labels.iter().zip(scores) {
+            let total = self.totals.entry(label.clone()).or_default();
+            total.0 += *score as f64;
+            total.1 += 1;
+        }
+        self.bar.inc(1);
+        if self.bar.position().is_multiple_of(64) || self.bar.length() == Some(self.bar.position())
+        {
+            let averages: Vec<String> = self
+                .totals
+                .iter()
+                .map(|(label, (sum, count))| format!("{} {:.0}", label, sum / *count as f64))
+                .collect();
+            self.bar
+                .set_message(format!("mean score: {}", averages.join(", ")));
+        }
+    }
+
+    fn finish(&mut self) {
+        self.bar.finish_and_clear();
+    }
+}
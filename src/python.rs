@@ -0,0 +1,593 @@
+use crate::engine::simulate_game;
+use crate::event_log::EventLog;
+use crate::scoring::score_hand as score;
+use crate::seeding::{game_seed, random_seed};
+use crate::stats::{game_stats, PlayerStats};
+use crate::strategies::{strategy_by_name, strategy_parameters};
+use crate::types::mahjong_tile::MahjongTile;
+use crate::types::notation::parse_tiles;
+use crate::types::tile_counts::{index_to_tile, is_agari, tile_index, to_counts};
+use crate::types::*;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+// The Python module: hands in and out as MPSZ notation, tiles as strings like "5m" or "0p",
+// and simulated games as columns ready for pandas. A strategy can be a Python callable
+// taking the same input the built-in ones get, as a dict.
+
+/// Riichi mahjong hand analysis, scoring and simulation.
+#[pymodule]
+fn mahjong_simulator(module: &Bound<PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(parse_hand, module)?)?;
+    module.add_function(wrap_pyfunction!(shanten, module)?)?;
+    module.add_function(wrap_pyfunction!(waits, module)?)?;
+    module.add_function(wrap_pyfunction!(ukeire, module)?)?;
+    module.add_function(wrap_pyfunction!(score_hand, module)?)?;
+    module.add_function(wrap_pyfunction!(simulate, module)?)?;
+    module.add("STRATEGY_NAMES", crate::strategies::STRATEGY_NAMES.to_vec())?;
+    Ok(())
+}
+
+fn value_error(error: impl std::fmt::Display) -> PyErr {
+    PyValueError::new_err(error.to_string())
+}
+
+/// Parses a hand and checks it holds `sizes` tiles, counting each call as three.
+fn read_hand(notation: &str, sizes: &[usize]) -> PyResult<Hand> {
+    let hand: Hand = notation.parse().map_err(value_error)?;
+    let tiles = hand.tile_count();
+    if !sizes.contains(&tiles) {
+        let expected: Vec<String> = sizes.iter().map(|size| size.to_string()).collect();
+        return Err(value_error(format!(
+            "expected a hand of {} tiles, '{}' has {}",
+            expected.join(" or "),
+            notation,
+            tiles
+        )));
+    }
+    Ok(hand)
+}
+
+fn tile_names(tiles: &[MahjongTile]) -> Vec<String> {
+    tiles.iter().map(|tile| tile.to_string()).collect()
+}
+
+/// Splits a hand into its closed tiles and called melds.
+#[pyfunction]
+fn parse_hand<'py>(py: Python<'py>, hand: &str) -> PyResult<Bound<'py, PyDict>> {
+    let hand: Hand = hand.parse().map_err(value_error)?;
+    let parsed = PyDict::new(py);
+    parsed.set_item("closed", tile_names(&hand.closed))?;
+    let called: Vec<Vec<String>> = hand.called.iter().map(|meld| tile_names(meld)).collect();
+    parsed.set_item("called", called)?;
+    Ok(parsed)
+}
+
+/// Tiles away from tenpai for a hand of 13 or 14 tiles; tenpai is 0 and complete -1.
+#[pyfunction]
+fn shanten(hand: &str) -> PyResult<i8> {
+    let hand = read_hand(hand, &[13, 14])?;
+    Ok(tile_counts::shanten(&to_counts(&hand.closed)))
+}
+
+/// Tiles that complete a hand of 13 tiles.
+#[pyfunction]
+fn waits(hand: &str) -> PyResult<Vec<String>> {
+    let hand = read_hand(hand, &[13])?;
+    Ok(tile_names(&tile_counts::waits(&to_counts(&hand.closed))))
+}
+
+/// Tiles that bring a hand of 13 tiles closer to complete, with how many of each are left
+/// once the hand and the `visible` tiles, e.g. discards and dora indicators, are counted.
+#[pyfunction]
+#[pyo3(signature = (hand, visible = ""))]
+fn ukeire(hand: &str, visible: &str) -> PyResult<Vec<(String, u8)>> {
+    let hand = read_hand(hand, &[13])?;
+    let counts = to_counts(&hand.closed);
+    let mut seen = to_counts(&hand.called.concat());
+    for tile in parse_tiles(visible).map_err(value_error)? {
+        seen[tile_index(&tile)] += 1;
+    }
+    for (index, count) in counts.iter().enumerate() {
+        seen[index] += count;
+        if seen[index] > 4 {
+            return Err(value_error(format!(
+                "more than four copies of {} in sight",
+                index_to_tile(index)
+            )));
+        }
+    }
+    Ok(tile_counts::ukeire(&counts, &seen)
+        .into_iter()
+        .map(|(tile, left)| (tile.to_string(), left))
+        .collect())
+}
+
+/// Scores a complete hand, the last closed tile being the one won on, with its called melds
+/// in brackets counted toward yaku and fu.
+#[pyfunction]
+#[pyo3(signature = (hand, tsumo = true, seat_wind = "east"))]
+fn score_hand<'py>(
+    py: Python<'py>,
+    hand: &str,
+    tsumo: bool,
+    seat_wind: &str,
+) -> PyResult<Bound<'py, PyDict>> {
+    let notation = hand;
+    let hand = read_hand(notation, &[14])?;
+    let seat_wind: SeatWind = seat_wind.parse().map_err(value_error)?;
+    if !is_agari(&to_counts(&hand.closed)) {
+        return Err(value_error(format!(
+            "'{}' is not a complete hand",
+            notation
+        )));
+    }
+    let result = score(&hand.closed, &hand.called, tsumo, &seat_wind);
+    let scored = PyDict::new(py);
+    scored.set_item("han", result.han)?;
+    scored.set_item("fu", result.fu)?;
+    scored.set_item("base_points", result.base_points)?;
+    let yaku: Vec<(String, i32)> = result
+        .yaku
+        .into_iter()
+        .map(|yaku| (yaku.name, yaku.han))
+        .collect();
+    scored.set_item("yaku", yaku)?;
+    Ok(scored)
+}
+
+/// A strategy written in Python: an object with a `discard` method, and optionally `tsumo`,
+/// `call_pon`, `call_chi`, `kan` and `riichi`, or just a function that picks the discard.
+/// The first error it raises stops the run and is raised again once the games in progress
+/// are done; until then the strategy answers as the defaults would.
+struct PythonStrategy {
+    object: Py<PyAny>,
+    params: StrategyParams,
+    error: Arc<Mutex<Option<PyErr>>>,
+}
+
+impl PythonStrategy {
+    fn ask<T>(
+        &self,
+        method: &str,
+        input: &StrategyInput,
+        read: impl FnOnce(&Bound<PyAny>) -> PyResult<T>,
+    ) -> Option<T> {
+        if self.error.lock().unwrap().is_some() {
+            return None;
+        }
+        let answer = Python::attach(|py| {
+            let object = self.object.bind(py);
+            let function = match object.getattr_opt(method)? {
+                Some(function) => function,
+                None if method == "discard" => object.clone(),
+                None => return Ok(None),
+            };
+            let answer = function.call1((input_dict(py, input)?,))?;
+            read(&answer).map(Some)
+        });
+        answer.unwrap_or_else(|error| {
+            self.error.lock().unwrap().get_or_insert(error);
+            None
+        })
+    }
+
+    fn ask_bool(&self, method: &str, input: &StrategyInput, default: bool) -> bool {
+        self.ask(method, input, |answer| answer.is_truthy())
+            .unwrap_or(default)
+    }
+}
+
+impl Strategy for PythonStrategy {
+    fn discard(&self, input: &StrategyInput) -> usize {
+        let tiles = input.hand.len();
+        self.ask("discard", input, |answer| match answer.extract::<usize>() {
+            Ok(index) if index < tiles => Ok(index),
+            _ => Err(value_error(format!(
+                "discard must return an index into the {} tiles of the hand, got {}",
+                tiles, answer
+            ))),
+        })
+        .unwrap_or(tiles - 1)
+    }
+
+    fn tsumo(&self, input: &StrategyInput) -> bool {
+        self.ask_bool("tsumo", input, true)
+    }
+
+    fn call_pon(&self, input: &StrategyInput) -> bool {
+        self.ask_bool("call_pon", input, false)
+    }
+
+    fn call_chi(&self, input: &StrategyInput) -> bool {
+        self.ask_bool("call_chi", input, false)
+    }
+
+    fn kan(&self, input: &StrategyInput) -> bool {
+        self.ask_bool("kan", input, true)
+    }
+
+    fn riichi(&self, input: &StrategyInput) -> bool {
+        self.ask_bool("riichi", input, true)
+    }
+
+    fn params(&self) -> StrategyParams {
+        self.params.clone()
+    }
+}
+
+fn input_dict<'py>(py: Python<'py>, input: &StrategyInput) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("hand", tile_names(&input.hand))?;
+    let discards: Vec<Vec<String>> = input.discards.iter().map(|d| tile_names(d)).collect();
+    dict.set_item("discards", discards)?;
+    let seat_wind = match input.seat_wind {
+        SeatWind::East => "east",
+        SeatWind::South => "south",
+        SeatWind::West => "west",
+        SeatWind::North => "north",
+    };
+    dict.set_item("seat_wind", seat_wind)?;
+    dict.set_item("round", input.round_number)?;
+    dict.set_item("params", input.params.as_ref().clone())?;
+    Ok(dict)
+}
+
+/// Checks that a config dict has no keys but `known`.
+fn check_keys(dict: &Bound<PyDict>, what: &str, known: &[&str]) -> PyResult<()> {
+    for key in dict.keys() {
+        let key: String = key.extract()?;
+        if !known.contains(&key.as_str()) {
+            return Err(value_error(format!(
+                "unknown {} key '{}', expected one of {}",
+                what,
+                key,
+                known.join(", ")
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// A seat of the line-up and the label its results go under.
+fn read_seat(
+    seat: &Bound<PyAny>,
+    error: &Arc<Mutex<Option<PyErr>>>,
+) -> PyResult<(Arc<dyn Strategy>, String)> {
+    let (strategy, params, name) = match seat.cast::<PyDict>() {
+        Ok(dict) => {
+            check_keys(dict, "seat", &["strategy", "params", "name"])?;
+            let strategy = dict
+                .get_item("strategy")?
+                .ok_or_else(|| value_error("a seat needs a strategy"))?;
+            let params: BTreeMap<String, f64> = match dict.get_item("params")? {
+                Some(params) => params.extract()?,
+                None => BTreeMap::new(),
+            };
+            let name: Option<String> = match dict.get_item("name")? {
+                Some(name) => Some(name.extract()?),
+                None => None,
+            };
+            (strategy, params, name)
+        }
+        Err(_) => (seat.clone(), BTreeMap::new(), None),
+    };
+
+    if let Ok(builtin) = strategy.extract::<String>() {
+        let Some(found) = strategy_by_name(&builtin) else {
+            return Err(value_error(format!("unknown strategy '{}'", builtin)));
+        };
+        let mut merged: BTreeMap<String, f64> = strategy_parameters(&builtin)
+            .iter()
+            .map(|&(param, value)| (param.to_string(), value))
+            .collect();
+        let changed: Vec<String> = params
+            .iter()
+            .filter(|(param, value)| merged.get(*param) != Some(value))
+            .map(|(param, value)| format!("{}={}", param, value))
+            .collect();
+        merged.extend(params);
+        let label = name.unwrap_or_else(|| {
+            if changed.is_empty() {
+                builtin.clone()
+            } else {
+                format!("{}({})", builtin, changed.join(","))
+            }
+        });
+        let strategy = crate::strategies::BuiltinStrategy {
+            params: Arc::new(merged),
+            ..found
+        };
+        return Ok((Arc::new(strategy), label));
+    }
+
+    if !strategy.is_callable() && !strategy.hasattr("discard")? {
+        return Err(value_error(format!(
+            "a seat is a strategy name, a callable or an object with a discard method, got {}",
+            strategy
+        )));
+    }
+    let label = match name {
+        Some(name) => name,
+        None => match strategy.getattr_opt("__name__")? {
+            Some(name) => name.extract()?,
+            None => strategy.get_type().name()?.extract()?,
+        },
+    };
+    let strategy = PythonStrategy {
+        object: strategy.unbind(),
+        params: Arc::new(params),
+        error: error.clone(),
+    };
+    Ok((Arc::new(strategy), label))
+}
+
+fn read_ruleset(ruleset: Option<Bound<PyAny>>) -> PyResult<Ruleset> {
+    let Some(ruleset) = ruleset else {
+        return Ok(Ruleset::default());
+    };
+    let preset = |name: &str| {
+        Ruleset::by_name(name).ok_or_else(|| value_error(format!("unknown ruleset '{}'", name)))
+    };
+    if let Ok(name) = ruleset.extract::<String>() {
+        return preset(&name);
+    }
+    let dict = ruleset.cast::<PyDict>()?;
+    check_keys(
+        dict,
+        "ruleset",
+        &["preset", "rounds", "uma", "tobi", "starting_points"],
+    )?;
+    let mut rules = match dict.get_item("preset")? {
+        Some(name) => preset(&name.extract::<String>()?)?,
+        None => Ruleset::default(),
+    };
+    if let Some(rounds) = dict.get_item("rounds")? {
+        rules.rounds = rounds.extract()?;
+    }
+    if let Some(uma) = dict.get_item("uma")? {
+        rules.uma = uma.extract()?;
+    }
+    if let Some(tobi) = dict.get_item("tobi")? {
+        rules.tobi = tobi.extract()?;
+    }
+    if let Some(points) = dict.get_item("starting_points")? {
+        rules.starting_points = points.extract()?;
+    }
+    if rules.rounds == 0 {
+        return Err(value_error("a game needs at least one round"));
+    }
+    Ok(rules)
+}
+
+/// Plays games between four seats and returns one row per player and game as a dict of
+/// columns: numbers as `array.array` buffers NumPy reads without copying, strategy labels
+/// and bust flags as lists, so `pandas.DataFrame(simulate(...))` is the results table.
+///
+/// The config takes `seats`, four built-in strategy names, callables, objects with a
+/// `discard` method or dicts of `strategy`, `params` and `name`; `games`, 1000 unless
+/// given; `seed`, random unless given; and `ruleset`, a preset name or a dict of `preset`,
+/// `rounds`, `uma`, `tobi` and `starting_points`. Players keep their seat in every game,
+/// the first one dealing first.
+#[pyfunction]
+fn simulate<'py>(py: Python<'py>, config: &Bound<'py, PyDict>) -> PyResult<Bound<'py, PyDict>> {
+    check_keys(config, "config", &["seats", "games", "seed", "ruleset"])?;
+    let seats = config
+        .get_item("seats")?
+        .ok_or_else(|| value_error("the config needs four seats"))?;
+    let error = Arc::new(Mutex::new(None));
+    let mut lineup = Vec::new();
+    let mut labels = Vec::new();
+    for seat in seats.try_iter()? {
+        let (strategy, label) = read_seat(&seat?, &error)?;
+        lineup.push(strategy);
+        labels.push(label);
+    }
+    if lineup.len() != 4 {
+        return Err(value_error(format!(
+            "the config needs four seats, got {}",
+            lineup.len()
+        )));
+    }
+    let games: usize = match config.get_item("games")? {
+        Some(games) => games.extract()?,
+        None => 1000,
+    };
+    let seed: u64 = match config.get_item("seed")? {
+        Some(seed) => seed.extract()?,
+        None => random_seed(),
+    };
+    let ruleset = read_ruleset(config.get_item("ruleset")?)?;
+
+    // Python strategies take the GIL back for every decision, built-in ones play in parallel
+    let played: Vec<Option<(u64, GameResult, Vec<PlayerStats>)>> = py.detach(|| {
+        (0..games)
+            .into_par_iter()
+            .map(|index| {
+                if error.lock().unwrap().is_some() {
+                    return None;
+                }
+                let seed = game_seed(seed, index as u64);
+                let mut log = EventLog::enabled();
+                let result = simulate_game(&ruleset, &lineup, seed, None, &mut log);
+                Some((seed, result, game_stats(&log.events)))
+            })
+            .collect()
+    });
+    if let Some(error) = error.lock().unwrap().take() {
+        return Err(error);
+    }
+
+    let mut columns: BTreeMap<&str, Vec<i64>> = BTreeMap::new();
+    let mut strategies = Vec::new();
+    let mut busts = Vec::new();
+    for (game, (seed, result, stats)) in played.into_iter().flatten().enumerate() {
+        let scores = [
+            result.player_1_score,
+            result.player_2_score,
+            result.player_3_score,
+            result.player_4_score,
+        ];
+        for (player, stats) in stats.iter().enumerate() {
+            let row = [
+                ("game", game as i64),
+                ("seed", seed as i64),
+                ("player", player as i64),
+                ("seat", player as i64),
+                ("final_points", stats.final_points as i64),
+                ("uma", (scores[player] - stats.final_points) as i64),
+                ("score", scores[player] as i64),
+                ("placement", stats.placement as i64),
+                ("rounds", stats.rounds as i64),
+                ("tsumo_wins", stats.tsumo_wins as i64),
+                ("ron_wins", stats.ron_wins as i64),
+                ("deal_ins", stats.deal_ins as i64),
+                ("win_points", stats.win_points),
+                ("deal_in_points", stats.deal_in_points),
+                ("riichi", stats.riichi as i64),
+                ("calls", stats.calls as i64),
+                ("exhaustive_draws", stats.exhaustive_draws as i64),
+                ("tenpai_at_draw", stats.tenpai_at_draw as i64),
+            ];
+            for (name, value) in row {
+                columns.entry(name).or_default().push(value);
+            }
+            strategies.push(labels[player].clone());
+            busts.push(stats.bust);
+        }
+    }
+
+    let array = py.import("array")?.getattr("array")?;
+    let table = PyDict::new(py);
+    for name in [
+        "game",
+        "seed",
+        "player",
+        "seat",
+        "strategy",
+        "final_points",
+        "uma",
+        "score",
+        "placement",
+        "rounds",
+        "tsumo_wins",
+        "ron_wins",
+        "deal_ins",
+        "win_points",
+        "deal_in_points",
+        "riichi",
+        "calls",
+        "exhaustive_draws",
+        "tenpai_at_draw",
+        "bust",
+    ] {
+        match name {
+            "strategy" => table.set_item(name, PyList::new(py, &strategies)?)?,
+            "bust" => table.set_item(name, PyList::new(py, &busts)?)?,
+            // Seeds use all 64 bits, so they are stored unsigned
+            "seed" => {
+                let seeds: Vec<u64> = columns[name].iter().map(|&seed| seed as u64).collect();
+                table.set_item(name, array.call1(("Q", seeds))?)?
+            }
+            _ => table.set_item(
+                name,
+                array.call1(("q", columns.remove(name).unwrap_or_default()))?,
+            )?,
+        }
+    }
+    Ok(table)
+}
+
+#[cfg(test)]
+fn with_module<T>(run: impl FnOnce(Python, &Bound<PyModule>) -> PyResult<T>) -> T {
+    Python::initialize();
+    Python::attach(|py| {
+        let module = PyModule::new(py, "mahjong_simulator")?;
+        mahjong_simulator(&module)?;
+        run(py, &module)
+    })
+    .unwrap()
+}
+
+#[test]
+fn test_python_hand_functions() {
+    with_module(|py, module| {
+        let globals = PyDict::new(py);
+        globals.set_item("m", module)?;
+        py.run(
+            cr#"
+assert m.parse_hand("123m0p[555z]") == {"closed": ["1m", "2m", "3m", "0p"], "called": [["5z", "5z", "5z"]]}
+assert m.shanten("123m456p789s11222z") == -1
+assert m.waits("123m456p789s1122z") == ["1z", "2z"]
+assert ("1m", 1) in m.ukeire("23m456p789s11z135s", visible="111m")
+score = m.score_hand("123m456p789s11222z", tsumo=True)
+assert (score["han"], score["fu"]) == (1, 40)
+assert ("menzen tsumo", 1) in score["yaku"]
+called = m.score_hand("234m678s55p[777z][666s]")
+assert (called["han"], called["fu"], called["yaku"]) == (1, 30, [("yakuhai", 1)])
+for bad in ["123m", "123m456p789s11223z", "12x"]:
+    try:
+        m.score_hand(bad)
+    except ValueError:
+        pass
+    else:
+        raise AssertionError(bad)
+"#,
+            Some(&globals),
+            None,
+        )
+    })
+}
+
+#[test]
+fn test_python_simulate() {
+    with_module(|py, module| {
+        let globals = PyDict::new(py);
+        globals.set_item("m", module)?;
+        py.run(
+            cr#"
+class LastTile:
+    def __init__(self):
+        self.calls = 0
+    def discard(self, input):
+        self.calls += 1
+        assert input["seat_wind"] in ("east", "south", "west", "north")
+        return len(input["hand"]) - 1
+
+def first_tile(input):
+    return 0
+
+last = LastTile()
+seats = ["efficiency", {"strategy": "efficiency", "params": {"dora_weight": 2}}, last, first_tile]
+result = m.simulate({"seats": seats, "games": 3, "seed": 5, "ruleset": "tonpuusen"})
+assert len(result["score"]) == 12
+assert result["strategy"][:4] == ["efficiency", "efficiency(dora_weight=2)", "LastTile", "first_tile"]
+assert sum(result["score"][:4]) == 100000
+assert result["seed"].typecode == "Q"
+assert last.calls > 0
+assert result == m.simulate({"seats": seats, "games": 3, "seed": 5, "ruleset": "tonpuusen"})
+
+def broken(input):
+    raise KeyError("broken strategy")
+try:
+    m.simulate({"seats": ["standard"] * 3 + [broken], "games": 2})
+except KeyError:
+    pass
+else:
+    raise AssertionError("the strategy's error is raised")
+try:
+    m.simulate({"seats": ["standard"] * 3 + [lambda input: 99], "games": 2})
+except ValueError:
+    pass
+else:
+    raise AssertionError("a bad discard index is an error")
+"#,
+            Some(&globals),
+            None,
+        )
+    })
+}
@@ -0,0 +1,284 @@
+use crate::types::mahjong_tile::*;
+use crate::types::*;
+use std::env;
+
+const POND_ROW: usize = 6;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TileStyle {
+    Unicode,
+    Ascii,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Renderer {
+    pub style: TileStyle,
+    pub colour: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PondTile {
+    pub tile: MahjongTile,
+    pub is_riichi: bool,
+    pub is_called: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeatView {
+    pub seat_wind: SeatWind,
+    pub points: i32,
+    pub hand: Hand,
+    pub pond: Vec<PondTile>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableView {
+    pub round_number: u8,
+    pub tiles_left: usize,
+    pub dora_indicators: Vec<MahjongTile>,
+    pub seats: Vec<SeatView>,
+}
+
+impl TableView {
+    /// Snapshot of a running game. The simulator doesn't play riichi yet, so no pond tile
+    /// is drawn sideways.
+    pub fn from_game(
+        round_number: u8,
+        players: &[Player],
+        player_tiles: &PlayerTiles,
+        board_tiles: &BoardTiles,
+    ) -> TableView {
+        let seats = players
+            .iter()
+            .enumerate()
+            .map(|(index, player)| SeatView {
+                seat_wind: player.seat_wind.clone(),
+                points: player.points,
+                hand: Hand {
+                    closed: player_tiles.hand[index].clone(),
+                    called: player_tiles.open_hand[index]
+                        .chunks(3)
+                        .map(|meld| meld.to_vec())
+                        .collect(),
+                },
+                pond: player_tiles.discards[index]
+                    .iter()
+                    .enumerate()
+                    .map(|(position, &tile)| PondTile {
+                        tile,
+                        is_riichi: false,
+                        is_called: player_tiles.called[index].contains(&position),
+                    })
+                    .collect(),
+            })
+            .collect();
+        TableView {
+            round_number,
+            tiles_left: board_tiles.wall.len(),
+            dora_indicators: board_tiles.dora_indicators[..=board_tiles.dora_index].to_vec(),
+            seats,
+        }
+    }
+}
+
+impl Renderer {
+    /// Unicode tiles in colour, falling back to plain ASCII when `NO_COLOR` is set or
+    /// the terminal is dumb.
+    pub fn for_terminal() -> Renderer {
+        let dumb = env::var("TERM").map(|term| term == "dumb").unwrap_or(true);
+        let no_colour = env::var_os("NO_COLOR").is_some();
+        Renderer {
+            style: if dumb {
+                TileStyle::Ascii
+            } else {
+                TileStyle::Unicode
+            },
+            colour: !dumb && !no_colour,
+        }
+    }
+
+    pub fn tile(&self, tile: &MahjongTile) -> String {
+        let text = match self.style {
+            TileStyle::Unicode => format!("{} ", unicode_tile(tile)),
+            TileStyle::Ascii => tile.to_string(),
+        };
+        let mut codes = vec![colour_code(tile)];
+        if tile.is_red {
+            codes.push("1");
+        }
+        if tile.is_dora {
+            codes.push("4");
+        }
+        self.paint(&text, &codes)
+    }
+
+    pub fn tiles(&self, tiles: &[MahjongTile]) -> String {
+        let separator = match self.style {
+            TileStyle::Unicode => "",
+            TileStyle::Ascii => " ",
+        };
+        tiles
+            .iter()
+            .map(|tile| self.tile(tile))
+            .collect::<Vec<_>>()
+            .join(separator)
+    }
+
+    pub fn hand(&self, hand: &Hand) -> String {
+        let mut result = self.tiles(&hand.closed);
+        for meld in &hand.called {
+            result.push_str("  ");
+            result.push_str(&self.tiles(meld));
+        }
+        result
+    }
+
+    /// Six discards per row; the riichi tile is drawn sideways as `(tile)` and tiles
+    /// another player called are marked with `*`.
+    pub fn pond(&self, pond: &[PondTile]) -> Vec<String> {
+        pond.chunks(POND_ROW)
+            .map(|row| {
+                row.iter()
+                    .map(|discard| self.pond_tile(discard))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect()
+    }
+
+    fn pond_tile(&self, discard: &PondTile) -> String {
+        let mut text = self.tile(&discard.tile);
+        if discard.is_called {
+            text = format!("{}*", self.paint(&text, &["2"]));
+        }
+        if discard.is_riichi {
+            text = format!("({})", self.paint(&text, &["7"]));
+        }
+        text
+    }
+
+    pub fn table(&self, table: &TableView) -> String {
+        let mut lines = vec![format!(
+            "Round {}, {} tiles left, dora indicators: {}",
+            table.round_number + 1,
+            table.tiles_left,
+            self.tiles(&table.dora_indicators)
+        )];
+        for seat in &table.seats {
+            lines.push(format!(
+                "{:<5} {:>6}  {}",
+                format!("{:?}", seat.seat_wind),
+                seat.points,
+                self.hand(&seat.hand)
+            ));
+            for row in self.pond(&seat.pond) {
+                lines.push(format!("{:14}{}", "", row));
+            }
+        }
+        lines.join("\n")
+    }
+
+    fn paint(&self, text: &str, codes: &[&str]) -> String {
+        if !self.colour || codes.is_empty() {
+            return text.to_string();
+        }
+        format!("\x1b[{}m{}\x1b[0m", codes.join(";"), text)
+    }
+}
+
+fn colour_code(tile: &MahjongTile) -> &'static str {
+    match (tile.suit, tile.value) {
+        (Suit::Manzu, _) | (Suit::Sangen, 3) => "31",
+        (Suit::Souzu, _) | (Suit::Sangen, 2) => "32",
+        (Suit::Pinzu, _) => "34",
+        _ => "37",
+    }
+}
+
+fn unicode_tile(tile: &MahjongTile) -> char {
+    let offset = match tile.suit {
+        Suit::Kaze => tile.value as u32 - 1,
+        // Haku, hatsu and chun run backwards from U+1F006
+        Suit::Sangen => 7 - tile.value as u32,
+        Suit::Manzu => 6 + tile.value as u32,
+        Suit::Souzu => 15 + tile.value as u32,
+        Suit::Pinzu => 24 + tile.value as u32,
+    };
+    char::from_u32(0x1F000 + offset).unwrap()
+}
+
+#[test]
+fn test_unicode_tiles() {
+    let tiles = crate::types::notation::parse_tiles("19m19p19s1234567z").unwrap();
+    let glyphs: String = tiles.iter().map(unicode_tile).collect();
+    assert_eq!(glyphs, "🀇🀏🀙🀡🀐🀘🀀🀁🀂🀃🀆🀅🀄");
+}
+
+#[test]
+fn test_ascii_pond() {
+    let renderer = Renderer {
+        style: TileStyle::Ascii,
+        colour: false,
+    };
+    let pond: Vec<PondTile> = crate::types::notation::parse_tiles("1z9m0p5s6s7s2z")
+        .unwrap()
+        .into_iter()
+        .enumerate()
+        .map(|(index, tile)| PondTile {
+            tile,
+            is_riichi: index == 2,
+            is_called: index == 6,
+        })
+        .collect();
+    assert_eq!(renderer.pond(&pond), vec!["1z 9m (0p) 5s 6s 7s", "2z*"]);
+}
+
+#[test]
+fn test_ascii_hand_with_melds() {
+    let renderer = Renderer {
+        style: TileStyle::Ascii,
+        colour: false,
+    };
+    let hand: Hand = "23m[555p]".parse().unwrap();
+    assert_eq!(renderer.hand(&hand), "2m 3m  5p 5p 5p");
+}
+
+#[test]
+fn test_called_pond_tile() {
+    use std::sync::Arc;
+    let winds = [
+        SeatWind::East,
+        SeatWind::South,
+        SeatWind::West,
+        SeatWind::North,
+    ];
+    let players: Vec<Player> = winds
+        .into_iter()
+        .enumerate()
+        .map(|(index, seat_wind)| Player {
+            points: 25000,
+            seat_wind,
+            strategy: Arc::new(|_: &StrategyInput| 0),
+            id: index + 1,
+        })
+        .collect();
+    // South throws 1m, 9m and a 7z that North pons
+    let parse = |tiles| crate::types::notation::parse_tiles(tiles).unwrap();
+    let mut player_tiles = PlayerTiles::default();
+    player_tiles.discards[1] = parse("19m7z");
+    player_tiles.called[1].push(2);
+    player_tiles.open_hand[3] = parse("777z");
+    let board_tiles = BoardTiles {
+        wall: Vec::new(),
+        wall_dead: Vec::new(),
+        dora_indicators: parse("1p"),
+        dora_index: 0,
+    };
+    let view = TableView::from_game(0, &players, &player_tiles, &board_tiles);
+    let renderer = Renderer {
+        style: TileStyle::Ascii,
+        colour: false,
+    };
+    assert_eq!(renderer.pond(&view.seats[1].pond), vec!["1m 9m 7z*"]);
+    assert_eq!(view.seats[3].hand.called, vec![parse("777z")]);
+}
@@ -0,0 +1,205 @@
+use itertools::Itertools;
+use mahjong_simulator::significance::*;
+use mahjong_simulator::stats::StrategyStats;
+use mahjong_simulator::tournament::{stable_dan, Standings};
+use std::collections::BTreeMap;
+
+// The tables the simulator prints after a run or tournament. The library works out the
+// numbers; everything written to the terminal is laid out here.
+
+/// Prints one line of statistics per strategy.
+pub fn print_summary(totals: &BTreeMap<String, StrategyStats>) {
+    println!(
+        "{:<28} {:>6} {:>6} {:>6} {:>6} {:>8} {:>8} {:>8} {:>7} {:>6} {:>8} {:>6} {:>6}",
+        "strategy",
+        "games",
+        "win%",
+        "tsumo",
+        "ron",
+        "deal-in%",
+        "avg win",
+        "avg loss",
+        "riichi%",
+        "call%",
+        "tenpai%",
+        "place",
+        "busts"
+    );
+    for (label, strategy) in totals {
+        println!(
+            "{:<28} {:>6} {:>6.1} {:>6} {:>6} {:>8.1} {:>8.0} {:>8.0} {:>7.1} {:>6.1} {:>8.1} {:>6.2} {:>6}",
+            label,
+            strategy.games,
+            100.0 * strategy.win_rate(),
+            strategy.totals.tsumo_wins,
+            strategy.totals.ron_wins,
+            100.0 * strategy.deal_in_rate(),
+            strategy.average_win(),
+            strategy.average_deal_in(),
+            100.0 * strategy.riichi_rate(),
+            100.0 * strategy.call_rate(),
+            100.0 * strategy.tenpai_rate(),
+            strategy.average_placement(),
+            strategy.busts
+        );
+    }
+}
+
+/// Each strategy's mean score and placement with their 95% confidence intervals, and a
+/// paired t-test of every pair of strategies.
+pub fn print_significance(games: &Tally) {
+    let summaries: Vec<(&String, Summary, Summary)> = games
+        .scores
+        .iter()
+        .zip(&games.placements)
+        .map(|((label, score), (_, placement))| {
+            (label, Summary::of_sums(score), Summary::of_sums(placement))
+        })
+        .collect();
+    println!(
+        "{:<28} {:>6} {:>10} {:>8} {:>19} {:>6} {:>6} {:>13}",
+        "strategy", "games", "score", "se", "95% ci", "place", "se", "95% ci"
+    );
+    for (label, score, placement) in &summaries {
+        println!(
+            "{:<28} {:>6} {:>10.0} {:>8.0} {:>19} {:>6.3} {:>6.3} {:>13}",
+            label,
+            score.count,
+            score.mean,
+            score.standard_error(),
+            format!(
+                "{:.0} to {:.0}",
+                score.mean - score.margin(),
+                score.mean + score.margin()
+            ),
+            placement.mean,
+            placement.standard_error(),
+            format!(
+                "{:.2} to {:.2}",
+                placement.mean - placement.margin(),
+                placement.mean + placement.margin()
+            )
+        );
+    }
+    if summaries.len() < 2 {
+        return;
+    }
+    println!("Paired t-tests of the game by game differences, * where p < 0.05:");
+    for (first, second) in games.score_differences.keys() {
+        println!(
+            "  {} vs {}: score {}, place {}",
+            first,
+            second,
+            describe(games.score_test(first, second), 0),
+            describe(games.placement_test(first, second), 3)
+        );
+    }
+}
+
+/// Pairwise differences in mean score, one sample a set of duplicate games.
+pub fn print_paired_differences(sets: &Tally) {
+    let Some(count) = sets.scores.values().map(|sums| sums.count).max() else {
+        return;
+    };
+    if sets.scores.len() < 2 {
+        return;
+    }
+    println!("Paired score differences over {} wall sets:", count);
+    for (first, second) in sets.score_differences.keys() {
+        println!(
+            "  {} vs {}: {}",
+            first,
+            second,
+            describe(sets.score_test(first, second), 0)
+        );
+    }
+}
+
+fn describe(test: Option<TTest>, decimals: usize) -> String {
+    let Some(test) = test else {
+        return "not enough games".to_string();
+    };
+    let p = if test.p < 0.0001 {
+        "p < 0.0001".to_string()
+    } else {
+        format!("p = {:.4}", test.p)
+    };
+    format!(
+        "{:+.*} ± {:.*} ({}){}",
+        decimals,
+        test.difference,
+        decimals,
+        test.margin,
+        p,
+        if test.p < 0.05 { " *" } else { "" }
+    )
+}
+
+/// Prints how a sequential run ended and the final estimate of each difference.
+pub fn print_look(look: &Look, rule: &StoppingRule, budget: usize) {
+    let confidence = rule.confidence * 100.0;
+    match (look.decided(), look.games < budget) {
+        (true, true) => println!(
+            "Stopped after {} of at most {} games, every difference is significant at {}% confidence:",
+            look.games, budget, confidence
+        ),
+        (true, false) => println!(
+            "Every difference is significant at {}% confidence after all {} games:",
+            confidence, budget
+        ),
+        (false, _) => println!(
+            "No clear difference at {}% confidence after {} of at most {} games:",
+            confidence, look.games, budget
+        ),
+    }
+    for (first, second, test) in &look.tests {
+        let line = match test {
+            Some(test) => format!(
+                "score {:+.0} ± {:.0}, p = {:.2e}, needs p < {:.2e}{}",
+                test.difference,
+                test.margin,
+                test.p,
+                look.threshold,
+                if test.p < look.threshold { " *" } else { "" }
+            ),
+            None => "not enough games".to_string(),
+        };
+        println!("  {} vs {}: {}", first, second, line);
+    }
+}
+
+/// The standings of a tournament, best rated first.
+pub fn print_leaderboard(standings: &Standings) {
+    println!(
+        "{:>4} {:<20} {:>6} {:>7} {:>10} {:>6} {:>23} {:>7}  dan",
+        "rank", "strategy", "games", "elo", "avg score", "place", "1st/2nd/3rd/4th %", "stable"
+    );
+    for (rank, standing) in standings.by_rating().iter().enumerate() {
+        let rates = standing
+            .placements
+            .iter()
+            .map(|count| {
+                format!(
+                    "{:.1}",
+                    100.0 * *count as f64 / standing.games.max(1) as f64
+                )
+            })
+            .join("/");
+        let stable = match stable_dan(&standing.placements) {
+            Some(dan) => format!("{:.2}", dan),
+            None => "-".to_string(),
+        };
+        println!(
+            "{:>4} {:<20} {:>6} {:>7.0} {:>10.0} {:>6.2} {:>23} {:>7}  {}",
+            rank + 1,
+            standing.name,
+            standing.games,
+            standing.elo,
+            standing.average_score(),
+            standing.average_placement(),
+            rates,
+            stable,
+            standing.dan
+        );
+    }
+}
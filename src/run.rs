@@ -0,0 +1,681 @@
+use crate::engine::simulate_game;
+use crate::event_log::*;
+use crate::experiment::*;
+use crate::output::*;
+use crate::seeding::game_seed;
+use crate::significance::{GameMeans, Look, Tally};
+use crate::stats::*;
+use crate::types::*;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+// A run plays the games of an experiment on every core and writes them to its files in
+// order, so a run that stops part way leaves files that resume where it ended. The shards
+// of a run merge into the files the whole run would have written.
+
+/// Games handed to the workers at a time. Results reach the files in order within a batch
+/// or so; an interrupt lets only the games already started finish.
+const BATCH_GAMES: usize = 1200;
+
+#[derive(Debug)]
+pub enum RunError {
+    /// The stopping rule compares strategies the line-up doesn't have
+    Stop(String),
+    Resume(io::Error),
+    Results(io::Error),
+    Events(io::Error),
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RunError::Stop(message) => write!(f, "{}", message),
+            RunError::Resume(error) => write!(f, "the run can't be resumed: {}", error),
+            RunError::Results(error) => write!(f, "game results can't be written: {}", error),
+            RunError::Events(error) => write!(f, "the event log can't be written: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for RunError {}
+
+/// Follows a run as its games are written, on the thread that writes them.
+pub trait RunProgress: Send {
+    /// Before the first game, with `done` of the run's `games` already played when resumed.
+    fn start(&mut self, games: usize, done: usize);
+    /// After every game, with each player's strategy and score in line-up order.
+    fn add(&mut self, labels: &[String], scores: &[i32]);
+    fn finish(&mut self);
+}
+
+/// No progress to show.
+impl RunProgress for () {
+    fn start(&mut self, _games: usize, _done: usize) {}
+    fn add(&mut self, _labels: &[String], _scores: &[i32]) {}
+    fn finish(&mut self) {}
+}
+
+/// What a run keeps of its games once they are written: running sums, so a run of any
+/// length takes the same memory.
+#[derive(Default)]
+pub struct RunTotals {
+    pub games: usize,
+    pub strategy_stats: BTreeMap<String, StrategyStats>,
+    pub history: ScoreHistory,
+    /// Each strategy's score and placement, one sample a game
+    pub by_game: Tally,
+    /// Each strategy's mean score and placement, one sample a finished set of duplicate
+    /// games
+    pub by_set: Tally,
+    set: BTreeMap<String, (f64, f64, usize)>,
+    /// The latest look at a sequential run
+    pub look: Option<Look>,
+}
+
+impl RunTotals {
+    pub fn add(&mut self, labels: &[String], result: &GameResult, games_per_wall: usize) {
+        let scores = [
+            result.player_1_score,
+            result.player_2_score,
+            result.player_3_score,
+            result.player_4_score,
+        ];
+        add_game(&mut self.strategy_stats, labels, &result.stats);
+        let mut placements = Vec::new();
+        for (player, (label, score)) in labels.iter().zip(scores).enumerate() {
+            // Games read back from a dat or csv file come without statistics
+            let placement = match result.stats.get(player) {
+                Some(stats) => stats.placement,
+                None => placement(&scores, score),
+            };
+            placements.push(placement as f64);
+            if games_per_wall > 1 {
+                let total = self.set.entry(label.clone()).or_default();
+                total.0 += score as f64;
+                total.1 += placement as f64;
+                total.2 += 1;
+            }
+        }
+        let game_scores: Vec<f64> = scores.iter().map(|&score| score as f64).collect();
+        self.by_game
+            .add(&GameMeans::of(labels, &game_scores, &placements));
+        self.history.add(scores);
+        self.games += 1;
+        if games_per_wall > 1 && self.games.is_multiple_of(games_per_wall) {
+            let mut means = GameMeans::default();
+            for (label, (score, placement, count)) in std::mem::take(&mut self.set) {
+                means.scores.insert(label.clone(), score / count as f64);
+                means.placements.insert(label, placement / count as f64);
+            }
+            self.by_set.add(&means);
+        }
+    }
+
+    /// The running sums a sequential run looks at: duplicate sets when the run plays
+    /// them, games otherwise.
+    pub fn samples(&self, games_per_wall: usize) -> &Tally {
+        if games_per_wall > 1 {
+            &self.by_set
+        } else {
+            &self.by_game
+        }
+    }
+}
+
+/// Points scored in a bucket of the score histogram.
+pub const SCORE_BUCKET: i32 = 1000;
+/// Most points kept of the cumulative scores before every other one is dropped.
+const MAX_SAMPLES: usize = 2000;
+
+/// What the score charts draw of every game, in memory that doesn't grow with the run:
+/// how many games each line-up seat ended in each bucket of scores, and every seat's
+/// running total, sampled every so many games.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoreHistory {
+    pub games: usize,
+    /// Games each seat ended in a bucket, keyed by the lowest score of the bucket
+    pub buckets: BTreeMap<i32, [usize; 4]>,
+    /// Each seat's total score after evenly spaced games, at most `MAX_SAMPLES` of them
+    pub samples: Vec<(usize, [i64; 4])>,
+    pub totals: [i64; 4],
+    every: usize,
+}
+
+impl Default for ScoreHistory {
+    fn default() -> ScoreHistory {
+        ScoreHistory {
+            games: 0,
+            buckets: BTreeMap::new(),
+            samples: Vec::new(),
+            totals: [0; 4],
+            every: 1,
+        }
+    }
+}
+
+impl ScoreHistory {
+    pub fn add(&mut self, scores: [i32; 4]) {
+        self.games += 1;
+        for (seat, score) in scores.into_iter().enumerate() {
+            let bucket = score.div_euclid(SCORE_BUCKET) * SCORE_BUCKET;
+            self.buckets.entry(bucket).or_default()[seat] += 1;
+            self.totals[seat] += score as i64;
+        }
+        if self.games.is_multiple_of(self.every) {
+            self.samples.push((self.games, self.totals));
+        }
+        if self.samples.len() == MAX_SAMPLES {
+            self.every *= 2;
+            self.samples
+                .retain(|(games, _)| games.is_multiple_of(self.every));
+        }
+    }
+}
+
+/// A run that played all its games, stopped at a look or was interrupted.
+pub struct Run {
+    /// Every game in the files, those of an earlier run that was resumed included
+    pub totals: RunTotals,
+    /// Games the run would play without stopping
+    pub planned: usize,
+    /// Errors writing a file part way, after which the run played on without it
+    pub errors: Vec<RunError>,
+}
+
+impl Run {
+    /// The look taken at the last game, when the run has a stopping rule.
+    pub fn last_look(&self) -> Option<&Look> {
+        self.totals
+            .look
+            .as_ref()
+            .filter(|look| look.games == self.totals.games)
+    }
+
+    /// Whether the run ended short of its games without its stopping rule deciding.
+    pub fn interrupted(&self) -> bool {
+        self.totals.games < self.planned && !self.last_look().is_some_and(Look::decided)
+    }
+}
+
+/// Plays `experiment` into its files, or with `resume` only the games its files don't hold
+/// yet. Once `interrupted` is set no more games start, and the run ends with the games
+/// already started.
+pub fn run_experiment(
+    experiment: &Experiment,
+    resume: bool,
+    interrupted: &AtomicBool,
+    progress: &mut impl RunProgress,
+) -> Result<Run, RunError> {
+    let seats = experiment.lineup();
+    let labels: Vec<String> = experiment.seats.iter().map(SeatConfig::label).collect();
+    let range = experiment.game_range();
+    let pairs = match &experiment.stop {
+        Some(rule) => rule.pairs(&experiment.seats).map_err(RunError::Stop)?,
+        None => Vec::new(),
+    };
+    let mut totals = RunTotals::default();
+    let files = if resume && experiment.output.path.exists() {
+        resume_run(experiment, &labels, &mut totals).map_err(RunError::Resume)?
+    } else {
+        start_run(experiment)?
+    };
+
+    // Workers send each game to a collector thread, which writes them in order as they come
+    // and decides when a sequential run has played enough
+    let (sender, receiver) = mpsc::channel();
+    let stopped = AtomicBool::new(false);
+    let stop = || interrupted.load(Ordering::SeqCst) || stopped.load(Ordering::SeqCst);
+    let run = thread::scope(|scope| {
+        let (labels, pairs, stopped) = (&labels, &pairs, &stopped);
+        let first = range.start + totals.games;
+        let collector = scope.spawn(move || {
+            collect_games(
+                receiver,
+                experiment,
+                (labels, pairs),
+                files,
+                (totals, stopped),
+                progress,
+            )
+        });
+        for start in (first..range.end).step_by(BATCH_GAMES) {
+            if stop() {
+                break;
+            }
+            (start..range.end.min(start + BATCH_GAMES))
+                .into_par_iter()
+                .for_each_with(sender.clone(), |sender, index| {
+                    // The games after the first one skipped are dropped with it
+                    if stop() {
+                        return;
+                    }
+                    let (result, log) = play_game(experiment, &seats, index);
+                    sender
+                        .send((index, result, log))
+                        .expect("collector runs until the workers are done");
+                });
+        }
+        drop(sender);
+        collector.join().expect("collector thread panicked")
+    });
+    Ok(run)
+}
+
+/// Plays the `index`th game of the unsharded run, with scores and statistics in line-up
+/// order. The events are kept only when the experiment writes them.
+fn play_game(
+    experiment: &Experiment,
+    seats: &[Arc<dyn Strategy>],
+    index: usize,
+) -> (GameResult, EventLog) {
+    // Duplicate games share their seed, and with it their walls
+    let wall = index / experiment.rotation.games_per_wall();
+    let seed = game_seed(experiment.seed.unwrap(), wall as u64);
+    let seating = experiment.rotation.seating(index);
+    let seated: Vec<Arc<dyn Strategy>> =
+        seating.iter().map(|&entry| seats[entry].clone()).collect();
+    // Statistics are read off the events, so every game is logged
+    let mut log = EventLog::enabled();
+    log.record(|| GameEvent::GameStart {
+        seed,
+        ruleset: experiment.ruleset.name.clone(),
+        strategies: seating
+            .iter()
+            .map(|&entry| experiment.seats[entry].strategy.clone())
+            .collect(),
+    });
+    let result = simulate_game(&experiment.ruleset, &seated, seed, None, &mut log);
+    // Report scores in line-up order rather than seat order
+    let mut scores = [0; 4];
+    let mut stats = vec![PlayerStats::default(); 4];
+    for (seat, (score, player)) in [
+        result.player_1_score,
+        result.player_2_score,
+        result.player_3_score,
+        result.player_4_score,
+    ]
+    .into_iter()
+    .zip(game_stats(&log.events))
+    .enumerate()
+    {
+        scores[seating[seat]] = score;
+        stats[seating[seat]] = player;
+    }
+    let result = GameResult {
+        player_1_score: scores[0],
+        player_2_score: scores[1],
+        player_3_score: scores[2],
+        player_4_score: scores[3],
+        seed,
+        seats: (0..4)
+            .map(|player| seating.iter().position(|&entry| entry == player).unwrap())
+            .collect(),
+        stats,
+    };
+    if experiment.output.events.is_none() {
+        log.events = Vec::new();
+    }
+    (result, log)
+}
+
+/// Creates the results and event files of a run.
+fn start_run(experiment: &Experiment) -> Result<(ResultWriter, Option<BufWriter<File>>), RunError> {
+    let output = &experiment.output;
+    let results =
+        ResultWriter::create(&output.path, output.format, experiment).map_err(RunError::Results)?;
+    let events = match &output.events {
+        Some(path) => Some(BufWriter::new(
+            File::create(path).map_err(RunError::Events)?,
+        )),
+        None => None,
+    };
+    Ok((results, events))
+}
+
+/// Opens the files of an interrupted run after its last game that made it into both, and
+/// counts the games already played into `totals`.
+fn resume_run(
+    experiment: &Experiment,
+    labels: &[String],
+    totals: &mut RunTotals,
+) -> io::Result<(ResultWriter, Option<BufWriter<File>>)> {
+    let output = &experiment.output;
+    if read_experiment(&output.path)? != *experiment {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} was written by a different experiment",
+                output.path.display()
+            ),
+        ));
+    }
+    let event_ends = match &output.events {
+        Some(path) if path.exists() => Some(game_ends(path)?),
+        Some(_) => Some(Vec::new()),
+        None => None,
+    };
+    let limit = event_ends.as_ref().map_or(usize::MAX, Vec::len);
+    let games_per_wall = experiment.rotation.games_per_wall();
+    let file = read_game_results(&output.path, limit, |result| {
+        totals.add(labels, &result, games_per_wall)
+    })?;
+    let results = ResultWriter::append(
+        &output.path,
+        output.format,
+        experiment,
+        file.games,
+        file.length,
+    )?;
+    let events = match (&output.events, event_ends) {
+        (Some(path), Some(ends)) => {
+            let mut log = OpenOptions::new().create(true).append(true).open(path)?;
+            log.set_len(if file.games == 0 {
+                0
+            } else {
+                ends[file.games - 1]
+            })?;
+            log.seek(SeekFrom::End(0))?;
+            Some(BufWriter::new(log))
+        }
+        _ => None,
+    };
+    Ok((results, events))
+}
+
+/// Writes the games the workers send in order of their index, as soon as every earlier
+/// game is in, and tallies them. A sequential run looks at its strategies every so many
+/// games and sets `stopped` once they differ; the games played past that look are dropped,
+/// so the run always ends at the same game.
+fn collect_games(
+    receiver: Receiver<(usize, GameResult, EventLog)>,
+    experiment: &Experiment,
+    (labels, pairs): (&[String], &[(String, String)]),
+    (results, events): (ResultWriter, Option<BufWriter<File>>),
+    (mut totals, stopped): (RunTotals, &AtomicBool),
+    progress: &mut impl RunProgress,
+) -> Run {
+    let (mut results, mut events) = (Some(results), events);
+    let mut errors = Vec::new();
+    let range = experiment.game_range();
+    let games_per_wall = experiment.rotation.games_per_wall();
+    let look_at = |totals: &mut RunTotals| {
+        let Some(rule) = &experiment.stop else {
+            return;
+        };
+        let interval = rule.check_every * games_per_wall;
+        let games = totals.games;
+        if games == 0 || !(games.is_multiple_of(interval) || games == range.len()) {
+            return;
+        }
+        let previous = (games - 1) / interval * interval;
+        let look = Look::take(
+            rule,
+            pairs,
+            (previous, games, range.len()),
+            totals.samples(games_per_wall),
+        );
+        if look.decided() {
+            stopped.store(true, Ordering::SeqCst);
+        }
+        totals.look = Some(look);
+    };
+    // A resumed run may have stopped at its last look already
+    look_at(&mut totals);
+    progress.start(range.len(), totals.games);
+    let mut waiting: BTreeMap<usize, (GameResult, EventLog)> = BTreeMap::new();
+    let mut flushed = totals.games;
+    for (index, result, log) in receiver {
+        if stopped.load(Ordering::SeqCst) {
+            continue;
+        }
+        waiting.insert(index, (result, log));
+        while let Some((result, log)) = waiting.remove(&(range.start + totals.games)) {
+            if let Some(writer) = &mut results {
+                if let Err(error) = writer.write(&result) {
+                    errors.push(RunError::Results(error));
+                    results = None;
+                }
+            }
+            if let Some(writer) = &mut events {
+                if let Err(error) = write_game_events(writer, range.start + totals.games, &log) {
+                    errors.push(RunError::Events(error));
+                    events = None;
+                }
+            }
+            totals.add(labels, &result, games_per_wall);
+            let scores = [
+                result.player_1_score,
+                result.player_2_score,
+                result.player_3_score,
+                result.player_4_score,
+            ];
+            progress.add(labels, &scores);
+            look_at(&mut totals);
+            if stopped.load(Ordering::SeqCst) {
+                break;
+            }
+        }
+        // Keep the files current to within a batch, in case the run dies
+        if totals.games >= flushed + BATCH_GAMES {
+            flushed = totals.games;
+            if let Some(writer) = &mut results {
+                let _ = writer.flush();
+            }
+            if let Some(writer) = &mut events {
+                let _ = writer.flush();
+            }
+        }
+    }
+    progress.finish();
+    if let Some(writer) = results {
+        if let Err(error) = writer.finish() {
+            errors.push(RunError::Results(error));
+        }
+    }
+    if let Some(mut writer) = events {
+        if let Err(error) = writer.flush() {
+            errors.push(RunError::Events(error));
+        }
+    }
+    Run {
+        totals,
+        planned: range.len(),
+        errors,
+    }
+}
+
+#[derive(Debug)]
+pub enum MergeError {
+    Read(PathBuf, io::Error),
+    Write(io::Error),
+    /// A file from another run, one that isn't a shard or one that doesn't fit with the
+    /// other shards
+    Mismatch(PathBuf, &'static str),
+    /// Shards given and shards in the run
+    Missing(usize, usize),
+    /// A shard, the games it holds and the games it should
+    Incomplete(PathBuf, usize, usize),
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MergeError::Read(path, error) => write!(f, "{}: {}", path.display(), error),
+            MergeError::Write(error) => write!(f, "game results can't be written: {}", error),
+            MergeError::Mismatch(path, problem) => write!(f, "{} {}", path.display(), problem),
+            MergeError::Missing(given, count) => write!(
+                f,
+                "{} of {} shards given, shard {}/{} is missing",
+                given,
+                count,
+                given + 1,
+                count
+            ),
+            MergeError::Incomplete(path, games, expected) => write!(
+                f,
+                "{} holds {} of its {} games, resume it first",
+                path.display(),
+                games,
+                expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+/// Merges the results files of every shard of one run into the results file of the whole
+/// run, in the format of the shards, and tallies its games. `output` turns the output of
+/// the first shard into that of the whole run.
+pub fn merge_shards(
+    inputs: &[PathBuf],
+    output: impl FnOnce(&OutputConfig) -> OutputConfig,
+) -> Result<(Experiment, RunTotals), MergeError> {
+    let mut shards: Vec<(Experiment, &Path)> = Vec::new();
+    for path in inputs {
+        let experiment =
+            read_experiment(path).map_err(|error| MergeError::Read(path.clone(), error))?;
+        shards.push((experiment, path));
+    }
+    shards.sort_by_key(|(experiment, _)| experiment.shard.map(|shard| shard.index));
+    // Every shard must come from the same run, and the run must be complete
+    let whole = |experiment: &Experiment| Experiment {
+        shard: None,
+        output: shards[0].0.output.clone(),
+        ..experiment.clone()
+    };
+    let count = shards[0].0.shard.map_or(0, |shard| shard.count);
+    for (index, (experiment, path)) in shards.iter().enumerate() {
+        let problem = match experiment.shard {
+            _ if whole(experiment) != whole(&shards[0].0) => Some("comes from a different run"),
+            None => Some("isn't a shard"),
+            Some(shard) if shard.count != count || shard.index != index + 1 => {
+                Some("doesn't fit with the other shards")
+            }
+            Some(_) => None,
+        };
+        if let Some(problem) = problem {
+            return Err(MergeError::Mismatch(path.to_path_buf(), problem));
+        }
+    }
+    if shards.len() != count {
+        return Err(MergeError::Missing(shards.len(), count));
+    }
+
+    let first = &shards[0].0;
+    let experiment = Experiment {
+        shard: None,
+        output: output(&first.output),
+        ..first.clone()
+    };
+    let labels: Vec<String> = experiment.seats.iter().map(SeatConfig::label).collect();
+    let games_per_wall = experiment.rotation.games_per_wall();
+    let path = &experiment.output.path;
+    let mut writer =
+        ResultWriter::create(path, first.output.format, &experiment).map_err(MergeError::Write)?;
+    let mut totals = RunTotals::default();
+    for (shard, path) in &shards {
+        let mut written = Ok(());
+        let file = read_game_results(path, usize::MAX, |result| {
+            if written.is_ok() {
+                written = writer.write(&result);
+            }
+            totals.add(&labels, &result, games_per_wall);
+        })
+        .map_err(|error| MergeError::Read(path.to_path_buf(), error))?;
+        written.map_err(MergeError::Write)?;
+        let games = shard.game_range().len();
+        if file.games != games {
+            return Err(MergeError::Incomplete(
+                path.to_path_buf(),
+                file.games,
+                games,
+            ));
+        }
+    }
+    writer.finish().map_err(MergeError::Write)?;
+    Ok((experiment, totals))
+}
+
+#[test]
+fn test_collect_games_in_order() {
+    let mut experiment = Experiment::parse(
+        "games = 4\nseed = 7\n[[seats]]\nstrategy = \"completor\"\n[[seats]]\n\
+         strategy = \"completor\"\n[[seats]]\nstrategy = \"standard\"\n[[seats]]\n\
+         strategy = \"standard\"\n",
+    )
+    .unwrap()
+    .remove(0);
+    experiment.output.path =
+        std::env::temp_dir().join(format!("mahjong-collect-{}.csv", std::process::id()));
+    experiment.output.format = OutputFormat::Csv;
+    let labels: Vec<String> = experiment.seats.iter().map(SeatConfig::label).collect();
+    let game = |index: usize| GameResult {
+        player_1_score: 25000 + index as i32,
+        player_2_score: 25000,
+        player_3_score: 25000,
+        player_4_score: 25000 - index as i32,
+        seed: index as u64,
+        seats: vec![0, 1, 2, 3],
+        stats: Vec::new(),
+    };
+
+    // Games come in out of order, and game 2 never does, as when an interrupt stops the
+    // workers before they start it
+    let (sender, receiver) = mpsc::channel();
+    for index in [1, 3, 0] {
+        sender
+            .send((index, game(index), EventLog::default()))
+            .unwrap();
+    }
+    drop(sender);
+    let files = start_run(&experiment).unwrap();
+    let run = collect_games(
+        receiver,
+        &experiment,
+        (&labels, &[]),
+        files,
+        (RunTotals::default(), &AtomicBool::new(false)),
+        &mut (),
+    );
+    assert_eq!(run.totals.games, 2);
+    assert!(run.interrupted() && run.errors.is_empty());
+
+    // The games before the gap are written in order and the file is complete
+    let text = std::fs::read_to_string(&experiment.output.path).unwrap();
+    let rows: Vec<&str> = text.lines().filter(|line| !line.starts_with('#')).collect();
+    assert_eq!(
+        &rows[1..],
+        ["25000,25000,25000,25000,0", "25001,25000,25000,24999,1"]
+    );
+    let file = read_game_results(&experiment.output.path, usize::MAX, |_| {}).unwrap();
+    assert_eq!(file.games, 2);
+    std::fs::remove_file(&experiment.output.path).unwrap();
+}
+
+#[test]
+fn test_score_history_is_bounded() {
+    let mut history = ScoreHistory::default();
+    for game in 0..10_000 {
+        history.add([30000 + game % 7 * 100, 25000, 25000, 20000]);
+    }
+    assert!(history.samples.len() < MAX_SAMPLES);
+    assert_eq!(history.buckets.len(), 3);
+    assert_eq!(history.buckets[&30000], [10_000, 0, 0, 0]);
+    assert_eq!(history.totals[3], 200_000_000);
+    // Samples stay evenly spaced as they thin out
+    let every = history.samples[0].0;
+    assert!(history
+        .samples
+        .iter()
+        .enumerate()
+        .all(|(index, (games, _))| *games == (index + 1) * every));
+}
@@ -0,0 +1,402 @@
+use crate::types::mahjong_tile::*;
+use crate::types::tile_counts::*;
+use crate::types::wait::*;
+use crate::types::*;
+use num_traits::pow;
+
+/// Han, fu and base points of a complete hand, with the yaku and dora the han came from.
+/// `hand` holds the closed tiles, the last one won on, and `called` the melds called.
+/// Panics when the closed tiles are neither melds and a pair nor seven pairs.
+pub fn score_hand(
+    hand: &[MahjongTile],
+    called: &[Vec<MahjongTile>],
+    tsumo: bool,
+    seat_wind: &SeatWind,
+) -> HandScore {
+    score(hand, called, tsumo, seat_wind, |tile| tile.is_dora as i32)
+}
+
+/// As `score_hand`, but with `dora` holding the tile each indicator makes dora in place of
+/// the tiles' own flags, so a tile two indicators point at is worth two han.
+pub fn score_hand_with_dora(
+    hand: &[MahjongTile],
+    called: &[Vec<MahjongTile>],
+    tsumo: bool,
+    seat_wind: &SeatWind,
+    dora: &[MahjongTile],
+) -> HandScore {
+    score(hand, called, tsumo, seat_wind, |tile| {
+        dora.iter().filter(|dora| *dora == tile).count() as i32
+    })
+}
+
+fn score(
+    hand: &[MahjongTile],
+    called: &[Vec<MahjongTile>],
+    tsumo: bool,
+    seat_wind: &SeatWind,
+    dora_han: impl Fn(&MahjongTile) -> i32,
+) -> HandScore {
+    let hand_copy = hand.to_vec();
+    let counts = to_counts(&hand_copy);
+    // Seven pairs that also read as melds and a pair are scored as melds
+    let is_chiitoitsu = !is_standard_agari(&counts);
+    assert!(
+        !is_chiitoitsu || is_chiitoitsu_agari(&counts),
+        "only complete hands are scored"
+    );
+
+    let mut yaku = Vec::new();
+    let mut add_yaku = |name: &str, han: i32| {
+        if han > 0 {
+            yaku.push(Yaku {
+                name: name.to_string(),
+                han,
+            });
+        }
+    };
+    let fu_score;
+
+    if called.is_empty() && tsumo {
+        add_yaku("menzen tsumo", 1);
+        fu_score = 20;
+    } else if called.is_empty() && !tsumo {
+        fu_score = 30;
+    } else {
+        fu_score = 20;
+    }
+    let mut tanyao = true;
+    let mut number_suits = Vec::new();
+    let mut honors = false;
+
+    let mut dora = 0;
+    let mut red_fives = 0;
+    for tile in hand_copy.iter().chain(called.iter().flatten()) {
+        dora += dora_han(tile);
+        red_fives += if tile.is_red { 1 } else { 0 };
+
+        if tile.value == 1
+            || tile.value == 9
+            || tile.suit == Suit::Kaze
+            || tile.suit == Suit::Sangen
+        {
+            tanyao = false;
+        }
+        if tile.suit == Suit::Kaze || tile.suit == Suit::Sangen {
+            honors = true;
+        } else if !number_suits.contains(&tile.suit) {
+            number_suits.push(tile.suit);
+        }
+    }
+    // One suit with honors is honitsu, without them chinitsu, each a han less when open
+    let one_suit = number_suits.len() == 1;
+    let open_han = if called.is_empty() { 0 } else { 1 };
+    add_yaku("dora", dora);
+    add_yaku("aka dora", red_fives);
+    add_yaku("tanyao", if tanyao { 1 } else { 0 });
+    add_yaku("honitsu", if one_suit && honors { 3 - open_han } else { 0 });
+    add_yaku(
+        "chinitsu",
+        if one_suit && !honors { 6 - open_han } else { 0 },
+    );
+
+    if is_chiitoitsu {
+        //chiitoi temp fix
+        let han_score: i32 = yaku.iter().map(|y| y.han).sum();
+        yaku.push(Yaku {
+            name: "chiitoitsu".to_string(),
+            han: 2,
+        });
+        let mut hand_score = 25 * pow(2, 2 + 2 + han_score as usize);
+        if hand_score > 2000 {
+            hand_score = limit_hand_score(han_score);
+        }
+        return HandScore {
+            han: han_score + 2,
+            fu: 25,
+            yaku,
+            base_points: hand_score,
+        };
+    }
+
+    let winning_tile = &hand_copy[hand_copy.len() - 1];
+
+    // let round_wind_number = match strat.round_number {
+    //     1..=4 => 1,
+    //     5..=8 => 2,
+    //     9..=12 => 3,
+    //     13..=16 => 4,
+    //     _ => 0,
+    // };
+
+    let seat_wind_number = match seat_wind {
+        SeatWind::East => 1,
+        SeatWind::South => 2,
+        SeatWind::West => 3,
+        SeatWind::North => 4,
+    };
+
+    // Each reading as melds and a pair, with the wait the winning tile filled in it, keeping
+    // the best; the seven pairs reading has seven blocks
+    win_shapes(&counts, tile_index(winning_tile))
+        .iter()
+        .filter(|reading| reading.decomposition.len() < 7)
+        .map(|reading| {
+            let mut reading_yaku = yaku.clone();
+            let meld_list: Vec<Vec<MahjongTile>> = reading
+                .decomposition
+                .iter()
+                .map(|block| block.tiles())
+                .collect();
+            let fu_score = score_reading(
+                &meld_list,
+                called,
+                reading.shape,
+                tsumo,
+                seat_wind_number,
+                fu_score,
+                &mut reading_yaku,
+            );
+
+            let han_score: i32 = reading_yaku.iter().map(|y| y.han).sum();
+            let mut hand_score = fu_score * pow(2, 2 + han_score as usize);
+            if hand_score > 2000 {
+                hand_score = limit_hand_score(han_score);
+            }
+            HandScore {
+                han: han_score,
+                fu: fu_score,
+                yaku: reading_yaku,
+                base_points: hand_score,
+            }
+        })
+        .max_by_key(|score| (score.base_points, score.han, score.fu))
+        .expect("the hand is complete")
+}
+
+/// Fu of one reading of the closed tiles as melds together with the called melds, starting
+/// from `fu_score`, adding their yaku to `yaku`.
+fn score_reading(
+    meld_list: &[Vec<MahjongTile>],
+    called: &[Vec<MahjongTile>],
+    wait: WaitShape,
+    tsumo: bool,
+    seat_wind_number: u8,
+    mut fu_score: i32,
+    yaku: &mut Vec<Yaku>,
+) -> i32 {
+    let mut add_yaku = |name: &str, han: i32| {
+        yaku.push(Yaku {
+            name: name.to_string(),
+            han,
+        });
+    };
+    let closed = called.is_empty();
+    let mut triplet_count = 0;
+    let mut sequence_count = 0;
+    let mut value_pair = false;
+    let melds = meld_list.iter().map(|meld| (meld, true));
+    for (meld, concealed) in melds.chain(called.iter().map(|meld| (meld, false))) {
+        let is_triplet = meld.len() >= 3 && meld[1].value == meld[0].value;
+
+        if meld.len() == 2
+            && (meld[0].suit == Suit::Sangen
+                || (meld[0].suit == Suit::Kaze && meld[0].value == seat_wind_number))
+        {
+            fu_score += 2;
+            value_pair = true;
+            //Add round wind
+        }
+        if meld.len() == 3 && !is_triplet {
+            sequence_count += 1;
+        }
+        if is_triplet {
+            let triplet_suit = meld[0].suit;
+            let triplet_value = meld[0].value;
+
+            let mut triplet_fu = if triplet_suit == Suit::Sangen
+                || triplet_suit == Suit::Kaze
+                || triplet_value == 1
+                || triplet_value == 9
+            {
+                8
+            } else {
+                4
+            };
+            // Called triplets are worth half, kans four times as much
+            if concealed {
+                triplet_count += 1;
+            } else {
+                triplet_fu /= 2;
+            }
+            if meld.len() == 4 {
+                triplet_fu *= 4;
+            }
+            fu_score += triplet_fu;
+            if triplet_suit == Suit::Sangen
+                || (triplet_suit == Suit::Kaze && triplet_value == seat_wind_number)
+            {
+                //Add round wind
+                add_yaku("yakuhai", 1);
+            }
+        }
+    }
+    if triplet_count >= 3 {
+        add_yaku("sanankou", 2); //san ankou and temp suuankou
+    }
+    if closed {
+        // Pairs of identical sequences, two of them being ryanpeikou
+        let mut sequences: Vec<&Vec<MahjongTile>> = meld_list
+            .iter()
+            .filter(|meld| meld.len() == 3 && meld[0].value != meld[1].value)
+            .collect();
+        sequences.sort_by_key(|meld| (meld[0].suit, meld[0].value));
+        let mut peikou = 0;
+        let mut index = 0;
+        while index + 1 < sequences.len() {
+            if sequences[index] == sequences[index + 1] {
+                peikou += 1;
+                index += 2;
+            } else {
+                index += 1;
+            }
+        }
+        match peikou {
+            0 => {}
+            1 => add_yaku("iipeikou", 1),
+            _ => add_yaku("ryanpeikou", 3),
+        }
+    }
+
+    // Pinfu keeps the 30 fu of a closed ron and takes 20 on a tsumo, without the tsumo fu
+    if closed && sequence_count == 4 && !value_pair && wait == WaitShape::Ryanmen {
+        add_yaku("pinfu", 1);
+        return fu_score;
+    }
+    fu_score += wait.fu() as i32; //from wait
+    if tsumo {
+        fu_score += 2;
+    }
+
+    round_up_to_10(fu_score)
+}
+
+/// Base points of a hand at or above mangan, by its han.
+pub fn limit_hand_score(han_score: i32) -> i32 {
+    match han_score {
+        0..=5 => 2000,
+        6..=7 => 3000,
+        8..=10 => 4000,
+        11..=12 => 6000,
+        _ => 8000, // 13 or greater, not in EMA
+    }
+}
+
+/// What the winner of a hand is paid.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Payments {
+    /// Paid by the discarder on a ron
+    pub ron: i32,
+    /// Paid by the dealer on someone else's tsumo
+    pub tsumo_dealer: i32,
+    /// Paid by each other player on a tsumo
+    pub tsumo_non_dealer: i32,
+    /// Everything the winner collects, riichi sticks included
+    pub total: i32,
+}
+
+/// Payments for a win of `base_points`, with 300 points a honba and 1000 a riichi stick.
+pub fn payments(
+    base_points: i32,
+    dealer: bool,
+    tsumo: bool,
+    honba: i32,
+    riichi_sticks: i32,
+) -> Payments {
+    let mut payments = Payments::default();
+    match (tsumo, dealer) {
+        (false, true) => payments.ron = round_up_to_100(6 * base_points) + 300 * honba,
+        (false, false) => payments.ron = round_up_to_100(4 * base_points) + 300 * honba,
+        (true, true) => payments.tsumo_non_dealer = round_up_to_100(2 * base_points) + 100 * honba,
+        (true, false) => {
+            payments.tsumo_dealer = round_up_to_100(2 * base_points) + 100 * honba;
+            payments.tsumo_non_dealer = round_up_to_100(base_points) + 100 * honba;
+        }
+    }
+    payments.total = match (tsumo, dealer) {
+        (false, _) => payments.ron,
+        (true, true) => 3 * payments.tsumo_non_dealer,
+        (true, false) => payments.tsumo_dealer + 2 * payments.tsumo_non_dealer,
+    } + 1000 * riichi_sticks;
+    payments
+}
+
+/// Payments are rounded up to whole hundreds.
+pub fn round_up_to_100(number: i32) -> i32 {
+    (number + 99) / 100 * 100
+}
+fn round_up_to_10(number: i32) -> i32 {
+    (number + 9) / 10 * 10
+}
+
+#[test]
+fn test_payments() {
+    // 30 fu 3 han: 3900 from the discarder, or 1000 and 2000 on a tsumo, plus a honba
+    assert_eq!(payments(960, false, false, 0, 0).ron, 3900);
+    let tsumo = payments(960, false, true, 1, 2);
+    assert_eq!((tsumo.tsumo_dealer, tsumo.tsumo_non_dealer), (2100, 1100));
+    assert_eq!(tsumo.total, 2100 + 2 * 1100 + 2000);
+    // Dealer mangan
+    assert_eq!(payments(2000, true, false, 0, 0).total, 12000);
+    assert_eq!(payments(2000, true, true, 0, 0).tsumo_non_dealer, 4000);
+}
+
+#[test]
+fn test_chiitoitsu() {
+    use crate::types::notation::parse_tiles;
+    let pairs = parse_tiles("1133m2255p4466s77z").unwrap();
+    let score = score_hand(&pairs, &[], false, &SeatWind::South);
+    assert_eq!((score.han, score.fu), (2, 25));
+    assert_eq!(score.yaku[0].name, "chiitoitsu");
+
+    let melds = parse_tiles("123m456p789s11222z").unwrap();
+    let score = score_hand(&melds, &[], true, &SeatWind::East);
+    assert!(score.yaku.iter().all(|yaku| yaku.name != "chiitoitsu"));
+}
+
+#[test]
+#[should_panic(expected = "only complete hands are scored")]
+fn test_score_four_of_a_kind_as_pairs() {
+    use crate::types::notation::parse_tiles;
+    let tiles = parse_tiles("1111m2233p4455s66z").unwrap();
+    score_hand(&tiles, &[], true, &SeatWind::East);
+}
+
+#[test]
+fn test_wait_from_scored_reading() {
+    use crate::types::notation::parse_tiles;
+    // 8m is a shanpon when 777888999m is read as triplets, only a kanchan as sequences
+    let mut hand = parse_tiles("77788999m456p22z8m").unwrap();
+    let score = score_hand(&hand, &[], true, &SeatWind::South);
+    assert_eq!((score.han, score.fu), (3, 40));
+
+    // Three concealed triplets pay more than pinfu and a single iipeikou of 678m
+    hand = parse_tiles("66677788m456p99s8m").unwrap();
+    let score = score_hand(&hand, &[], true, &SeatWind::South);
+    let names: Vec<&str> = score.yaku.iter().map(|yaku| yaku.name.as_str()).collect();
+    assert_eq!(names, ["menzen tsumo", "sanankou"]);
+    assert_eq!((score.han, score.fu), (3, 40));
+
+    // A closed ron on a two-sided wait is pinfu at 30 fu
+    hand = parse_tiles("234m567p23478s55p6s").unwrap();
+    let score = score_hand(&hand, &[], false, &SeatWind::South);
+    let names: Vec<&str> = score.yaku.iter().map(|yaku| yaku.name.as_str()).collect();
+    assert_eq!(names, ["tanyao", "pinfu"]);
+    assert_eq!((score.han, score.fu), (2, 30));
+
+    // A value pair rules pinfu out, and pays its 2 fu
+    hand = parse_tiles("234m567p23478s77z6s").unwrap();
+    let score = score_hand(&hand, &[], false, &SeatWind::South);
+    assert!(score.yaku.iter().all(|yaku| yaku.name != "pinfu"));
+    assert_eq!((score.han, score.fu), (0, 40));
+}
@@ -0,0 +1,630 @@
+use crate::experiment::SeatConfig;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Whether one strategy really beats another after N games. The seats at one table aren't
+// independent, they share the deal and their scores add up to the same total, so each game
+// is one sample: a strategy's seats in it are averaged into its score and placement for
+// the game. Each strategy's mean gets a standard error and a 95% confidence interval from
+// Student's t, and every pair of strategies a paired t-test on the differences between
+// them game by game. Duplicate games pair up whole sets instead: both strategies played
+// the same walls from every seat, so the luck of the deal cancels out of each set's
+// difference.
+//
+// A sequential run looks at its strategies every so many games and stops once they differ.
+// Testing at 5% on every look would find a difference that isn't there far more often than
+// 5% of the time, so the looks share the error out: the Lan-DeMets O'Brien-Fleming spending
+// function says how much of the 5% is spent by each point of the budget, almost nothing
+// early on, and a look needs p below what is spent since the look before it. The shares add
+// up to 5%, so by Bonferroni a run stops on a difference that isn't there less than 5% of
+// the time. That is conservative: the looks see mostly the same games, and the exact
+// O'Brien-Fleming boundaries, which account for that, would stop a little sooner.
+
+/// Mean and spread of one sample.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub mean: f64,
+    /// Sample variance, with `count - 1` in the denominator
+    pub variance: f64,
+}
+
+impl Summary {
+    pub fn of(values: &[f64]) -> Summary {
+        let count = values.len();
+        let mean = values.iter().sum::<f64>() / count.max(1) as f64;
+        let variance = if count > 1 {
+            values
+                .iter()
+                .map(|value| (value - mean).powi(2))
+                .sum::<f64>()
+                / (count - 1) as f64
+        } else {
+            0.0
+        };
+        Summary {
+            count,
+            mean,
+            variance,
+        }
+    }
+
+    /// The summary of the values added to `sums`.
+    pub fn of_sums(sums: &Sums) -> Summary {
+        let count = sums.count;
+        let mean = sums.sum / count.max(1) as f64;
+        let variance = if count > 1 {
+            ((sums.sum_of_squares - sums.sum * mean) / (count - 1) as f64).max(0.0)
+        } else {
+            0.0
+        };
+        Summary {
+            count,
+            mean,
+            variance,
+        }
+    }
+
+    pub fn standard_error(&self) -> f64 {
+        (self.variance / self.count.max(1) as f64).sqrt()
+    }
+
+    /// Half the width of the 95% confidence interval around the mean.
+    pub fn margin(&self) -> f64 {
+        if self.count < 2 {
+            return f64::NAN;
+        }
+        t_quantile_95((self.count - 1) as f64) * self.standard_error()
+    }
+}
+
+/// Running count, sum and sum of squares of a sample, all a summary of it needs.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Sums {
+    pub count: usize,
+    pub sum: f64,
+    pub sum_of_squares: f64,
+}
+
+impl Sums {
+    pub fn add(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.sum_of_squares += value * value;
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TTest {
+    /// Mean of the first sample minus mean of the second
+    pub difference: f64,
+    /// Half the width of the 95% confidence interval around the difference
+    pub margin: f64,
+    pub t: f64,
+    pub degrees_of_freedom: f64,
+    /// Two-sided
+    pub p: f64,
+}
+
+/// One-sample t-test of whether paired differences average zero.
+pub fn paired_test(differences: &[f64]) -> Option<TTest> {
+    summary_test(Summary::of(differences))
+}
+
+/// The same test, given the summary of the differences.
+pub fn summary_test(summary: Summary) -> Option<TTest> {
+    let standard_error = summary.standard_error();
+    if summary.count < 2 || standard_error == 0.0 {
+        return None;
+    }
+    let degrees_of_freedom = (summary.count - 1) as f64;
+    let t = summary.mean / standard_error;
+    Some(TTest {
+        difference: summary.mean,
+        margin: summary.margin(),
+        t,
+        degrees_of_freedom,
+        p: t_two_sided_p(t, degrees_of_freedom),
+    })
+}
+
+/// Lanczos approximation of ln Γ(x) for x > 0.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let series = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (i, c)| {
+            sum + c / (x + i as f64 + 1.0)
+        });
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+/// Continued fraction for the incomplete beta function, evaluated the modified Lentz way.
+fn beta_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1.0 / d;
+    let mut fraction = d;
+    for m in 1..300 {
+        let m = m as f64;
+        for numerator in [
+            m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m)),
+            -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0)),
+        ] {
+            d = 1.0 + numerator * d;
+            if d.abs() < TINY {
+                d = TINY;
+            }
+            c = 1.0 + numerator / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            d = 1.0 / d;
+            fraction *= d * c;
+        }
+        if (d * c - 1.0).abs() < 1e-15 {
+            break;
+        }
+    }
+    fraction
+}
+
+/// The regularized incomplete beta function I_x(a, b).
+fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    // The fraction converges quickly on this side of the mean, use the symmetry otherwise
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+/// Chance of a Student's t at least as far from zero as `t`.
+pub fn t_two_sided_p(t: f64, degrees_of_freedom: f64) -> f64 {
+    incomplete_beta(
+        degrees_of_freedom / 2.0,
+        0.5,
+        degrees_of_freedom / (degrees_of_freedom + t * t),
+    )
+}
+
+/// The t that leaves 2.5% in each tail, found by bisection.
+pub fn t_quantile_95(degrees_of_freedom: f64) -> f64 {
+    let (mut low, mut high) = (0.0, 1000.0);
+    for _ in 0..100 {
+        let middle = (low + high) / 2.0;
+        if t_two_sided_p(middle, degrees_of_freedom) > 0.05 {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    (low + high) / 2.0
+}
+
+/// Chance of a standard normal at least as far from zero as `z`, from the Chebyshev fit to
+/// erfc with a relative error below 1.2e-7.
+pub fn normal_two_sided_p(z: f64) -> f64 {
+    let x = z.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.5 * x);
+    let polynomial = [
+        -1.265_512_23,
+        1.000_023_68,
+        0.374_091_96,
+        0.096_784_18,
+        -0.186_288_06,
+        0.278_868_07,
+        -1.135_203_98,
+        1.488_515_87,
+        -0.822_152_23,
+        0.170_872_77,
+    ]
+    .iter()
+    .rev()
+    .fold(0.0, |sum, coefficient| coefficient + t * sum);
+    t * (-x * x + polynomial).exp()
+}
+
+/// The z that leaves `alpha` in the two tails together, found by bisection.
+pub fn normal_quantile(alpha: f64) -> f64 {
+    let (mut low, mut high) = (0.0, 40.0);
+    for _ in 0..100 {
+        let middle = (low + high) / 2.0;
+        if normal_two_sided_p(middle) > alpha {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    (low + high) / 2.0
+}
+
+/// Error spent by the time `fraction` of the budget is played, Lan and DeMets' version of
+/// the O'Brien-Fleming bounds.
+pub fn error_spent(alpha: f64, fraction: f64) -> f64 {
+    if fraction <= 0.0 {
+        return 0.0;
+    }
+    normal_two_sided_p(normal_quantile(alpha) / fraction.min(1.0).sqrt())
+}
+
+/// When a run may stop before its budget: once every compared pair of strategies differs
+/// in mean score.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct StoppingRule {
+    /// Chance of not calling a difference that isn't there, e.g. 0.95
+    pub confidence: f64,
+    /// Strategies to compare: two for one pair, one to compare every other strategy
+    /// against, none for every pair. A bare strategy name stands for its only variant.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub compare: Vec<String>,
+    /// Wall sets played between looks
+    #[serde(default = "default_check_every")]
+    pub check_every: usize,
+}
+
+fn default_check_every() -> usize {
+    1000
+}
+
+impl StoppingRule {
+    /// The pairs of strategy labels to compare among the seats.
+    pub fn pairs(&self, seats: &[SeatConfig]) -> Result<Vec<(String, String)>, String> {
+        let labels: Vec<String> = seats.iter().map(SeatConfig::label).unique().collect();
+        let mut named = Vec::new();
+        for name in &self.compare {
+            let matches: Vec<String> = if labels.contains(name) {
+                vec![name.clone()]
+            } else {
+                seats
+                    .iter()
+                    .filter(|seat| seat.strategy == *name)
+                    .map(SeatConfig::label)
+                    .unique()
+                    .collect()
+            };
+            match &matches[..] {
+                [label] => named.push(label.clone()),
+                [] => return Err(format!("no seat plays '{}'", name)),
+                _ => {
+                    return Err(format!(
+                        "'{}' could mean {}",
+                        name,
+                        matches.iter().join(" or ")
+                    ))
+                }
+            }
+        }
+        let pairs: Vec<(String, String)> = match &named[..] {
+            [] => labels.iter().cloned().tuple_combinations().collect(),
+            [baseline] => labels
+                .iter()
+                .filter(|label| *label != baseline)
+                .map(|label| (label.clone(), baseline.clone()))
+                .collect(),
+            [first, second] if first != second => vec![(first.clone(), second.clone())],
+            _ => return Err("compare takes one or two different strategies".to_string()),
+        };
+        if pairs.is_empty() {
+            return Err(
+                "every seat plays the same strategy, there is nothing to compare".to_string(),
+            );
+        }
+        Ok(pairs)
+    }
+
+    /// The p-value a difference needs at the look after `games` of `budget` games, when the
+    /// last look came after `previous`: the error spent between the two looks, not the
+    /// exact O'Brien-Fleming boundary of the look.
+    pub fn threshold(&self, previous: usize, games: usize, budget: usize) -> f64 {
+        let alpha = 1.0 - self.confidence;
+        let fraction = |games: usize| games as f64 / budget as f64;
+        error_spent(alpha, fraction(games)) - error_spent(alpha, fraction(previous))
+    }
+}
+
+/// The tests of one look at a sequential run.
+#[derive(Debug, Clone)]
+pub struct Look {
+    pub games: usize,
+    /// p-value each difference needed
+    pub threshold: f64,
+    pub tests: Vec<(String, String, Option<TTest>)>,
+}
+
+impl Look {
+    /// Compares each pair of strategies on score, from the running sums of the games or
+    /// duplicate sets in `tally`.
+    pub fn take(
+        rule: &StoppingRule,
+        pairs: &[(String, String)],
+        (previous, games, budget): (usize, usize, usize),
+        tally: &Tally,
+    ) -> Look {
+        let tests = pairs
+            .iter()
+            .map(|(first, second)| {
+                let test = tally.score_test(first, second);
+                (first.clone(), second.clone(), test)
+            })
+            .collect();
+        Look {
+            games,
+            threshold: rule.threshold(previous, games, budget),
+            tests,
+        }
+    }
+
+    /// Whether every difference is clear.
+    pub fn decided(&self) -> bool {
+        self.tests
+            .iter()
+            .all(|(_, _, test)| test.is_some_and(|test| test.p < self.threshold))
+    }
+}
+
+/// Each strategy's final score and placement in one game, averaged over its seats.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GameMeans {
+    pub scores: BTreeMap<String, f64>,
+    pub placements: BTreeMap<String, f64>,
+}
+
+impl GameMeans {
+    /// Averages one game's seats by the strategy label each played.
+    pub fn of(labels: &[String], scores: &[f64], placements: &[f64]) -> GameMeans {
+        let mut totals: BTreeMap<&String, (f64, f64, usize)> = BTreeMap::new();
+        for ((label, score), placement) in labels.iter().zip(scores).zip(placements) {
+            let total = totals.entry(label).or_default();
+            total.0 += score;
+            total.1 += placement;
+            total.2 += 1;
+        }
+        let mut means = GameMeans::default();
+        for (label, (score, placement, seats)) in totals {
+            means.scores.insert(label.clone(), score / seats as f64);
+            means
+                .placements
+                .insert(label.clone(), placement / seats as f64);
+        }
+        means
+    }
+}
+
+/// Running sums of each strategy's score and placement, and of the differences between
+/// every pair of strategies, one sample a game or duplicate set.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tally {
+    pub scores: BTreeMap<String, Sums>,
+    pub placements: BTreeMap<String, Sums>,
+    /// Keyed by the pair in label order, the first's value minus the second's
+    pub score_differences: BTreeMap<(String, String), Sums>,
+    pub placement_differences: BTreeMap<(String, String), Sums>,
+}
+
+impl Tally {
+    pub fn add(&mut self, means: &GameMeans) {
+        add_sample(&mut self.scores, &mut self.score_differences, &means.scores);
+        add_sample(
+            &mut self.placements,
+            &mut self.placement_differences,
+            &means.placements,
+        );
+    }
+
+    /// Paired t-test of the first strategy's score minus the second's.
+    pub fn score_test(&self, first: &str, second: &str) -> Option<TTest> {
+        pair_test(&self.score_differences, first, second)
+    }
+
+    /// Paired t-test of the first strategy's placement minus the second's.
+    pub fn placement_test(&self, first: &str, second: &str) -> Option<TTest> {
+        pair_test(&self.placement_differences, first, second)
+    }
+}
+
+fn add_sample(
+    values: &mut BTreeMap<String, Sums>,
+    differences: &mut BTreeMap<(String, String), Sums>,
+    sample: &BTreeMap<String, f64>,
+) {
+    for (label, value) in sample {
+        values.entry(label.clone()).or_default().add(*value);
+    }
+    for ((first, a), (second, b)) in sample.iter().tuple_combinations() {
+        differences
+            .entry((first.clone(), second.clone()))
+            .or_default()
+            .add(a - b);
+    }
+}
+
+fn pair_test(
+    differences: &BTreeMap<(String, String), Sums>,
+    first: &str,
+    second: &str,
+) -> Option<TTest> {
+    let key = |a: &str, b: &str| (a.to_string(), b.to_string());
+    if let Some(sums) = differences.get(&key(first, second)) {
+        return summary_test(Summary::of_sums(sums));
+    }
+    let test = summary_test(Summary::of_sums(differences.get(&key(second, first))?))?;
+    Some(TTest {
+        difference: -test.difference,
+        t: -test.t,
+        ..test
+    })
+}
+
+#[test]
+fn test_t_distribution() {
+    assert!((t_two_sided_p(2.0, 10.0) - 0.073388).abs() < 1e-5);
+    assert!((t_two_sided_p(0.0, 5.0) - 1.0).abs() < 1e-12);
+    assert!((t_quantile_95(10.0) - 2.228139).abs() < 1e-5);
+    assert!((t_quantile_95(1e6) - 1.959966).abs() < 1e-4);
+}
+
+#[test]
+fn test_paired_test() {
+    let first = [27.5, 21.0, 19.0, 23.6, 17.0, 17.9, 16.9, 20.1, 21.9, 22.6];
+    let second = [27.1, 22.0, 20.8, 23.4, 23.4, 23.5, 25.8, 22.0, 24.8, 20.2];
+    assert!((Summary::of(&first).mean - 20.75).abs() < 1e-9);
+
+    let differences: Vec<f64> = second.iter().zip(first).map(|(b, a)| b - a).collect();
+    let test = paired_test(&differences).unwrap();
+    assert!((test.difference - 2.55).abs() < 1e-9);
+    assert!((test.t - 2.3200).abs() < 1e-4);
+    assert_eq!(test.degrees_of_freedom, 9.0);
+    assert!((test.p - 0.04543).abs() < 1e-4, "{}", test.p);
+    assert!(paired_test(&[1.0]).is_none());
+
+    let mut sums = Sums::default();
+    differences
+        .iter()
+        .for_each(|&difference| sums.add(difference));
+    let summary = Summary::of_sums(&sums);
+    assert_eq!(summary.count, 10);
+    assert!((summary.mean - 2.55).abs() < 1e-9);
+    assert!((summary.variance - Summary::of(&differences).variance).abs() < 1e-9);
+}
+
+#[test]
+fn test_game_means() {
+    let labels = ["completor", "standard", "completor", "standard"].map(String::from);
+    let first = GameMeans::of(
+        &labels,
+        &[40000.0, 10000.0, 30000.0, 20000.0],
+        &[1.0, 4.0, 2.0, 3.0],
+    );
+    assert_eq!(first.scores["completor"], 35000.0);
+    assert_eq!(first.placements["standard"], 3.5);
+    let second = GameMeans::of(
+        &labels,
+        &[20000.0, 30000.0, 25000.0, 25000.0],
+        &[4.0, 1.0, 2.0, 3.0],
+    );
+    // One sample a game, however many seats the strategies had in it
+    let mut tally = Tally::default();
+    tally.add(&first);
+    tally.add(&second);
+    let pair = ("completor".to_string(), "standard".to_string());
+    assert_eq!(
+        tally.score_differences[&pair],
+        Sums {
+            count: 2,
+            sum: 15000.0,
+            sum_of_squares: 425_000_000.0
+        }
+    );
+    assert_eq!(tally.placements["standard"].sum, 5.5);
+    let forward = tally.score_test("completor", "standard").unwrap();
+    let backward = tally.score_test("standard", "completor").unwrap();
+    assert_eq!(backward.difference, -forward.difference);
+    assert_eq!(backward.p, forward.p);
+}
+
+#[test]
+fn test_look_pairs_games() {
+    let labels = ["completor", "standard", "completor", "standard"].map(String::from);
+    // Completor's seats win every game by a margin that varies, standard's lose it
+    let mut tally = Tally::default();
+    for game in 0..20 {
+        let margin = 1000.0 * (game % 5) as f64;
+        tally.add(&GameMeans::of(
+            &labels,
+            &[31000.0 + margin, 20000.0, 29000.0, 20000.0 - margin],
+            &[1.0, 3.0, 2.0, 4.0],
+        ));
+    }
+    let rule = StoppingRule {
+        confidence: 0.95,
+        compare: Vec::new(),
+        check_every: 20,
+    };
+    let pairs = [("completor".to_string(), "standard".to_string())];
+    let look = Look::take(&rule, &pairs, (0, 20, 20), &tally);
+    let test = look.tests[0].2.unwrap();
+    assert_eq!(test.degrees_of_freedom, 19.0);
+    assert!((test.difference - 12000.0).abs() < 1e-9);
+    assert!(look.decided());
+}
+
+#[test]
+fn test_error_spending() {
+    assert!((normal_two_sided_p(1.959964) - 0.05).abs() < 1e-7);
+    assert!((normal_quantile(0.01) - 2.575829).abs() < 1e-5);
+    assert!((error_spent(0.05, 1.0) - 0.05).abs() < 1e-7);
+    assert!((error_spent(0.05, 0.5) - 0.005575).abs() < 1e-6);
+    assert_eq!(error_spent(0.05, 0.0), 0.0);
+
+    let rule = StoppingRule {
+        confidence: 0.95,
+        compare: Vec::new(),
+        check_every: 100,
+    };
+    // The looks' thresholds add up to the whole error
+    let looks = [0, 250, 500, 750, 1000];
+    let total: f64 = looks
+        .windows(2)
+        .map(|pair| rule.threshold(pair[0], pair[1], 1000))
+        .sum();
+    assert!((total - 0.05).abs() < 1e-9);
+
+    let seat = |strategy: &str, params: &[(&str, f64)]| SeatConfig {
+        strategy: strategy.to_string(),
+        params: params
+            .iter()
+            .map(|&(name, value)| (name.to_string(), value))
+            .collect(),
+    };
+    let seats = [
+        seat("completor", &[]),
+        seat("completor", &[]),
+        seat("standard", &[]),
+        seat("kanchan-completor", &[]),
+    ];
+    assert_eq!(rule.pairs(&seats).unwrap().len(), 3);
+    let baseline = StoppingRule {
+        compare: vec!["standard".to_string()],
+        ..rule.clone()
+    };
+    assert_eq!(
+        baseline.pairs(&seats).unwrap(),
+        vec![
+            ("completor".to_string(), "standard".to_string()),
+            ("kanchan-completor".to_string(), "standard".to_string())
+        ]
+    );
+    let unknown = StoppingRule {
+        compare: vec!["efficiency".to_string()],
+        ..rule
+    };
+    assert!(unknown.pairs(&seats).is_err());
+}
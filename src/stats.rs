@@ -0,0 +1,384 @@
+use crate::event_log::*;
+use crate::types::mahjong_tile::*;
+use crate::types::notation::parse_tiles;
+use crate::types::suit_table::Evaluator;
+use crate::types::tile_counts::*;
+#[cfg(test)]
+use crate::types::HandScore;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Per-player statistics are read off a game's events, so the simulator and imported logs
+// are measured the same way. Hands are followed from the deal to find each player's
+// shanten after every discard; nothing else needs the tiles.
+
+/// What one player did over one game.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlayerStats {
+    pub rounds: u32,
+    pub tsumo_wins: u32,
+    pub ron_wins: u32,
+    pub deal_ins: u32,
+    /// Points gained from wins, and lost to deal-ins as a positive number
+    pub win_points: i64,
+    pub deal_in_points: i64,
+    pub riichi: u32,
+    /// Rounds in which the player called a chi, pon or open kan
+    pub calls: u32,
+    pub exhaustive_draws: u32,
+    pub tenpai_at_draw: u32,
+    /// Summed shanten after the player's nth discard of a round, and how many rounds got
+    /// that far. Left out of per-game results, the `--stats` file has the curves
+    #[serde(skip)]
+    pub shanten_by_turn: Vec<(u32, u32)>,
+    /// 1 to 4, ties share the better place
+    pub placement: usize,
+    pub final_points: i32,
+    /// Whether the player was below zero points at the end of any round
+    pub bust: bool,
+}
+
+impl PlayerStats {
+    pub fn wins(&self) -> u32 {
+        self.tsumo_wins + self.ron_wins
+    }
+}
+
+fn remove_tile(hand: &mut Vec<MahjongTile>, tile: &MahjongTile) {
+    if let Some(position) = hand.iter().position(|other| other == tile) {
+        hand.remove(position);
+    }
+}
+
+/// Statistics of every seat over one game.
+pub fn game_stats(events: &[GameEvent]) -> Vec<PlayerStats> {
+    let mut stats = vec![PlayerStats::default(); 4];
+    let mut hands: Vec<Vec<MahjongTile>> = vec![Vec::new(); 4];
+    let mut turns = [0; 4];
+    let mut called = [false; 4];
+    let mut last_discard: Option<MahjongTile> = None;
+    // Winner and dealer-in of the win the next payment settles
+    let mut pending_win: Option<(usize, Option<usize>)> = None;
+    for event in events {
+        match event {
+            GameEvent::Deal {
+                points,
+                hands: dealt,
+                ..
+            } => {
+                for seat in 0..4 {
+                    stats[seat].rounds += 1;
+                    stats[seat].bust |= points.get(seat).is_some_and(|points| *points < 0);
+                    hands[seat] = dealt
+                        .get(seat)
+                        .and_then(|hand| parse_tiles(hand).ok())
+                        .unwrap_or_default();
+                }
+                turns = [0; 4];
+                called = [false; 4];
+                last_discard = None;
+            }
+            GameEvent::Draw { seat, tile } => {
+                if let Ok(tile) = tile.parse() {
+                    hands[*seat].push(tile);
+                }
+            }
+            GameEvent::Discard { seat, tile } => {
+                let Ok(tile) = tile.parse::<MahjongTile>() else {
+                    continue;
+                };
+                remove_tile(&mut hands[*seat], &tile);
+                last_discard = Some(tile);
+                let turn = turns[*seat];
+                turns[*seat] += 1;
+                let by_turn = &mut stats[*seat].shanten_by_turn;
+                if by_turn.len() <= turn {
+                    by_turn.resize(turn + 1, (0, 0));
+                }
+                by_turn[turn].0 +=
+                    Evaluator::Table.shanten(&to_counts(&hands[*seat])).max(0) as u32;
+                by_turn[turn].1 += 1;
+            }
+            GameEvent::Call {
+                seat, kind, tiles, ..
+            } => {
+                let Ok(tiles) = parse_tiles(tiles) else {
+                    continue;
+                };
+                let mut taken = tiles.clone();
+                match kind {
+                    CallKind::Chi | CallKind::Pon | CallKind::OpenKan => {
+                        if let Some(discard) = &last_discard {
+                            remove_tile(&mut taken, discard);
+                        }
+                        if !called[*seat] {
+                            called[*seat] = true;
+                            stats[*seat].calls += 1;
+                        }
+                    }
+                    CallKind::AddedKan => taken.truncate(1),
+                    CallKind::ClosedKan => {}
+                }
+                for tile in &taken {
+                    remove_tile(&mut hands[*seat], tile);
+                }
+            }
+            GameEvent::Riichi { seat } => stats[*seat].riichi += 1,
+            GameEvent::Win { seat, from, .. } => {
+                match from {
+                    None => stats[*seat].tsumo_wins += 1,
+                    Some(from) => {
+                        stats[*seat].ron_wins += 1;
+                        stats[*from].deal_ins += 1;
+                    }
+                }
+                pending_win = Some((*seat, *from));
+            }
+            GameEvent::ExhaustiveDraw { tenpai } => {
+                for (seat, player) in stats.iter_mut().enumerate() {
+                    player.exhaustive_draws += 1;
+                    player.tenpai_at_draw += tenpai.contains(&seat) as u32;
+                }
+            }
+            GameEvent::Payment { deltas } => {
+                if let Some((winner, from)) = pending_win.take() {
+                    let delta = |seat: usize| deltas.get(seat).copied().unwrap_or(0) as i64;
+                    stats[winner].win_points += delta(winner);
+                    if let Some(from) = from {
+                        stats[from].deal_in_points -= delta(from);
+                    }
+                }
+            }
+            GameEvent::GameEnd { points, .. } => {
+                for (seat, player) in stats.iter_mut().enumerate() {
+                    let own = points.get(seat).copied().unwrap_or(0);
+                    player.final_points = own;
+                    player.placement = placement(points, own);
+                    player.bust |= own < 0;
+                }
+            }
+            _ => {}
+        }
+    }
+    stats
+}
+
+/// 1 to 4 for a player ending on `own` among everyone's `points`, ties sharing the better
+/// place.
+pub fn placement(points: &[i32], own: i32) -> usize {
+    1 + points.iter().filter(|&&other| other > own).count()
+}
+
+/// Statistics of every game one strategy played, summed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StrategyStats {
+    pub games: u32,
+    pub totals: PlayerStats,
+    /// How often the strategy finished in each place
+    pub placements: [u32; 4],
+    pub busts: u32,
+}
+
+fn ratio(part: impl Into<f64>, whole: impl Into<f64>) -> f64 {
+    let whole = whole.into();
+    if whole == 0.0 {
+        0.0
+    } else {
+        part.into() / whole
+    }
+}
+
+impl StrategyStats {
+    pub fn add(&mut self, game: &PlayerStats) {
+        let totals = &mut self.totals;
+        self.games += 1;
+        totals.rounds += game.rounds;
+        totals.tsumo_wins += game.tsumo_wins;
+        totals.ron_wins += game.ron_wins;
+        totals.deal_ins += game.deal_ins;
+        totals.win_points += game.win_points;
+        totals.deal_in_points += game.deal_in_points;
+        totals.riichi += game.riichi;
+        totals.calls += game.calls;
+        totals.exhaustive_draws += game.exhaustive_draws;
+        totals.tenpai_at_draw += game.tenpai_at_draw;
+        if totals.shanten_by_turn.len() < game.shanten_by_turn.len() {
+            totals
+                .shanten_by_turn
+                .resize(game.shanten_by_turn.len(), (0, 0));
+        }
+        for (total, (sum, count)) in totals.shanten_by_turn.iter_mut().zip(&game.shanten_by_turn) {
+            total.0 += sum;
+            total.1 += count;
+        }
+        totals.placement += game.placement;
+        if (1..=4).contains(&game.placement) {
+            self.placements[game.placement - 1] += 1;
+        }
+        self.busts += game.bust as u32;
+    }
+
+    /// Wins per round
+    pub fn win_rate(&self) -> f64 {
+        ratio(self.totals.wins(), self.totals.rounds)
+    }
+
+    pub fn deal_in_rate(&self) -> f64 {
+        ratio(self.totals.deal_ins, self.totals.rounds)
+    }
+
+    pub fn average_win(&self) -> f64 {
+        ratio(self.totals.win_points as f64, self.totals.wins())
+    }
+
+    pub fn average_deal_in(&self) -> f64 {
+        ratio(self.totals.deal_in_points as f64, self.totals.deal_ins)
+    }
+
+    pub fn riichi_rate(&self) -> f64 {
+        ratio(self.totals.riichi, self.totals.rounds)
+    }
+
+    pub fn call_rate(&self) -> f64 {
+        ratio(self.totals.calls, self.totals.rounds)
+    }
+
+    /// Share of exhaustive draws the strategy was tenpai at
+    pub fn tenpai_rate(&self) -> f64 {
+        ratio(self.totals.tenpai_at_draw, self.totals.exhaustive_draws)
+    }
+
+    pub fn average_placement(&self) -> f64 {
+        ratio(self.totals.placement as f64, self.games)
+    }
+
+    pub fn average_shanten(&self) -> Vec<f64> {
+        self.totals
+            .shanten_by_turn
+            .iter()
+            .map(|&(sum, count)| ratio(sum, count))
+            .collect()
+    }
+}
+
+/// Adds each seat's statistics of one game to the totals under its strategy's label.
+pub fn add_game(
+    totals: &mut BTreeMap<String, StrategyStats>,
+    labels: &[String],
+    stats: &[PlayerStats],
+) {
+    for (label, player) in labels.iter().zip(stats) {
+        totals.entry(label.clone()).or_default().add(player);
+    }
+}
+
+#[test]
+fn test_game_stats() {
+    let tile = |notation: &str| notation.to_string();
+    let events = vec![
+        GameEvent::Deal {
+            round: 0,
+            dealer: 0,
+            points: vec![25000; 4],
+            hands: vec![
+                tile("123m456p789s1122z"),
+                tile("1112345678999m"),
+                tile("19p19s123455667z"),
+                tile("3344556677889p"),
+            ],
+        },
+        GameEvent::Draw {
+            seat: 0,
+            tile: tile("7z"),
+        },
+        GameEvent::Discard {
+            seat: 0,
+            tile: tile("7z"),
+        },
+        GameEvent::Draw {
+            seat: 1,
+            tile: tile("2z"),
+        },
+        GameEvent::Discard {
+            seat: 1,
+            tile: tile("2z"),
+        },
+        GameEvent::Call {
+            seat: 0,
+            from: 1,
+            kind: CallKind::Pon,
+            tiles: tile("222z"),
+        },
+        GameEvent::Discard {
+            seat: 0,
+            tile: tile("1m"),
+        },
+        GameEvent::Draw {
+            seat: 1,
+            tile: tile("1z"),
+        },
+        GameEvent::Discard {
+            seat: 1,
+            tile: tile("1z"),
+        },
+        GameEvent::Win {
+            seat: 0,
+            from: Some(1),
+            hand: tile("23m456p789s11z[222z]1z"),
+            winning_tile: tile("1z"),
+            score: HandScore {
+                han: 1,
+                fu: 40,
+                yaku: Vec::new(),
+                base_points: 320,
+            },
+        },
+        GameEvent::Payment {
+            deltas: vec![1000, -1000, 0, 0],
+        },
+        GameEvent::Deal {
+            round: 1,
+            dealer: 1,
+            points: vec![26000, 24000, 25000, 25000],
+            hands: vec![String::new(); 4],
+        },
+        GameEvent::ExhaustiveDraw { tenpai: vec![1, 3] },
+        GameEvent::Payment {
+            deltas: vec![-1500, 1500, -1500, 1500],
+        },
+        GameEvent::GameEnd {
+            points: vec![24500, 25500, 23500, 26500],
+            uma: vec![0; 4],
+        },
+    ];
+    let stats = game_stats(&events);
+    assert_eq!(stats[0].rounds, 2);
+    assert_eq!((stats[0].ron_wins, stats[0].win_points), (1, 1000));
+    assert_eq!((stats[1].deal_ins, stats[1].deal_in_points), (1, 1000));
+    assert_eq!(stats[0].calls, 1);
+    // Tenpai after both discards: 123m456p789s1122z, then 23m456p789s11z with 222z called
+    assert_eq!(stats[0].shanten_by_turn, vec![(0, 1), (0, 1)]);
+    assert_eq!(stats[2].shanten_by_turn, Vec::new());
+    assert_eq!((stats[1].tenpai_at_draw, stats[1].exhaustive_draws), (1, 1));
+    assert_eq!(
+        stats
+            .iter()
+            .map(|player| player.placement)
+            .collect::<Vec<_>>(),
+        vec![3, 2, 4, 1]
+    );
+
+    let labels = vec!["efficiency".to_string(); 2]
+        .into_iter()
+        .chain(vec!["completor".to_string(); 2])
+        .collect::<Vec<_>>();
+    let mut totals = BTreeMap::new();
+    add_game(&mut totals, &labels, &stats);
+    let efficiency = &totals["efficiency"];
+    assert_eq!(efficiency.games, 2);
+    assert_eq!(efficiency.win_rate(), 0.25);
+    assert_eq!(efficiency.average_win(), 1000.0);
+    assert_eq!(efficiency.tenpai_rate(), 0.5);
+    assert_eq!(efficiency.placements, [0, 1, 1, 0]);
+    assert_eq!(totals["completor"].average_placement(), 2.5);
+}
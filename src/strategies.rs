@@ -0,0 +1,213 @@
+use crate::types::mahjong_tile::*;
+use crate::types::suit_table::Evaluator;
+use crate::types::tile_counts::*;
+use crate::types::*;
+
+/// Names the built-in strategies go by on the command line.
+pub const STRATEGY_NAMES: [&str; 4] = ["completor", "kanchan-completor", "standard", "efficiency"];
+
+/// Parameters a built-in strategy reads, with the value it uses when none is given.
+pub fn strategy_parameters(name: &str) -> &'static [(&'static str, f64)] {
+    match name {
+        "efficiency" => &[("dora_weight", 1.0)],
+        _ => &[],
+    }
+}
+
+/// One of the strategies that come with the simulator, with the parameters it plays with.
+/// None of them open their hand yet.
+#[derive(Debug, Clone)]
+pub struct BuiltinStrategy {
+    pub discard: fn(&StrategyInput) -> usize,
+    pub params: StrategyParams,
+}
+
+impl Strategy for BuiltinStrategy {
+    fn discard(&self, input: &StrategyInput) -> usize {
+        (self.discard)(input)
+    }
+
+    fn params(&self) -> StrategyParams {
+        self.params.clone()
+    }
+}
+
+/// Looks up a built-in strategy by name.
+pub fn strategy_by_name(name: &str) -> Option<BuiltinStrategy> {
+    let discard: fn(&StrategyInput) -> usize = match name {
+        "completor" => completor,
+        "kanchan-completor" => kanchan_completor,
+        "standard" => standard_discarder,
+        "efficiency" => efficiency_discarder,
+        _ => return None,
+    };
+    Some(BuiltinStrategy {
+        discard,
+        params: StrategyParams::default(),
+    })
+}
+
+/// Index of `tile` in the strategy's hand, or of the tile just drawn should it be missing.
+fn position_in_hand(strat: &StrategyInput, tile: &MahjongTile) -> usize {
+    find_tile_in_hand(&strat.hand, tile).unwrap_or(strat.hand.len() - 1)
+}
+
+fn completor(strat: &StrategyInput) -> usize {
+    let mut own_hand = strat.hand.clone();
+    own_hand.sort();
+    let partial_hand = get_partial_completion(&own_hand);
+    if partial_hand.is_empty() {
+        //println!("Partial hand is empty, hand was complete!");
+        return 13;
+    }
+    if partial_hand.len() == 1 {
+        return position_in_hand(strat, &partial_hand[0]);
+    }
+
+    for tile in &partial_hand {
+        if tile.suit == Suit::Sangen || tile.suit == Suit::Kaze {
+            return position_in_hand(strat, tile);
+        }
+    }
+    let mut skip_following = false;
+    for tile_index in 0..partial_hand.len() - 1 {
+        let tile = &partial_hand[tile_index];
+        let right = &partial_hand[tile_index + 1];
+        if skip_following {
+            skip_following = false;
+            continue;
+        }
+        if tile.value + 1 == right.value && tile.suit == right.suit {
+            //keep the tile and the next tile
+            skip_following = true;
+            continue;
+        }
+        return position_in_hand(strat, tile);
+    }
+    for tile in &partial_hand {
+        if tile.value == 1 || tile.value == 9 {
+            return position_in_hand(strat, tile);
+        }
+    }
+    position_in_hand(strat, &partial_hand[partial_hand.len() - 1])
+}
+
+fn kanchan_completor(strat: &StrategyInput) -> usize {
+    let mut own_hand = strat.hand.clone();
+    own_hand.sort();
+    let partial_hand = get_partial_completion(&own_hand);
+    if partial_hand.is_empty() {
+        //println!("Partial hand is empty, hand was complete!");
+        return 13;
+    }
+    if partial_hand.len() == 1 {
+        return position_in_hand(strat, &partial_hand[0]);
+    }
+
+    for tile in &partial_hand {
+        if tile.suit == Suit::Sangen || tile.suit == Suit::Kaze {
+            return position_in_hand(strat, tile);
+        }
+    }
+    let mut skip_following = false;
+    for tile_index in 0..partial_hand.len() - 1 {
+        let tile = &partial_hand[tile_index];
+        let right = &partial_hand[tile_index + 1];
+
+        if (tile.value + 1 == right.value || tile.value + 2 == right.value)
+            && tile.suit == right.suit
+        {
+            //keep the tile and the next tile
+            skip_following = true;
+            continue;
+        } else if skip_following {
+            skip_following = false;
+            continue;
+        }
+        return position_in_hand(strat, tile);
+    }
+    for tile in &partial_hand {
+        if tile.value == 1 || tile.value == 9 {
+            return position_in_hand(strat, tile);
+        }
+    }
+    position_in_hand(strat, &partial_hand[partial_hand.len() - 1])
+}
+
+fn standard_discarder(strat: &StrategyInput) -> usize {
+    let mut own_hand = strat.hand.clone();
+    own_hand.sort();
+    let partial_hand = get_partial_completion(&own_hand);
+    if partial_hand.is_empty() {
+        return 13;
+    }
+    position_in_hand(strat, &partial_hand[partial_hand.len() - 1])
+}
+
+/// Discards toward the lowest shanten, breaking ties by the number of unseen tiles that
+/// would improve the hand. Throwing a dora costs `dora_weight` of those tiles.
+fn efficiency_discarder(strat: &StrategyInput) -> usize {
+    let dora_weight = strat.param("dora_weight", 1.0);
+    let mut counts = to_counts(&strat.hand);
+    let mut visible = counts;
+    for tile in strat.discards.iter().flatten() {
+        visible[tile_index(tile)] += 1;
+    }
+
+    let mut best: Option<(i8, f64, usize)> = None;
+    for index in 0..TILE_KINDS {
+        if counts[index] == 0 {
+            continue;
+        }
+        // Keep red fives and dora while a plain copy of the same tile can go instead
+        let (hand_index, tile) = strat
+            .hand
+            .iter()
+            .enumerate()
+            .filter(|(_, tile)| tile_index(tile) == index)
+            .min_by_key(|(_, tile)| tile.is_red || tile.is_dora)
+            .unwrap();
+        counts[index] -= 1;
+        let shanten = Evaluator::Table.shanten(&counts);
+        let mut value = acceptance(&mut counts, &visible, shanten) as f64;
+        if tile.is_dora || tile.is_red {
+            value -= dora_weight;
+        }
+        counts[index] += 1;
+        if best.is_none_or(|(best_shanten, best_value, _)| {
+            shanten < best_shanten || shanten == best_shanten && value > best_value
+        }) {
+            best = Some((shanten, value, hand_index));
+        }
+    }
+    best.map_or(0, |(_, _, hand_index)| hand_index)
+}
+
+/// Unseen tiles that would bring the hand below `shanten`.
+fn acceptance(counts: &mut TileCounts, visible: &TileCounts, shanten: i8) -> u8 {
+    let mut accepted = 0;
+    for index in 0..TILE_KINDS {
+        if visible[index] >= 4 || !near_hand(counts, index) {
+            continue;
+        }
+        counts[index] += 1;
+        if Evaluator::Table.shanten(counts) < shanten {
+            accepted += 4 - visible[index];
+        }
+        counts[index] -= 1;
+    }
+    accepted
+}
+
+/// Only tiles held or within two of a held number tile can improve a hand.
+fn near_hand(counts: &TileCounts, index: usize) -> bool {
+    if counts[index] > 0 {
+        return true;
+    }
+    if index >= 27 {
+        return false;
+    }
+    let suit_start = index / 9 * 9;
+    (index.saturating_sub(2).max(suit_start)..=(index + 2).min(suit_start + 8))
+        .any(|neighbour| counts[neighbour] > 0)
+}
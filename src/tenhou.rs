@@ -0,0 +1,1052 @@
+use crate::event_log::*;
+use crate::import::ImportError;
+use crate::log_replay::TableState;
+use crate::types::mahjong_tile::*;
+use crate::types::notation::{format_tiles, parse_tiles, NotationError};
+use crate::types::tile_counts::*;
+use crate::types::*;
+use serde_json::{json, Value};
+
+// Tenhou.net/6 logs number tiles 11-19, 21-29 and 31-39 for manzu, pinzu and souzu, 41-47
+// for the winds and then haku, hatsu and chun, and 51-53 for red fives. Each round lists
+// every seat's starting hand, draws and discards, where 60 marks a discard of the tile
+// just drawn and calls are strings such as "p151515" with the `p` placed on the side the
+// tile came from. Kans declared from the hand ("151515a15") stand among the discards, and
+// a 0 takes the place of the discard an open kan replaces.
+
+const TSUMOGIRI: u8 = 60;
+
+/// Yaku by their number in Tenhou's logs, with the names our scoring and Tenhou use.
+const YAKU: [(&str, &str); 55] = [
+    ("menzen tsumo", "門前清自摸和"),
+    ("riichi", "立直"),
+    ("ippatsu", "一発"),
+    ("chankan", "槍槓"),
+    ("rinshan kaihou", "嶺上開花"),
+    ("haitei", "海底摸月"),
+    ("houtei", "河底撈魚"),
+    ("pinfu", "平和"),
+    ("tanyao", "断幺九"),
+    ("iipeikou", "一盃口"),
+    ("yakuhai", "自風 東"),
+    ("yakuhai", "自風 南"),
+    ("yakuhai", "自風 西"),
+    ("yakuhai", "自風 北"),
+    ("yakuhai", "場風 東"),
+    ("yakuhai", "場風 南"),
+    ("yakuhai", "場風 西"),
+    ("yakuhai", "場風 北"),
+    ("yakuhai", "役牌 白"),
+    ("yakuhai", "役牌 發"),
+    ("yakuhai", "役牌 中"),
+    ("double riichi", "両立直"),
+    ("chiitoitsu", "七対子"),
+    ("chanta", "混全帯幺九"),
+    ("ittsu", "一気通貫"),
+    ("sanshoku", "三色同順"),
+    ("sanshoku doukou", "三色同刻"),
+    ("sankantsu", "三槓子"),
+    ("toitoi", "対々和"),
+    ("sanankou", "三暗刻"),
+    ("shousangen", "小三元"),
+    ("honroutou", "混老頭"),
+    ("ryanpeikou", "二盃口"),
+    ("junchan", "純全帯幺九"),
+    ("honitsu", "混一色"),
+    ("chinitsu", "清一色"),
+    ("renhou", "人和"),
+    ("tenhou", "天和"),
+    ("chiihou", "地和"),
+    ("daisangen", "大三元"),
+    ("suuankou", "四暗刻"),
+    ("suuankou tanki", "四暗刻単騎"),
+    ("tsuuiisou", "字一色"),
+    ("ryuuiisou", "緑一色"),
+    ("chinroutou", "清老頭"),
+    ("chuuren poutou", "九蓮宝燈"),
+    ("junsei chuuren poutou", "純正九蓮宝燈"),
+    ("kokushi musou", "国士無双"),
+    ("kokushi musou juusanmen", "国士無双１３面"),
+    ("daisuushii", "大四喜"),
+    ("shousuushii", "小四喜"),
+    ("suukantsu", "四槓子"),
+    ("dora", "ドラ"),
+    ("ura dora", "裏ドラ"),
+    ("aka dora", "赤ドラ"),
+];
+
+/// Rounds that end before the wall runs out: our name, the mjlog `type` and Tenhou's label.
+const ABORTIVE_DRAWS: [(&str, &str, &str); 5] = [
+    ("nine_terminals", "yao9", "九種九牌"),
+    ("four_winds", "kaze4", "四風連打"),
+    ("four_riichi", "reach4", "四家立直"),
+    ("triple_ron", "ron3", "三家和了"),
+    ("four_kans", "kan4", "四槓散了"),
+];
+
+/// Our name for a yaku given by its Tenhou id.
+pub fn yaku_by_id(id: usize) -> String {
+    match YAKU.get(id) {
+        Some((name, _)) => name.to_string(),
+        None => format!("yaku {}", id),
+    }
+}
+
+/// Our name for an abortive draw given as an mjlog `type`.
+pub fn abortive_draw_reason(kind: &str) -> &str {
+    ABORTIVE_DRAWS
+        .iter()
+        .find(|(_, mjlog, _)| *mjlog == kind)
+        .map_or(kind, |(reason, _, _)| reason)
+}
+
+/// Tenhou.net/6 number of a tile.
+pub fn tenhou_tile(tile: &MahjongTile) -> u8 {
+    match tile.suit {
+        Suit::Manzu if tile.is_red => 51,
+        Suit::Pinzu if tile.is_red => 52,
+        Suit::Souzu if tile.is_red => 53,
+        Suit::Manzu => 10 + tile.value,
+        Suit::Pinzu => 20 + tile.value,
+        Suit::Souzu => 30 + tile.value,
+        Suit::Kaze => 40 + tile.value,
+        Suit::Sangen => 44 + tile.value,
+    }
+}
+
+fn tile_codes(notation: &str) -> Result<Vec<u8>, NotationError> {
+    Ok(parse_tiles(notation)?.iter().map(tenhou_tile).collect())
+}
+
+fn tile_code(notation: &str) -> Result<u8, NotationError> {
+    Ok(tenhou_tile(&notation.parse::<MahjongTile>()?))
+}
+
+enum Outcome {
+    Win {
+        seat: usize,
+        from: Option<usize>,
+        score: HandScore,
+    },
+    ExhaustiveDraw,
+    AbortiveDraw(String),
+}
+
+struct Round {
+    number: usize,
+    honba: usize,
+    dealer: usize,
+    points: Vec<i32>,
+    dora: Vec<u8>,
+    hands: Vec<Vec<u8>>,
+    takes: Vec<Vec<Value>>,
+    discards: Vec<Vec<Value>>,
+    last_draw: Option<(usize, String)>,
+    last_discard: Option<MahjongTile>,
+    riichi: [bool; 4],
+    outcome: Option<Outcome>,
+}
+
+fn marked(codes: &[u8], marker: char, position: usize) -> String {
+    let mut call = String::new();
+    for (index, code) in codes.iter().enumerate() {
+        if index == position {
+            call.push(marker);
+        }
+        call.push_str(&code.to_string());
+    }
+    call
+}
+
+impl Round {
+    fn call(&mut self, seat: usize, from: usize, kind: CallKind, meld: &[MahjongTile]) {
+        let codes: Vec<u8> = meld.iter().map(tenhou_tile).collect();
+        // Left, across and right put the marker first, second and last
+        let position = match (from + 4 - seat) % 4 {
+            3 => 0,
+            2 => 1,
+            _ => codes.len() - 1,
+        };
+        match kind {
+            CallKind::Chi => {
+                // The called tile goes first
+                let mut codes = codes;
+                if let Some(called) = self.last_discard {
+                    let index = codes
+                        .iter()
+                        .position(|&code| code == tenhou_tile(&called))
+                        .unwrap_or(0);
+                    let code = codes.remove(index);
+                    codes.insert(0, code);
+                }
+                self.takes[seat].push(json!(marked(&codes, 'c', 0)));
+            }
+            CallKind::Pon => self.takes[seat].push(json!(marked(&codes, 'p', position))),
+            CallKind::OpenKan => {
+                self.takes[seat].push(json!(marked(&codes, 'm', position)));
+                // The kan takes the place of a discard
+                self.discards[seat].push(json!(0));
+            }
+            CallKind::ClosedKan => self.discards[seat].push(json!(marked(&codes, 'a', 3))),
+            CallKind::AddedKan => self.discards[seat].push(json!(marked(&codes, 'k', 0))),
+        }
+    }
+
+    fn to_json(&self, deltas: &[i32]) -> Value {
+        let mut round = vec![
+            json!([self.number, self.honba, 0]),
+            json!(self.points),
+            json!(self.dora),
+            json!([]),
+        ];
+        for seat in 0..4 {
+            round.push(json!(self.hands[seat]));
+            round.push(json!(self.takes[seat]));
+            round.push(json!(self.discards[seat]));
+        }
+        round.push(match &self.outcome {
+            Some(Outcome::Win { seat, from, score }) => {
+                let mut details = vec![
+                    json!(seat),
+                    json!(from.unwrap_or(*seat)),
+                    json!(seat),
+                    json!(score_text(score, *seat == self.dealer, from.is_none())),
+                ];
+                details.extend(
+                    score
+                        .yaku
+                        .iter()
+                        .map(|yaku| json!(format!("{}({}飜)", yaku_name(&yaku.name), yaku.han))),
+                );
+                json!(["和了", deltas, details])
+            }
+            Some(Outcome::AbortiveDraw(reason)) => {
+                let label = ABORTIVE_DRAWS
+                    .iter()
+                    .find(|(name, _, _)| name == reason)
+                    .map_or(reason.as_str(), |(_, _, label)| label);
+                json!([label])
+            }
+            _ => json!(["流局", deltas]),
+        });
+        Value::Array(round)
+    }
+}
+
+/// Converts the events of one game into a Tenhou.net/6 JSON log.
+pub fn to_tenhou(events: &[GameEvent]) -> Result<Value, NotationError> {
+    let mut names = vec![String::new(); 4];
+    let mut title = "mahjong-simulator".to_string();
+    let mut rules = "般南喰";
+    let mut starting_points = 25000;
+    let mut rounds: Vec<Value> = Vec::new();
+    let mut current: Option<Round> = None;
+    let mut previous_round: Option<u8> = None;
+    let mut honba = 0;
+    let mut final_scores = Vec::new();
+
+    for event in events {
+        match event {
+            GameEvent::GameStart {
+                seed,
+                ruleset,
+                strategies,
+            } => {
+                names = strategies.clone();
+                title = format!("mahjong-simulator, game seed {}", seed);
+                if ruleset == "tonpuusen" {
+                    rules = "般東喰";
+                }
+            }
+            GameEvent::Deal {
+                round,
+                dealer,
+                points,
+                hands,
+            } => {
+                // Rounds are numbered the way Tenhou does, East 1 being 0 with seat 0
+                // dealing, and every deal that repeats a round adds a honba
+                match previous_round {
+                    None => starting_points = points[0],
+                    Some(previous) if previous == *round => honba += 1,
+                    Some(_) => honba = 0,
+                }
+                previous_round = Some(*round);
+                current = Some(Round {
+                    number: *round as usize,
+                    honba,
+                    dealer: *dealer,
+                    points: points.clone(),
+                    dora: Vec::new(),
+                    hands: hands
+                        .iter()
+                        .map(|hand| tile_codes(hand))
+                        .collect::<Result<_, _>>()?,
+                    takes: vec![Vec::new(); 4],
+                    discards: vec![Vec::new(); 4],
+                    last_draw: None,
+                    last_discard: None,
+                    riichi: [false; 4],
+                    outcome: None,
+                });
+            }
+            GameEvent::GameEnd { points, uma } => {
+                for (points, uma) in points.iter().zip(uma) {
+                    final_scores.push(json!(points));
+                    final_scores.push(json!((points + uma - starting_points) as f64 / 1000.0));
+                }
+            }
+            _ => {
+                let Some(round) = current.as_mut() else {
+                    continue;
+                };
+                match event {
+                    GameEvent::DoraFlip { indicator } => round.dora.push(tile_code(indicator)?),
+                    GameEvent::Draw { seat, tile } => {
+                        round.takes[*seat].push(json!(tile_code(tile)?));
+                        round.last_draw = Some((*seat, tile.clone()));
+                    }
+                    GameEvent::Discard { seat, tile } => {
+                        let drawn = round.last_draw.take() == Some((*seat, tile.clone()));
+                        round.last_discard = Some(tile.parse()?);
+                        let code = if drawn { TSUMOGIRI } else { tile_code(tile)? };
+                        if round.riichi[*seat] {
+                            round.riichi[*seat] = false;
+                            round.discards[*seat].push(json!(format!("r{}", code)));
+                        } else {
+                            round.discards[*seat].push(json!(code));
+                        }
+                    }
+                    GameEvent::Riichi { seat } => round.riichi[*seat] = true,
+                    GameEvent::Call {
+                        seat,
+                        from,
+                        kind,
+                        tiles,
+                    } => {
+                        round.call(*seat, *from, *kind, &parse_tiles(tiles)?);
+                        round.last_draw = None;
+                    }
+                    GameEvent::Win {
+                        seat, from, score, ..
+                    } => {
+                        round.outcome = Some(Outcome::Win {
+                            seat: *seat,
+                            from: *from,
+                            score: score.clone(),
+                        })
+                    }
+                    GameEvent::ExhaustiveDraw { .. } => {
+                        round.outcome = Some(Outcome::ExhaustiveDraw)
+                    }
+                    GameEvent::AbortiveDraw { reason } => {
+                        round.outcome = Some(Outcome::AbortiveDraw(reason.clone()))
+                    }
+                    GameEvent::Payment { deltas } => {
+                        if let Some(round) = current.take() {
+                            rounds.push(round.to_json(deltas));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    Ok(json!({
+        "title": [title, ""],
+        "name": names,
+        "rule": { "disp": rules, "aka": 0 },
+        "log": rounds,
+        "sc": final_scores,
+    }))
+}
+
+fn score_text(score: &HandScore, dealer: bool, tsumo: bool) -> String {
+    let round_up = |points: i32| (points + 99) / 100 * 100;
+    let value = match score.base_points {
+        8000.. => "役満".to_string(),
+        6000.. => "三倍満".to_string(),
+        4000.. => "倍満".to_string(),
+        3000.. => "跳満".to_string(),
+        2000.. => "満貫".to_string(),
+        _ => format!("{}符{}飜", score.fu, score.han),
+    };
+    let base = score.base_points;
+    let points = match (dealer, tsumo) {
+        (true, true) => format!("{}点∀", round_up(2 * base)),
+        (false, true) => format!("{}-{}点", round_up(base), round_up(2 * base)),
+        (true, false) => format!("{}点", round_up(6 * base)),
+        (false, false) => format!("{}点", round_up(4 * base)),
+    };
+    value + &points
+}
+
+fn yaku_name(name: &str) -> &str {
+    match name {
+        "yakuhai" => "役牌",
+        _ => YAKU
+            .iter()
+            .find(|(ours, _)| *ours == name)
+            .map_or(name, |(_, tenhou)| tenhou),
+    }
+}
+
+fn tile_from_code(code: u64) -> Result<MahjongTile, ImportError> {
+    let index = match code {
+        11..=19 => code - 11,
+        21..=29 => code - 12,
+        31..=39 => code - 13,
+        41..=47 => code - 14,
+        51..=53 => {
+            let mut tile = index_to_tile(9 * (code as usize - 51) + 4);
+            tile.is_red = true;
+            return Ok(tile);
+        }
+        _ => return Err(ImportError::Malformed(format!("unknown tile {}", code))),
+    };
+    Ok(index_to_tile(index as usize))
+}
+
+fn tiles_from_codes(codes: &Value) -> Result<Vec<MahjongTile>, ImportError> {
+    let codes = codes
+        .as_array()
+        .ok_or_else(|| ImportError::Malformed(format!("expected tiles, got {}", codes)))?;
+    codes
+        .iter()
+        .map(|code| {
+            code.as_u64()
+                .ok_or_else(|| ImportError::Malformed(format!("expected a tile, got {}", code)))
+                .and_then(tile_from_code)
+        })
+        .collect()
+}
+
+/// A call or kan written like "c275226" or "1515p15": its letter, where the letter stands
+/// among the tiles and the tiles themselves.
+fn parse_call(call: &str) -> Result<(char, usize, Vec<MahjongTile>), ImportError> {
+    let malformed = || ImportError::Malformed(format!("call \"{}\"", call));
+    let mut marker = None;
+    let mut tiles = Vec::new();
+    let mut rest = call;
+    while !rest.is_empty() {
+        match rest.chars().next() {
+            Some(letter @ ('c' | 'p' | 'm' | 'a' | 'k')) => {
+                // An added kan keeps the marker of the pon it was added to
+                if marker.is_none_or(|(previous, _)| previous == 'p') {
+                    marker = Some((letter, tiles.len()));
+                }
+                rest = &rest[1..];
+            }
+            _ => {
+                let code = rest.get(..2).and_then(|code| code.parse().ok());
+                tiles.push(tile_from_code(code.ok_or_else(malformed)?)?);
+                rest = &rest[2..];
+            }
+        }
+    }
+    let (letter, position) = marker.ok_or_else(malformed)?;
+    Ok((letter, position.min(tiles.len().saturating_sub(1)), tiles))
+}
+
+/// Score details like "30符1飜1000点" or "満貫8000点" together with the yaku listed after them.
+fn score_from_text(text: &str, yaku: Vec<Yaku>) -> HandScore {
+    let han: i32 = yaku.iter().map(|yaku| yaku.han).sum();
+    let limit = [
+        ("三倍満", 6000),
+        ("倍満", 4000),
+        ("跳満", 3000),
+        ("満貫", 2000),
+        ("役満", 8000),
+    ]
+    .iter()
+    .find(|(name, _)| text.contains(name));
+    let fu = text
+        .split_once('符')
+        .and_then(|(fu, _)| fu.parse().ok())
+        .unwrap_or(0);
+    let base_points = match limit {
+        Some((_, points)) => *points,
+        None => (fu << (2 + han.clamp(0, 16))).min(2000),
+    };
+    HandScore {
+        han,
+        fu,
+        yaku,
+        base_points,
+    }
+}
+
+fn yaku_from_text(text: &str) -> Yaku {
+    let (name, han) = text.split_once('(').unwrap_or((text, ""));
+    let han = match han.trim_end_matches(')') {
+        "役満" => 13,
+        han => han.trim_end_matches('飜').parse().unwrap_or(0),
+    };
+    let name = match YAKU.iter().find(|(_, tenhou)| *tenhou == name) {
+        Some((ours, _)) => ours.to_string(),
+        None if name == "役牌" => "yakuhai".to_string(),
+        None => name.to_string(),
+    };
+    Yaku { name, han }
+}
+
+/// Points or payments of the four seats.
+fn points_from(value: &Value) -> Result<Vec<i32>, ImportError> {
+    match value.as_array() {
+        Some(points) if points.len() == 4 => Ok(points
+            .iter()
+            .map(|points| points.as_i64().unwrap_or(0) as i32)
+            .collect()),
+        _ => Err(ImportError::Malformed(format!(
+            "expected points of four seats, got {}",
+            value
+        ))),
+    }
+}
+
+fn last_draw_code(takes: &[Value]) -> Option<u64> {
+    takes.iter().rev().find_map(|take| take.as_u64())
+}
+
+/// Events of one round, played out in the order Tenhou's per-seat lists imply: whoever's
+/// turn it is takes their next draw and discard, unless another seat's next take is a call
+/// on that discard.
+fn round_from_tenhou(round: &[Value], events: &mut Vec<GameEvent>) -> Result<(), ImportError> {
+    let malformed = |what: &str| ImportError::Malformed(what.to_string());
+    let number = round[0][0]
+        .as_u64()
+        .ok_or_else(|| malformed("round without a number"))?;
+    let dealer = (number % 4) as usize;
+    let mut dora = tiles_from_codes(&round[2])?.into_iter();
+    let mut takes = Vec::new();
+    let mut discards = Vec::new();
+    let mut hands = Vec::new();
+    for seat in 0..4 {
+        let mut hand = tiles_from_codes(&round[4 + 3 * seat])?;
+        hand.sort();
+        hands.push(format_tiles(&hand));
+        takes.push(round[5 + 3 * seat].as_array().cloned().unwrap_or_default());
+        discards.push(round[6 + 3 * seat].as_array().cloned().unwrap_or_default());
+    }
+
+    let points = points_from(&round[1])?;
+
+    let mut state = TableState::default();
+    let mut emit = |event: GameEvent, state: &mut TableState| {
+        // Mistakes in the log are for the replay to report
+        let _ = state.apply(&event);
+        events.push(event);
+    };
+    emit(
+        GameEvent::Deal {
+            round: number as u8,
+            dealer,
+            points,
+            hands,
+        },
+        &mut state,
+    );
+    if let Some(indicator) = dora.next() {
+        let indicator = indicator.to_string();
+        emit(GameEvent::DoraFlip { indicator }, &mut state);
+    }
+
+    let (mut taken, mut discarded) = ([0; 4], [0; 4]);
+    let mut turn = dealer;
+    'turns: while let Some(take) = takes[turn].get(taken[turn]) {
+        taken[turn] += 1;
+        match take {
+            Value::String(call) => {
+                let (letter, position, tiles) = parse_call(call)?;
+                let (kind, from) = match letter {
+                    'c' => (CallKind::Chi, (turn + 3) % 4),
+                    'p' => (CallKind::Pon, (turn + 3 - position.min(2)) % 4),
+                    'm' => (CallKind::OpenKan, (turn + 3 - position.min(2)) % 4),
+                    _ => return Err(malformed("closed kan among a seat's draws")),
+                };
+                let tiles = format_tiles(&tiles);
+                emit(
+                    GameEvent::Call {
+                        seat: turn,
+                        from,
+                        kind,
+                        tiles,
+                    },
+                    &mut state,
+                );
+                if kind == CallKind::OpenKan {
+                    // Skip the placeholder discard and draw the replacement tile
+                    discarded[turn] += 1;
+                    if let Some(indicator) = dora.next() {
+                        let indicator = indicator.to_string();
+                        emit(GameEvent::DoraFlip { indicator }, &mut state);
+                    }
+                    continue 'turns;
+                }
+            }
+            code => {
+                let code = code.as_u64().ok_or_else(|| malformed("unknown draw"))?;
+                let tile = tile_from_code(code)?.to_string();
+                emit(GameEvent::Draw { seat: turn, tile }, &mut state);
+            }
+        }
+
+        let Some(discard) = discards[turn].get(discarded[turn]) else {
+            break;
+        };
+        discarded[turn] += 1;
+        let code = match discard {
+            Value::String(call) if call.starts_with('r') => {
+                emit(GameEvent::Riichi { seat: turn }, &mut state);
+                call[1..]
+                    .parse()
+                    .map_err(|_| malformed("riichi without a tile"))?
+            }
+            Value::String(call) => {
+                let (letter, _, tiles) = parse_call(call)?;
+                let kind = match letter {
+                    'a' => CallKind::ClosedKan,
+                    'k' => CallKind::AddedKan,
+                    _ => return Err(malformed("call among a seat's discards")),
+                };
+                emit(
+                    GameEvent::Call {
+                        seat: turn,
+                        from: turn,
+                        kind,
+                        tiles: format_tiles(&tiles),
+                    },
+                    &mut state,
+                );
+                if let Some(indicator) = dora.next() {
+                    let indicator = indicator.to_string();
+                    emit(GameEvent::DoraFlip { indicator }, &mut state);
+                }
+                continue 'turns;
+            }
+            code => code.as_u64().ok_or_else(|| malformed("unknown discard"))?,
+        };
+        let tile = match code {
+            60 => match state.last_draw {
+                Some((seat, tile)) if seat == turn => tile,
+                _ => return Err(malformed("tsumogiri without a draw")),
+            },
+            code => tile_from_code(code)?,
+        };
+        emit(
+            GameEvent::Discard {
+                seat: turn,
+                tile: tile.to_string(),
+            },
+            &mut state,
+        );
+
+        let calls_discard = |seat: usize, letters: &[char]| {
+            let Some(Value::String(call)) = takes[seat].get(taken[seat]) else {
+                return false;
+            };
+            let Ok((letter, position, tiles)) = parse_call(call) else {
+                return false;
+            };
+            let from = match letter {
+                'c' => (seat + 3) % 4,
+                _ => (seat + 3 - position.min(2)) % 4,
+            };
+            letters.contains(&letter) && from == turn && tiles.get(position) == Some(&tile)
+        };
+        let next = (1..4)
+            .map(|offset| (turn + offset) % 4)
+            .find(|&seat| calls_discard(seat, &['p', 'm']))
+            .or(Some((turn + 1) % 4).filter(|&seat| calls_discard(seat, &['c'])));
+        turn = next.unwrap_or((turn + 1) % 4);
+    }
+
+    let result = round[16]
+        .as_array()
+        .ok_or_else(|| malformed("round without a result"))?;
+    let label = result
+        .first()
+        .and_then(|label| label.as_str())
+        .unwrap_or("");
+    if label == "和了" {
+        for pair in result[1..].chunks(2) {
+            let [deltas, details] = pair else {
+                return Err(malformed("win without details"));
+            };
+            let seat = details[0].as_u64().unwrap_or(0) as usize;
+            let from = details[1].as_u64().unwrap_or(0) as usize;
+            let from = (from != seat).then_some(from);
+            let winning_tile = match (from, state.last_draw, state.last_discard) {
+                (None, Some((drawer, tile)), _) if drawer == seat => tile,
+                (Some(from), _, Some((discarder, tile))) if discarder == from => tile,
+                // The turns went astray; the lists still say which tile won and the
+                // replay reports what went wrong
+                (None, ..) => tile_from_code(last_draw_code(&takes[seat]).unwrap_or(0))?,
+                (Some(from), ..) => {
+                    let code = match discards[from].last() {
+                        Some(Value::String(riichi)) => riichi[1..].parse().unwrap_or(0),
+                        Some(code) => code.as_u64().unwrap_or(0),
+                        None => 0,
+                    };
+                    match code {
+                        60 => tile_from_code(last_draw_code(&takes[from]).unwrap_or(0))?,
+                        code => tile_from_code(code)?,
+                    }
+                }
+            };
+            let mut closed = state.hands[seat].clone();
+            if from.is_none() {
+                if let Some(position) = closed.iter().rposition(|tile| *tile == winning_tile) {
+                    closed.remove(position);
+                }
+            }
+            closed.sort();
+            closed.push(winning_tile);
+            let hand = Hand {
+                closed,
+                called: state.melds[seat]
+                    .iter()
+                    .map(|(_, meld)| meld.clone())
+                    .collect(),
+            };
+            let yaku = details
+                .as_array()
+                .map(|details| details[4..].to_vec())
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|yaku| yaku.as_str().map(yaku_from_text))
+                .collect();
+            let score = score_from_text(details[3].as_str().unwrap_or(""), yaku);
+            emit(
+                GameEvent::Win {
+                    seat,
+                    from,
+                    hand: hand.to_string(),
+                    winning_tile: winning_tile.to_string(),
+                    score,
+                },
+                &mut state,
+            );
+            let deltas = points_from(deltas)?;
+            emit(GameEvent::Payment { deltas }, &mut state);
+        }
+        return Ok(());
+    }
+
+    let deltas = match result.get(1) {
+        Some(deltas) => points_from(deltas)?,
+        None => vec![0; 4],
+    };
+    match ABORTIVE_DRAWS
+        .iter()
+        .find(|(_, _, tenhou)| *tenhou == label)
+    {
+        Some((reason, _, _)) => emit(
+            GameEvent::AbortiveDraw {
+                reason: reason.to_string(),
+            },
+            &mut state,
+        ),
+        None => {
+            let tenpai = match label {
+                "全員聴牌" => (0..4).collect(),
+                _ => (0..4).filter(|&seat| deltas[seat] > 0).collect(),
+            };
+            emit(GameEvent::ExhaustiveDraw { tenpai }, &mut state);
+        }
+    }
+    emit(GameEvent::Payment { deltas }, &mut state);
+    Ok(())
+}
+
+/// Reads a Tenhou.net/6 JSON log into events, the way `to_tenhou` would have written them.
+pub fn from_tenhou(log: &Value) -> Result<Vec<GameEvent>, ImportError> {
+    let names = (0..4)
+        .map(|seat| log["name"][seat].as_str().unwrap_or("").to_string())
+        .collect();
+    let rules = log["rule"]["disp"].as_str().unwrap_or("");
+    let mut events = vec![GameEvent::GameStart {
+        seed: 0,
+        ruleset: if rules.contains('東') {
+            "tonpuusen"
+        } else {
+            "hanchan"
+        }
+        .to_string(),
+        strategies: names,
+    }];
+    let rounds = log["log"]
+        .as_array()
+        .filter(|rounds| !rounds.is_empty())
+        .ok_or_else(|| ImportError::Malformed("no rounds in log".to_string()))?;
+    for round in rounds {
+        match round.as_array() {
+            Some(round) if round.len() >= 17 => round_from_tenhou(round, &mut events)?,
+            _ => {
+                return Err(ImportError::Malformed(
+                    "a round with fewer than 17 entries".to_string(),
+                ))
+            }
+        }
+    }
+    // Every round's points were checked on the way
+    let starting_points = points_from(&rounds[0][1])?[0];
+    if let Some(scores) = log["sc"].as_array().filter(|scores| scores.len() == 8) {
+        let points: Vec<i32> = scores
+            .iter()
+            .step_by(2)
+            .map(|points| points.as_i64().unwrap_or(0) as i32)
+            .collect();
+        let uma = scores
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .zip(&points)
+            .map(|(score, points)| {
+                (score.as_f64().unwrap_or(0.0) * 1000.0).round() as i32 + starting_points - points
+            })
+            .collect();
+        events.push(GameEvent::GameEnd { points, uma });
+    }
+    Ok(events)
+}
+
+#[test]
+fn test_tenhou_tiles() {
+    let codes = tile_codes("19m0p55s1234567z").unwrap();
+    assert_eq!(codes, vec![11, 19, 52, 35, 35, 41, 42, 43, 44, 45, 46, 47]);
+}
+
+#[test]
+fn test_tenhou_round() {
+    let hands = [
+        "123m456p789s1122z",
+        "1112345678999m",
+        "19m19p19s1234567z",
+        "3344556677889p",
+    ];
+    let mut events = vec![
+        GameEvent::GameStart {
+            seed: 9,
+            ruleset: "hanchan".to_string(),
+            strategies: vec!["a".into(), "b".into(), "c".into(), "d".into()],
+        },
+        GameEvent::Deal {
+            round: 0,
+            dealer: 0,
+            points: vec![25000; 4],
+            hands: hands.iter().map(|hand| hand.to_string()).collect(),
+        },
+        GameEvent::DoraFlip {
+            indicator: "5z".to_string(),
+        },
+        GameEvent::Draw {
+            seat: 0,
+            tile: "7z".to_string(),
+        },
+        GameEvent::Discard {
+            seat: 0,
+            tile: "7z".to_string(),
+        },
+        GameEvent::Call {
+            seat: 3,
+            from: 0,
+            kind: CallKind::Pon,
+            tiles: "777z".to_string(),
+        },
+        GameEvent::Draw {
+            seat: 1,
+            tile: "2z".to_string(),
+        },
+        GameEvent::Discard {
+            seat: 1,
+            tile: "1m".to_string(),
+        },
+        GameEvent::Win {
+            seat: 2,
+            from: None,
+            hand: "19m19p19s1234567z1m".to_string(),
+            winning_tile: "1m".to_string(),
+            score: HandScore {
+                han: 13,
+                fu: 30,
+                yaku: vec![Yaku {
+                    name: "kokushi".to_string(),
+                    han: 13,
+                }],
+                base_points: 8000,
+            },
+        },
+        GameEvent::Payment {
+            deltas: vec![-16000, -8000, 32000, -8000],
+        },
+    ];
+    events.push(GameEvent::Deal {
+        round: 0,
+        dealer: 0,
+        points: vec![9000, 17000, 57000, 17000],
+        hands: hands.iter().map(|hand| hand.to_string()).collect(),
+    });
+    events.push(GameEvent::ExhaustiveDraw { tenpai: vec![] });
+    events.push(GameEvent::Payment { deltas: vec![0; 4] });
+
+    let log = to_tenhou(&events).unwrap();
+    assert_eq!(log["name"], json!(["a", "b", "c", "d"]));
+    let first = &log["log"][0];
+    assert_eq!(first[0], json!([0, 0, 0]));
+    assert_eq!(first[2], json!([45]));
+    assert_eq!(
+        first[4],
+        json!([11, 12, 13, 24, 25, 26, 37, 38, 39, 41, 41, 42, 42])
+    );
+    assert_eq!(first[5], json!([47]));
+    assert_eq!(first[6], json!([TSUMOGIRI]));
+    assert_eq!(first[8], json!([42]));
+    assert_eq!(first[9], json!([11]));
+    // Seat 0 is to seat 3's right
+    assert_eq!(first[14], json!(["4747p47"]));
+    assert_eq!(
+        first[16],
+        json!([
+            "和了",
+            [-16000, -8000, 32000, -8000],
+            [2, 2, 2, "役満8000-16000点", "kokushi(13飜)"]
+        ])
+    );
+    assert_eq!(log["log"][1][0], json!([0, 1, 0]));
+    assert_eq!(log["log"][1][16], json!(["流局", [0, 0, 0, 0]]));
+}
+
+#[test]
+fn test_from_tenhou() {
+    // East 1: seat 3 pons seat 1's 7z and seat 2 wins by ron on the 2m seat 3 discards
+    let log = json!({
+        "title": ["", ""],
+        "name": ["A", "B", "C", "D"],
+        "rule": { "disp": "般東喰", "aka": 1 },
+        "log": [[
+            [0, 0, 0],
+            [25000, 25000, 25000, 25000],
+            [45],
+            [],
+            [11, 11, 11, 12, 13, 14, 15, 16, 17, 18, 19, 19, 19],
+            [41],
+            [60],
+            [21, 22, 23, 24, 25, 26, 27, 28, 29, 31, 32, 47, 43],
+            [42],
+            [47],
+            [31, 32, 33, 34, 35, 36, 37, 38, 39, 41, 41, 13, 14],
+            [],
+            [],
+            [21, 22, 23, 24, 25, 26, 27, 28, 29, 47, 47, 44, 12],
+            ["47p4747"],
+            [12],
+            ["和了", [0, 0, 2000, -2000], [2, 3, 2, "30符2飜2000点", "一気通貫(2飜)"]],
+        ]],
+        "sc": [25000, 15.0, 25000, -5.0, 25000, -5.0, 25000, -5.0],
+    });
+    let events = from_tenhou(&log).unwrap();
+    assert_eq!(
+        events[0],
+        GameEvent::GameStart {
+            seed: 0,
+            ruleset: "tonpuusen".to_string(),
+            strategies: vec!["A".into(), "B".into(), "C".into(), "D".into()],
+        }
+    );
+    let plays: Vec<String> = events[3..events.len() - 3]
+        .iter()
+        .map(|event| match event {
+            GameEvent::Draw { seat, tile } => format!("{} draws {}", seat, tile),
+            GameEvent::Discard { seat, tile } => format!("{} discards {}", seat, tile),
+            GameEvent::Call {
+                seat, from, tiles, ..
+            } => format!("{} calls {} from {}", seat, tiles, from),
+            other => format!("{:?}", other),
+        })
+        .collect();
+    assert_eq!(
+        plays,
+        vec![
+            "0 draws 1z",
+            "0 discards 1z",
+            "1 draws 2z",
+            "1 discards 7z",
+            "3 calls 777z from 1",
+            "3 discards 2m",
+        ]
+    );
+    let GameEvent::Win {
+        seat,
+        from,
+        hand,
+        score,
+        ..
+    } = &events[events.len() - 3]
+    else {
+        panic!("expected a win, got {:?}", events[events.len() - 3]);
+    };
+    assert_eq!((*seat, *from), (2, Some(3)));
+    assert_eq!(hand, "34m123456789s11z2m");
+    assert_eq!(score.yaku[0].name, "ittsu");
+    assert_eq!((score.fu, score.base_points), (30, 480));
+    assert!(crate::log_replay::replay_log(&events).violations.is_empty());
+    assert_eq!(
+        events.last(),
+        Some(&GameEvent::GameEnd {
+            points: vec![25000; 4],
+            uma: vec![15000, -5000, -5000, -5000],
+        })
+    );
+
+    // Writing the events out again gives the same rounds
+    assert_eq!(
+        to_tenhou(&events).unwrap()["log"][0][14],
+        json!(["47p4747"])
+    );
+
+    // A round has to say every seat's points
+    let mut pointless = log.clone();
+    pointless["log"][0][1] = json!([]);
+    assert!(matches!(
+        from_tenhou(&pointless),
+        Err(ImportError::Malformed(_))
+    ));
+}
+
+#[test]
+fn test_simulated_game_round_trip() {
+    use crate::engine::simulate_game;
+    use crate::log_replay::replay_log;
+    use crate::strategies::strategy_by_name;
+    use std::sync::Arc;
+
+    let names = ["completor", "standard", "efficiency", "kanchan-completor"];
+    let seats: Vec<Arc<dyn Strategy>> = names
+        .iter()
+        .map(|name| -> Arc<dyn Strategy> { Arc::new(strategy_by_name(name).unwrap()) })
+        .collect();
+    for seed in 0..8 {
+        let mut log = EventLog::enabled();
+        log.record(|| GameEvent::GameStart {
+            seed,
+            ruleset: "hanchan".to_string(),
+            strategies: names.iter().map(|name| name.to_string()).collect(),
+        });
+        simulate_game(&Ruleset::hanchan(), &seats, seed, None, &mut log);
+        let exported = to_tenhou(&log.events).unwrap();
+        // Every round of a hanchan is East 1 to South 4, with the dealer drawing first
+        for round in exported["log"].as_array().unwrap() {
+            assert!(round[0][0].as_u64().unwrap() < 8, "round {}", round[0]);
+        }
+        let report = replay_log(&from_tenhou(&exported).unwrap());
+        assert_eq!(report.violations, Vec::<String>::new(), "seed {}", seed);
+        assert_eq!(
+            report.score_differences,
+            Vec::<String>::new(),
+            "seed {}",
+            seed
+        );
+    }
+}
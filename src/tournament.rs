@@ -0,0 +1,406 @@
+use crate::engine::simulate_game;
+use crate::event_log::EventLog;
+use crate::experiment::Rotation;
+use crate::output::write_header;
+use crate::seeding::game_seed;
+use crate::strategies::strategy_by_name;
+use crate::types::*;
+use itertools::Itertools;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Result, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+// A tournament seats a pool of strategies at tables of four, either every table the pool
+// can make once (round-robin) or, round by round, neighbours in the current ratings
+// (Swiss). Every game moves the Elo ratings of each pair of seats by who placed above whom,
+// and plays out a Tenhou-style dan for every strategy as if it had been one player.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Schedule {
+    /// Every table of four the pool can make, except one strategy alone
+    #[default]
+    RoundRobin,
+    /// Rounds of tables seated by rating, strongest together
+    Swiss,
+}
+
+/// Every multiset of four strategies except the tables one strategy plays alone.
+pub fn round_robin_tables(pool: &[String]) -> Vec<Vec<String>> {
+    pool.iter()
+        .cloned()
+        .combinations_with_replacement(4)
+        .filter(|table| table.iter().any(|name| name != &table[0]))
+        .collect()
+}
+
+/// Tables of four neighbours in `ranked`. A pool that doesn't split into fours seats its
+/// last table from the bottom four, and a pool smaller than four fills one table by
+/// repeating itself.
+pub fn swiss_tables(ranked: &[String]) -> Vec<Vec<String>> {
+    if ranked.len() < 4 {
+        return vec![ranked.iter().cycle().take(4).cloned().sorted().collect()];
+    }
+    let mut tables: Vec<Vec<String>> = ranked.chunks_exact(4).map(|table| table.to_vec()).collect();
+    if !ranked.len().is_multiple_of(4) {
+        tables.push(ranked[ranked.len() - 4..].to_vec());
+    }
+    tables
+}
+
+const INITIAL_ELO: f64 = 1500.0;
+/// Spread over the three opponents of every game
+const ELO_K: f64 = 32.0;
+
+/// Name, points to be promoted and the points lost for fourth place in a hanchan.
+/// Players start at novice, a promotion starts the next rank at half its target, and
+/// tenhoui is the top.
+const RANKS: [(&str, i32, i32); 21] = [
+    ("novice", 20, 0),
+    ("9 kyu", 20, 0),
+    ("8 kyu", 20, 0),
+    ("7 kyu", 20, 0),
+    ("6 kyu", 40, 0),
+    ("5 kyu", 60, 0),
+    ("4 kyu", 80, 0),
+    ("3 kyu", 100, 0),
+    ("2 kyu", 100, 0),
+    ("1 kyu", 100, 0),
+    ("1 dan", 400, 105),
+    ("2 dan", 800, 120),
+    ("3 dan", 1200, 135),
+    ("4 dan", 1600, 150),
+    ("5 dan", 2000, 165),
+    ("6 dan", 2400, 180),
+    ("7 dan", 2800, 195),
+    ("8 dan", 3200, 210),
+    ("9 dan", 3600, 225),
+    ("10 dan", 4000, 240),
+    ("tenhoui", 0, 0),
+];
+const FIRST_DAN: usize = 10;
+
+/// A rank on Tenhou's phoenix table ladder and the points towards the next one.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Dan {
+    pub rank: usize,
+    pub points: i32,
+}
+
+impl Dan {
+    /// Hanchan give 90 and 45 points for first and second; tonpuusen two thirds of every
+    /// number. Below 2 dan a player can't drop a rank.
+    pub fn add(&mut self, placement: usize, tonpuusen: bool) {
+        if self.rank + 1 == RANKS.len() {
+            return;
+        }
+        let (_, promotion, fourth) = RANKS[self.rank];
+        let points = match placement {
+            1 => 90,
+            2 => 45,
+            3 => 0,
+            _ => -fourth,
+        };
+        self.points += if tonpuusen { points * 2 / 3 } else { points };
+        if self.points >= promotion {
+            self.rank += 1;
+            self.points = RANKS[self.rank].1 / 2;
+        } else if self.points < 0 {
+            if self.rank > FIRST_DAN {
+                self.rank -= 1;
+                self.points = RANKS[self.rank].1 / 2;
+            } else {
+                self.points = 0;
+            }
+        }
+    }
+}
+
+impl fmt::Display for Dan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (name, promotion, _) = RANKS[self.rank];
+        if self.rank + 1 == RANKS.len() {
+            write!(f, "{}", name)
+        } else {
+            write!(f, "{} ({}/{})", name, self.points, promotion)
+        }
+    }
+}
+
+/// The dan a player with these placements settles at in the long run on the phoenix table,
+/// `(5 × first rate + 2 × second rate) / fourth rate − 2`. `None` without a fourth place.
+pub fn stable_dan(placements: &[u32; 4]) -> Option<f64> {
+    let games: u32 = placements.iter().sum();
+    if placements[3] == 0 || games == 0 {
+        return None;
+    }
+    let rate = |place: usize| placements[place] as f64 / games as f64;
+    Some((5.0 * rate(0) + 2.0 * rate(1)) / rate(3) - 2.0)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Standing {
+    pub name: String,
+    pub games: u32,
+    pub score: i64,
+    /// How often the strategy finished in each place
+    pub placements: [u32; 4],
+    pub elo: f64,
+    pub dan: Dan,
+}
+
+impl Standing {
+    pub fn average_score(&self) -> f64 {
+        self.score as f64 / self.games.max(1) as f64
+    }
+
+    pub fn average_placement(&self) -> f64 {
+        let sum: u32 = (0..4)
+            .map(|place| (place as u32 + 1) * self.placements[place])
+            .sum();
+        sum as f64 / self.games.max(1) as f64
+    }
+}
+
+pub struct Standings {
+    entries: BTreeMap<String, Standing>,
+    tonpuusen: bool,
+}
+
+impl Standings {
+    pub fn new(pool: &[String], tonpuusen: bool) -> Standings {
+        let entries = pool
+            .iter()
+            .map(|name| {
+                let standing = Standing {
+                    name: name.clone(),
+                    games: 0,
+                    score: 0,
+                    placements: [0; 4],
+                    elo: INITIAL_ELO,
+                    dan: Dan::default(),
+                };
+                (name.clone(), standing)
+            })
+            .collect();
+        Standings { entries, tonpuusen }
+    }
+
+    /// Adds one game, with the strategies and final scores in seat order. Ties share the
+    /// better place.
+    pub fn record(&mut self, names: &[String], scores: &[i32]) {
+        let placements: Vec<usize> = scores
+            .iter()
+            .map(|score| 1 + scores.iter().filter(|&other| other > score).count())
+            .collect();
+        let mut elo_changes = vec![0.0; names.len()];
+        for (i, j) in (0..names.len()).tuple_combinations() {
+            // A strategy learns nothing from beating itself
+            if names[i] == names[j] {
+                continue;
+            }
+            let (a, b) = (self.entries[&names[i]].elo, self.entries[&names[j]].elo);
+            let expected = 1.0 / (1.0 + 10f64.powf((b - a) / 400.0));
+            let actual = match placements[i].cmp(&placements[j]) {
+                std::cmp::Ordering::Less => 1.0,
+                std::cmp::Ordering::Equal => 0.5,
+                std::cmp::Ordering::Greater => 0.0,
+            };
+            let change = ELO_K / (names.len() - 1) as f64 * (actual - expected);
+            elo_changes[i] += change;
+            elo_changes[j] -= change;
+        }
+        for (seat, name) in names.iter().enumerate() {
+            let standing = self.entries.get_mut(name).expect("players are in the pool");
+            standing.games += 1;
+            standing.score += scores[seat] as i64;
+            standing.placements[placements[seat] - 1] += 1;
+            standing.elo += elo_changes[seat];
+            standing.dan.add(placements[seat], self.tonpuusen);
+        }
+    }
+
+    /// Strategies from the highest rating down, ties by name.
+    pub fn by_rating(&self) -> Vec<&Standing> {
+        self.entries
+            .values()
+            .sorted_by(|a, b| b.elo.total_cmp(&a.elo))
+            .collect()
+    }
+}
+
+/// Plays `rounds` rounds of tables from `pool`, `games` games at every table, and returns
+/// the standings and how many tables were played. A round-robin plays its tables once
+/// whatever `rounds` says.
+pub fn play_tournament(
+    pool: &[String],
+    (schedule, rounds, games): (Schedule, usize, usize),
+    ruleset: &Ruleset,
+    master_seed: u64,
+) -> (Standings, usize) {
+    let mut standings = Standings::new(pool, ruleset.rounds <= 4);
+    let rounds = match schedule {
+        Schedule::RoundRobin => 1,
+        Schedule::Swiss => rounds,
+    };
+    let mut table_count = 0;
+    for _ in 0..rounds {
+        let tables = match schedule {
+            Schedule::RoundRobin => round_robin_tables(pool),
+            Schedule::Swiss => {
+                let ranked: Vec<String> = standings
+                    .by_rating()
+                    .iter()
+                    .map(|standing| standing.name.clone())
+                    .collect();
+                swiss_tables(&ranked)
+            }
+        };
+        let played: Vec<(usize, &Vec<String>, usize)> = tables
+            .iter()
+            .enumerate()
+            .flat_map(|(index, table)| (0..games).map(move |game| (index, table, game)))
+            .collect();
+        let results: Vec<(Vec<String>, [i32; 4])> = played
+            .into_par_iter()
+            .map(|(index, table, game)| {
+                let table_index = table_count + index;
+                let seed = game_seed(master_seed, (table_index * games + game) as u64);
+                // Every strategy at the table takes every seat in turn
+                let seated: Vec<String> = Rotation::Rotate
+                    .seating(game)
+                    .iter()
+                    .map(|&entry| table[entry].clone())
+                    .collect();
+                let seats: Vec<Arc<dyn Strategy>> = seated
+                    .iter()
+                    .map(|name| -> Arc<dyn Strategy> {
+                        Arc::new(strategy_by_name(name).expect("the pool holds strategy names"))
+                    })
+                    .collect();
+                let result = simulate_game(ruleset, &seats, seed, None, &mut EventLog::disabled());
+                let scores = [
+                    result.player_1_score,
+                    result.player_2_score,
+                    result.player_3_score,
+                    result.player_4_score,
+                ];
+                (seated, scores)
+            })
+            .collect();
+        // Ratings are updated in a fixed order so they don't depend on the thread count
+        for (names, scores) in &results {
+            standings.record(names, scores);
+        }
+        table_count += tables.len();
+    }
+    (standings, table_count)
+}
+
+/// The leaderboard as CSV, best rated first.
+pub fn write_leaderboard(path: &Path, standings: &Standings, metadata: &str) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_header(&mut writer, metadata)?;
+    writeln!(
+        &mut writer,
+        "rank,strategy,games,elo,average_score,average_placement,first,second,third,fourth,\
+         stable_dan,dan,dan_points"
+    )?;
+    for (rank, standing) in standings.by_rating().iter().enumerate() {
+        writeln!(
+            &mut writer,
+            "{},{},{},{:.1},{:.1},{:.3},{},{},{},{},{},{},{}",
+            rank + 1,
+            standing.name,
+            standing.games,
+            standing.elo,
+            standing.average_score(),
+            standing.average_placement(),
+            standing.placements[0],
+            standing.placements[1],
+            standing.placements[2],
+            standing.placements[3],
+            stable_dan(&standing.placements)
+                .map(|dan| format!("{:.3}", dan))
+                .unwrap_or_default(),
+            RANKS[standing.dan.rank].0,
+            standing.dan.points
+        )?;
+    }
+    writer.flush()
+}
+
+#[test]
+fn test_tables() {
+    let pool: Vec<String> = ["a", "b", "c", "d", "e"]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+    // 70 multisets of four from five, minus the five tables of one strategy
+    assert_eq!(round_robin_tables(&pool).len(), 65);
+    assert_eq!(
+        swiss_tables(&pool),
+        vec![vec!["a", "b", "c", "d"], vec!["b", "c", "d", "e"]]
+    );
+    assert_eq!(swiss_tables(&pool[..2]), vec![vec!["a", "a", "b", "b"]]);
+}
+
+#[test]
+fn test_standings() {
+    let names: Vec<String> = ["a", "b", "c", "c"]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+    let mut standings = Standings::new(&names[..3], false);
+    standings.record(&names, &[40000, 30000, 20000, 10000]);
+    let ranked = standings.by_rating();
+    assert_eq!(ranked[0].name, "a");
+    // a beat three players it was expected to draw with, each worth a third of K / 2
+    assert!((ranked[0].elo - (INITIAL_ELO + 16.0)).abs() < 1e-9);
+    let total: f64 = ranked.iter().map(|standing| standing.elo).sum();
+    assert!((total - 3.0 * INITIAL_ELO).abs() < 1e-9);
+    assert_eq!(ranked[2].placements, [0, 0, 1, 1]);
+    assert_eq!(
+        ranked[0].dan,
+        Dan {
+            rank: 1,
+            points: 10
+        }
+    );
+}
+
+#[test]
+fn test_dan() {
+    let mut dan = Dan {
+        rank: FIRST_DAN + 1,
+        points: 400,
+    };
+    dan.add(4, false);
+    assert_eq!(dan.points, 280);
+    dan.add(4, true);
+    assert_eq!(dan.points, 200);
+    for _ in 0..2 {
+        dan.add(4, false);
+    }
+    assert_eq!(
+        dan,
+        Dan {
+            rank: FIRST_DAN,
+            points: 200
+        }
+    );
+    assert_eq!(dan.to_string(), "1 dan (200/400)");
+    let mut top = Dan {
+        rank: RANKS.len() - 2,
+        points: 3990,
+    };
+    top.add(1, false);
+    top.add(4, false);
+    assert_eq!((top.to_string(), top.points), ("tenhoui".to_string(), 0));
+    assert_eq!(stable_dan(&[25, 25, 25, 25]), Some(5.0));
+    assert_eq!(stable_dan(&[1, 0, 0, 0]), None);
+}
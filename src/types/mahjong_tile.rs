@@ -1,9 +1,12 @@
+use super::tile_counts::*;
 use itertools::Itertools;
 use rand::seq::SliceRandom;
+use rand::Rng;
 use std::cmp::{Ordering, PartialOrd};
 use std::collections::HashSet;
 const DUPLICATE_TILES: usize = 4;
 
+/// The three number suits, winds (kaze) and dragons (sangen).
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Suit {
     Manzu,
@@ -13,19 +16,19 @@ pub enum Suit {
     Sangen,
 }
 
+/// A tile. Winds count from East as 1 and dragons are 1 to 3 for haku, hatsu and chun.
+/// Copies are equal whether or not they are red or dora.
 #[derive(Debug, Copy, Clone, Eq)]
 pub struct MahjongTile {
     pub suit: Suit,
     pub value: u8,
     pub is_dora: bool,
+    pub is_red: bool,
 }
 
 impl PartialOrd for MahjongTile {
     fn partial_cmp(&self, other: &MahjongTile) -> Option<Ordering> {
-        match self.suit.partial_cmp(&other.suit) {
-            Some(Ordering::Equal) => self.value.partial_cmp(&other.value),
-            other => other,
-        }
+        Some(self.cmp(other))
     }
 }
 
@@ -37,10 +40,13 @@ impl PartialEq for MahjongTile {
 
 impl Ord for MahjongTile {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        self.suit
+            .cmp(&other.suit)
+            .then_with(|| self.value.cmp(&other.value))
     }
 }
 
+/// What is left of the wall, and the four starting hands.
 pub type Hands = (
     Vec<MahjongTile>, // Wall
     Vec<MahjongTile>,
@@ -49,7 +55,11 @@ pub type Hands = (
     Vec<MahjongTile>,
 );
 
-pub fn initialize_wall() -> (Vec<MahjongTile>, Vec<MahjongTile>, Vec<MahjongTile>) {
+/// A shuffled set of 136 tiles, split into the live wall, the dead wall and the dora
+/// indicators. The simulator plays without red fives, so every five is a plain one.
+pub fn initialize_wall<R: Rng>(
+    rng: &mut R,
+) -> (Vec<MahjongTile>, Vec<MahjongTile>, Vec<MahjongTile>) {
     let mut wall: Vec<MahjongTile> = Vec::new();
 
     for suit_index in 1..=5 {
@@ -67,17 +77,17 @@ pub fn initialize_wall() -> (Vec<MahjongTile>, Vec<MahjongTile>, Vec<MahjongTile
                 suit,
                 value,
                 is_dora: false,
+                is_red: false,
             })
         }
     }
 
     wall = wall
         .iter()
-        .flat_map(|&x| std::iter::repeat(x).take(DUPLICATE_TILES))
+        .flat_map(|&x| std::iter::repeat_n(x, DUPLICATE_TILES))
         .collect();
 
-    let mut rng = rand::thread_rng();
-    wall.shuffle(&mut rng);
+    wall.shuffle(rng);
 
     let mut wall_dead = wall.split_off(wall.len() - 14);
     let dora_indicators = wall_dead.split_off(wall_dead.len() - 10);
@@ -85,6 +95,22 @@ pub fn initialize_wall() -> (Vec<MahjongTile>, Vec<MahjongTile>, Vec<MahjongTile
     (wall, wall_dead, dora_indicators)
 }
 
+/// The tile a dora indicator makes dora, the next one in its suit.
+pub fn dora_for(indicator: &MahjongTile) -> MahjongTile {
+    let suit_modulo = match indicator.suit {
+        Suit::Manzu | Suit::Pinzu | Suit::Souzu => 9,
+        Suit::Kaze => 4,
+        Suit::Sangen => 3,
+    };
+    MahjongTile {
+        suit: indicator.suit,
+        value: indicator.value % suit_modulo + 1,
+        is_dora: false,
+        is_red: false,
+    }
+}
+
+/// Deals 13 tiles to each seat from the end of the wall.
 pub fn draw_hands(mut wall: Vec<MahjongTile>) -> Hands {
     let a = wall.split_off(wall.len() - 13);
     let b = wall.split_off(wall.len() - 13);
@@ -93,12 +119,14 @@ pub fn draw_hands(mut wall: Vec<MahjongTile>) -> Hands {
     (wall, a, b, c, d)
 }
 
+/// Moves the last tile of `hand_from` to `hand_to`.
 pub fn draw_tile(hand_from: &mut Vec<MahjongTile>, hand_to: &mut Vec<MahjongTile>) {
     if let Some(tile) = hand_from.pop() {
         hand_to.push(tile);
     }
 }
 
+/// Moves the tile at `tile_index` of `hand_from` to `hand_to`.
 pub fn move_tile(
     hand_from: &mut Vec<MahjongTile>,
     hand_to: &mut Vec<MahjongTile>,
@@ -108,61 +136,12 @@ pub fn move_tile(
     hand_to.push(tile);
 }
 
-pub fn print_hand(tiles: &[MahjongTile]) {
-    let mut result = String::new();
-    let mut suits: Vec<Suit> = Vec::new();
-    for suit in &[
-        Suit::Manzu,
-        Suit::Pinzu,
-        Suit::Souzu,
-        Suit::Kaze,
-        Suit::Sangen,
-    ] {
-        let suit_tiles: Vec<&MahjongTile> = tiles.iter().filter(|t| t.suit == *suit).collect();
-        if suit_tiles.is_empty() {
-            continue;
-        }
-        result.push_str(
-            &suit_tiles
-                .iter()
-                .map(|t| t.value.to_string())
-                .collect::<String>(),
-        );
-        match suit {
-            Suit::Manzu => result.push('m'),
-            Suit::Pinzu => result.push('p'),
-            Suit::Souzu => result.push('s'),
-            Suit::Kaze => result.push('k'),
-            Suit::Sangen => result.push('z'),
-        }
-        suits.push(*suit);
-    }
-    println!("{}", result);
-}
-
-pub fn print_tile(tile: &MahjongTile) {
-    let mut result = String::new();
-    result.push_str(&tile.value.to_string());
-    match &tile.suit {
-        Suit::Manzu => result.push('m'),
-        Suit::Pinzu => result.push('p'),
-        Suit::Souzu => result.push('s'),
-        Suit::Kaze => result.push('k'),
-        Suit::Sangen => result.push('z'),
-    }
-
-    println!("{}", result);
-}
-
-pub fn find_tile_in_hand(hand: &[MahjongTile], tile: &MahjongTile) -> usize {
-    for (tile_index, _) in hand.iter().enumerate() {
-        if tile == &hand[tile_index] {
-            return tile_index;
-        }
-    }
-    println!("tile not found");
-    14
+/// Index of the first copy of `tile` in `hand`, if it holds one.
+pub fn find_tile_in_hand(hand: &[MahjongTile], tile: &MahjongTile) -> Option<usize> {
+    hand.iter().position(|other| other == tile)
 }
+/// Every distinct triplet or sequence, and every distinct pair, that can be taken from
+/// `hand`.
 pub fn find_pairs_melds(hand: &[MahjongTile]) -> (Vec<Vec<MahjongTile>>, Vec<Vec<MahjongTile>>) {
     let (mut result_threes, mut result_pairs): (Vec<Vec<MahjongTile>>, Vec<Vec<MahjongTile>>) =
         (Vec::new(), Vec::new());
@@ -206,257 +185,44 @@ pub fn find_pairs_melds(hand: &[MahjongTile]) -> (Vec<Vec<MahjongTile>>, Vec<Vec
     }
     (result_threes, result_pairs)
 }
-pub fn is_subset<T: PartialEq + Clone>(superset: &[T], subset: &[T]) -> bool {
-    let mut temp_vec: Vec<T> = superset.to_vec();
-
-    let mut removed_count = 0;
-    for subset_element in subset {
-        let mut found = false;
-        for (i, superset_element) in temp_vec.iter().enumerate() {
-            if *superset_element == *subset_element {
-                temp_vec.remove(i);
-                removed_count += 1;
-                found = true;
-                break;
-            }
-        }
-        if !found {
-            return false;
-        }
-    }
-
-    removed_count == subset.len()
-}
-
+/// Whether a 13-tile hand is one tile from complete, and the tiles that complete it.
 pub fn check_tenpai(hand: &[MahjongTile]) -> (bool, Vec<MahjongTile>) {
-    let temp_hand = hand.to_vec();
-    let (is_tenpai, mut waits) = find_wait(&temp_hand);
+    let (is_tenpai, mut waits) = find_wait(hand);
     waits.sort();
     (is_tenpai, waits)
 }
 
+/// Like `check_tenpai`, with the waits in tile order.
 pub fn find_wait(hand: &[MahjongTile]) -> (bool, Vec<MahjongTile>) {
-    let mut waits: Vec<MahjongTile> = Vec::new();
-    for tile in hand {
-        if tile.suit != Suit::Kaze && tile.suit != Suit::Sangen {
-            for side in 0..=1 {
-                let mut temp_hand = hand.to_vec();
-                let test_tile = MahjongTile {
-                    value: tile.value - 1 + side * 2,
-                    suit: tile.suit,
-                    is_dora: false,
-                };
-                if waits.contains(&test_tile) {
-                    continue;
-                }
-
-                if (tile.value == 1 && side == 1)
-                    || (tile.value == 9 && side == 0)
-                    || (tile.value != 1 && tile.value != 9)
-                {
-                    temp_hand.push(test_tile);
-                    if is_complete(&temp_hand) {
-                        waits.push(test_tile);
-                    }
-                }
-            }
-        }
-        let mut temp_hand = hand.to_vec();
-        let same_tile = MahjongTile {
-            value: tile.value,
-            suit: tile.suit,
-            is_dora: false,
-        };
-
-        if waits.contains(&same_tile) {
-            continue;
-        }
-
-        temp_hand.push(same_tile);
-        if is_complete(&temp_hand) {
-            waits.push(same_tile);
-        }
-    }
+    let waits = waits(&to_counts(hand));
     (!waits.is_empty(), waits)
 }
 
+/// Tiles left over once the melds and up to two pairs are taken out, the candidates to
+/// throw.
 pub fn get_partial_completion(hand: &[MahjongTile]) -> Vec<MahjongTile> {
-    let mut first_copy = hand.to_vec();
-    let mut partial_hand = hand.to_vec();
-
-    first_copy.sort();
-
-    let (melds, mut pairs) = find_pairs_melds(&first_copy);
-    pairs.extend(melds.clone());
-    let n_melds = pairs.len();
-
-    for meld1 in &melds {
-        let mut second_copy = first_copy.to_vec();
-
-        for tile in meld1 {
-            if let Some(tilepos) = second_copy.iter().position(|x| x == tile) {
-                second_copy.remove(tilepos);
-            }
-        }
-
-        for start_index in 0..n_melds {
-            let mut third_copy = second_copy.to_vec();
-            let mut pair_counter = 0;
-
-            for meld_index in 0..n_melds {
-                let meld2 = &pairs[(start_index + meld_index) % n_melds];
-                if meld2.len() == 2 {
-                    pair_counter += 1;
-                }
-                if is_subset(&third_copy, meld2) {
-                    for tile in meld2 {
-                        if let Some(tilepos) = third_copy.iter().position(|x| x == tile) {
-                            third_copy.remove(tilepos);
-                        }
-                    }
-                } else {
-                    continue;
-                }
-            }
-            if third_copy.len() < partial_hand.len() && pair_counter <= 2 {
-                partial_hand = third_copy;
-            }
-        }
-    }
-    partial_hand
+    from_counts(&leftover_tiles(&to_counts(hand), 2))
 }
 
+/// Whether the tiles form four melds and a pair, or seven pairs.
 pub fn is_complete(hand: &[MahjongTile]) -> bool {
-    let mut first_copy = hand.to_vec();
-    first_copy.sort();
-
-    let (melds, mut pairs) = find_pairs_melds(&first_copy);
-
-    if melds.len() < 2 {
-        //gotta be careful, since ryanpeikou has the same shape
-        let mut second_copy = first_copy.to_vec();
-        for pair in &pairs {
-            for tile in pair {
-                if let Some(tilepos) = second_copy.iter().position(|x| x == tile) {
-                    second_copy.remove(tilepos);
-                }
-            }
-        }
-        return second_copy.is_empty();
-    }
-
-    pairs.extend(melds.clone());
-    let n_melds = pairs.len();
-
-    for meld1 in &melds {
-        let mut second_copy = first_copy.to_vec();
-        for tile in meld1 {
-            if let Some(tilepos) = second_copy.iter().position(|x| x == tile) {
-                second_copy.remove(tilepos);
-            }
-        }
-        for start_index in 0..n_melds {
-            let mut pair_counter = 0;
-            let mut third_copy = second_copy.to_vec();
-            for meld_index in 0..n_melds {
-                let meld2 = &pairs[(start_index + meld_index) % n_melds];
-
-                if is_subset(&third_copy, meld2) {
-                    if meld2.len() == 2 {
-                        pair_counter += 1;
-                    }
-                    for tile in meld2 {
-                        if let Some(tilepos) = third_copy.iter().position(|x| x == tile) {
-                            third_copy.remove(tilepos);
-                        }
-                    }
-                } else {
-                    continue;
-                }
-            }
-            if third_copy.is_empty() && pair_counter == 1 {
-                return true;
-            }
-        }
-    }
-    false
+    is_agari(&to_counts(hand))
 }
 
-pub fn construct_unique_meld_set(hand: &[MahjongTile]) -> Vec<Vec<MahjongTile>> {
-    let mut first_copy = hand.to_vec();
-    first_copy.sort();
-
-    let (melds, mut pairs) = find_pairs_melds(&first_copy);
-
-    pairs.extend(melds.clone());
-    let n_melds = pairs.len();
-    let mut result_tensor = Vec::new();
-
-    for meld1 in &melds {
-        let mut second_copy = first_copy.to_vec();
-        for tile in meld1 {
-            if let Some(tilepos) = second_copy.iter().position(|x| x == tile) {
-                second_copy.remove(tilepos);
-            }
-        }
-
-        for start_index in 0..n_melds {
-            let mut pair_counter = 0;
-            let mut results = Vec::new();
-            results.push(meld1.clone());
-
-            let mut third_copy = second_copy.to_vec();
-            for meld_index in 0..n_melds {
-                let meld2 = &pairs[(start_index + meld_index) % n_melds];
-
-                if is_subset(&third_copy, meld2) {
-                    if meld2.len() == 2 {
-                        pair_counter += 1;
-                    }
-                    results.push(meld2.clone());
-
-                    for tile in meld2 {
-                        if let Some(tilepos) = third_copy.iter().position(|x| x == tile) {
-                            third_copy.remove(tilepos);
-                        }
-                    }
-                } else {
-                    continue;
-                }
-            }
-            if third_copy.is_empty() && pair_counter == 1 {
-                result_tensor.push(results);
-            }
-        }
-    }
-    let mut cleaned_tensor: Vec<Vec<_>> = result_tensor
+/// The melds and pair of a complete hand, in one reading of it, or `None` when the tiles
+/// aren't four melds and a pair.
+pub fn construct_unique_meld_set(hand: &[MahjongTile]) -> Option<Vec<Vec<MahjongTile>>> {
+    let decompositions = decompositions(&to_counts(hand));
+    let mut meld_set: Vec<Vec<MahjongTile>> = decompositions
+        .first()?
         .iter()
-        .map(|inner_vec| {
-            let mut cloned_vec = inner_vec.clone();
-            cloned_vec.sort();
-            cloned_vec
-        })
+        .map(|block| block.tiles())
         .collect();
-
-    cleaned_tensor.sort();
-    cleaned_tensor.dedup();
-    // if cleaned_tensor.len() != 1 {
-    //     println!("Omg you found a rare hand!!");
-    //     println!("Printing meldtensor:");
-    //     for meldlist in &cleaned_tensor {
-    //         println!("Melds:");
-    //         for meld in meldlist {
-    //             print_hand(&meld);
-    //         }
-    //     }
-    // }
-    if cleaned_tensor.is_empty() {
-        println!("Wtf there is no hand");
-        print_hand(hand);
-    }
-    cleaned_tensor[0].clone()
+    meld_set.sort();
+    Some(meld_set)
 }
 
+/// Takes the two copies of a called tile out of the hand.
 pub fn remove_pon_tiles(deck: &mut Vec<MahjongTile>, card_to_remove: &MahjongTile) {
     let mut tiles_removed = 0;
     let mut i = 0;
@@ -470,6 +236,7 @@ pub fn remove_pon_tiles(deck: &mut Vec<MahjongTile>, card_to_remove: &MahjongTil
     }
 }
 
+/// Whether `tile` completes a sequence with two tiles of the hand.
 pub fn can_chi(hand: &[MahjongTile], tile: &MahjongTile) -> bool {
     if tile.suit == Suit::Kaze || tile.suit == Suit::Sangen {
         return false;
@@ -490,52 +257,27 @@ pub fn can_chi(hand: &[MahjongTile], tile: &MahjongTile) -> bool {
     let mut values: Vec<u8> = values.into_iter().collect();
     values.sort();
 
-    for i in 0..(values.len() - 2) {
-        if values[i] + 1 == values[i + 1] && values[i + 1] + 1 == values[i + 2] {
-            return true;
-        }
-    }
-
-    false
+    // The run has to include the called tile
+    values
+        .windows(3)
+        .any(|run| run[0] + 1 == run[1] && run[1] + 1 == run[2] && run.contains(&tile.value))
 }
 
+/// Whether the hand holds two copies of `tile`.
 pub fn can_pon(hand: &[MahjongTile], tile: &MahjongTile) -> bool {
     hand.iter().filter(|&t| t == tile).count() >= 2
 }
 
+#[cfg(test)]
+use super::notation::parse_tiles;
+
 #[test]
-#[rustfmt::skip]
 fn test_find_pairs_melds() {
-    let mut input = vec![
-        MahjongTile { suit: Suit::Sangen, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 8, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 6, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 9, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 7, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 9, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 8, is_dora: false },   
-        MahjongTile { suit: Suit::Sangen, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Sangen, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 8, is_dora: false },
-    ];
-
+    let mut input = parse_tiles("7889m1269p38s667z").unwrap();
     let expected_output = vec![
-        vec![
-        MahjongTile { suit: Suit::Manzu, value: 7, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 8, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 9, is_dora: false },
-        ],
-        vec![
-        MahjongTile { suit: Suit::Manzu, value: 8, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 8, is_dora: false },
-        ],
-        vec![
-        MahjongTile { suit: Suit::Sangen, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Sangen, value: 2, is_dora: false },
-        ],
+        parse_tiles("789m").unwrap(),
+        parse_tiles("88m").unwrap(),
+        parse_tiles("66z").unwrap(),
     ];
 
     input.sort();
@@ -545,459 +287,143 @@ fn test_find_pairs_melds() {
     output.extend(test_meld2);
 
     assert_eq!(output, expected_output);
-
 }
 
 #[test]
-#[rustfmt::skip]
 fn test_shabo() {
-    let mut input = vec![
-        MahjongTile { suit: Suit::Pinzu, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 4, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 3, is_dora: false },   
-        MahjongTile { suit: Suit::Souzu, value: 6, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 6, is_dora: false },
-        MahjongTile { suit: Suit::Kaze, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Kaze, value: 1, is_dora: false },
-    ];
-
-    let expected_output = vec![
-        MahjongTile { suit: Suit::Souzu, value: 6, is_dora: false },
-        MahjongTile { suit: Suit::Kaze, value: 1, is_dora: false }
-        ];
-
+    let mut input = parse_tiles("122334p12366s11z").unwrap();
     input.sort();
     let (tenpai, waits) = check_tenpai(&input);
-
-    for tile in &waits {
-        println!("{:?}", tile);
-    }
-    assert_eq!(waits, expected_output);
-    assert_eq!(tenpai, true);
-
+    assert_eq!(waits, parse_tiles("6s1z").unwrap());
+    assert!(tenpai);
 }
 
 #[test]
-#[rustfmt::skip]
 fn test_tenpai() {
-    let mut input = vec![
-        MahjongTile { suit: Suit::Pinzu, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 5, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 6, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 7, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 4, is_dora: false },   
-        MahjongTile { suit: Suit::Souzu, value: 5, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 6, is_dora: false },
-        MahjongTile { suit: Suit::Kaze, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Kaze, value: 1, is_dora: false },
-    ];
-
-    let expected_output = vec![];
-
+    let mut input = parse_tiles("1223567p2456s11z").unwrap();
     input.sort();
     let (tenpai, waits) = check_tenpai(&input);
-
-    for tile in &waits {
-        println!("{:?}", tile);
-    }
-    assert_eq!(waits, expected_output);
-    assert_eq!(tenpai, false);
-
+    assert_eq!(waits, vec![]);
+    assert!(!tenpai);
 }
 
 #[test]
-#[rustfmt::skip]
 fn test_false_shabo() {
-    let mut input = vec![
-        MahjongTile { suit: Suit::Pinzu, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 4, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 4, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 7, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 1, is_dora: false },   
-        MahjongTile { suit: Suit::Manzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Kaze, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Kaze, value: 1, is_dora: false },
-    ];
-
-    let expected_output = vec![];
-
+    let mut input = parse_tiles("123m12233447p11z").unwrap();
     input.sort();
     let (tenpai, waits) = check_tenpai(&input);
-
-    for tile in &waits {
-        println!("{:?}", tile);
-    }
-    assert_eq!(waits, expected_output);
-    assert_eq!(tenpai, false);
-
+    assert_eq!(waits, vec![]);
+    assert!(!tenpai);
 }
 
 #[test]
-#[rustfmt::skip]
 fn test_kanchan() {
-    let mut input = vec![
-        MahjongTile { suit: Suit::Pinzu, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 4, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 5, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 6, is_dora: false },   
-        MahjongTile { suit: Suit::Souzu, value: 6, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 6, is_dora: false },
-        MahjongTile { suit: Suit::Kaze, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Kaze, value: 1, is_dora: false },
-    ];
-
-    let expected_output = vec![
-        MahjongTile { suit: Suit::Souzu, value: 2, is_dora: false },
-        ];
-
+    let mut input = parse_tiles("123345p13666s11z").unwrap();
     input.sort();
     let (tenpai, waits) = check_tenpai(&input);
-
-    for tile in &waits {
-        println!("{:?}", tile);
-    }
-    assert_eq!(waits, expected_output);
-    assert_eq!(tenpai, true);
-
+    assert_eq!(waits, parse_tiles("2s").unwrap());
+    assert!(tenpai);
 }
+
 #[test]
-#[rustfmt::skip]
 fn test_can_chi() {
-    let mut hand = vec![
-        MahjongTile { suit: Suit::Sangen, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 8, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 6, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 9, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 7, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 8, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 8, is_dora: false },   
-        MahjongTile { suit: Suit::Sangen, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Sangen, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 8, is_dora: false },
-    ];
-
-    let mut tile1 = MahjongTile { suit: Suit::Pinzu, value: 3, is_dora: false };
-    let mut tile2 = MahjongTile { suit: Suit::Sangen, value: 1, is_dora: false };
-    let mut tile3 = MahjongTile { suit: Suit::Manzu, value: 7, is_dora: false };
-    let mut tile4 = MahjongTile { suit: Suit::Pinzu, value: 7, is_dora: false };
-
+    let mut hand = parse_tiles("7889m1268p38s667z").unwrap();
     hand.sort();
+    let tile = |notation: &str| notation.parse::<MahjongTile>().unwrap();
 
-    assert_eq!(can_chi(&hand, &tile1), true);
-    assert_eq!(can_chi(&hand, &tile2), false);
-    assert_eq!(can_chi(&hand, &tile3), true);
-    assert_eq!(can_chi(&hand, &tile4), true);
-
+    assert!(can_chi(&hand, &tile("3p")));
+    assert!(!can_chi(&hand, &tile("5z")));
+    assert!(can_chi(&hand, &tile("7m")));
+    assert!(can_chi(&hand, &tile("7p")));
 }
 
 #[test]
-#[rustfmt::skip]
 fn test_can_pon() {
-    let mut hand = vec![
-        MahjongTile { suit: Suit::Sangen, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 8, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 6, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 9, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 7, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 9, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 8, is_dora: false },   
-        MahjongTile { suit: Suit::Sangen, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Sangen, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 8, is_dora: false },
-    ];
-
-    let mut tile1 = MahjongTile { suit: Suit::Sangen, value: 2, is_dora: false };
-    let mut tile2 = MahjongTile { suit: Suit::Manzu, value: 8, is_dora: false };
-    let mut tile3 = MahjongTile { suit: Suit::Souzu, value: 3, is_dora: false };
-
+    let mut hand = parse_tiles("7889m1269p38s667z").unwrap();
     hand.sort();
+    let tile = |notation: &str| notation.parse::<MahjongTile>().unwrap();
 
-    assert_eq!(can_pon(&hand, &tile1), true);
-    assert_eq!(can_pon(&hand, &tile2), true);
-    assert_eq!(can_pon(&hand, &tile3), false);
-
+    assert!(can_pon(&hand, &tile("6z")));
+    assert!(can_pon(&hand, &tile("8m")));
+    assert!(!can_pon(&hand, &tile("3s")));
 }
 
 #[test]
-#[rustfmt::skip]
 fn test_super_tenpai() {
-
-    let mut hand = vec![
-        MahjongTile { suit: Suit::Pinzu, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 4, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 5, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 3, is_dora: false },   
-        MahjongTile { suit: Suit::Souzu, value: 6, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 6, is_dora: false },
-        MahjongTile { suit: Suit::Kaze, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Kaze, value: 1, is_dora: false },
-    ];
-
-    let expected_output = vec![
-        MahjongTile { suit: Suit::Souzu, value: 6, is_dora: false },
-        MahjongTile { suit: Suit::Kaze, value: 1, is_dora: false },
-
-        ];
-
+    let mut hand = parse_tiles("123345p12366s11z").unwrap();
     hand.sort();
-    let (tenpai1, waits2) = check_tenpai(&hand);
-    print_hand(&hand);
-    print_hand(&waits2);
-    assert_eq!(waits2, expected_output);
-    assert_eq!(tenpai1, true);
-
+    let (tenpai, waits) = check_tenpai(&hand);
+    assert_eq!(waits, parse_tiles("6s1z").unwrap());
+    assert!(tenpai);
 }
 
 #[test]
-#[rustfmt::skip]
 fn test_super_tenpai_sanmen() {
-
-    let mut hand = vec![
-        MahjongTile { suit: Suit::Pinzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 4, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 5, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 6, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 6, is_dora: false },   
-        MahjongTile { suit: Suit::Souzu, value: 6, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 6, is_dora: false },
-        MahjongTile { suit: Suit::Kaze, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Kaze, value: 1, is_dora: false },
-    ];
-
-    let expected_output = vec![
-        MahjongTile { suit: Suit::Pinzu, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 4, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 7, is_dora: false },
-
-        ];
-
+    let mut hand = parse_tiles("23456p123666s11z").unwrap();
     hand.sort();
-    let (tenpai1, waits2) = check_tenpai(&hand);
-    print_hand(&hand);
-    print_hand(&waits2);
-    assert_eq!(waits2, expected_output);
-    assert_eq!(tenpai1, true);
-
+    let (tenpai, waits) = check_tenpai(&hand);
+    assert_eq!(waits, parse_tiles("147p").unwrap());
+    assert!(tenpai);
 }
 
 #[test]
-#[rustfmt::skip]
 fn test_super_tenpai_happoubijin() {
-    let mut hand = vec![
-        MahjongTile { suit: Suit::Souzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 4, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 5, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 6, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 7, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 7, is_dora: false },   
-        MahjongTile { suit: Suit::Souzu, value: 7, is_dora: false },
-        MahjongTile { suit: Suit::Kaze, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Kaze, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Kaze, value: 1, is_dora: false },
-    ];
-
-    let expected_output = vec![
-        MahjongTile { suit: Suit::Souzu, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 4, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 5, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 6, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 7, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 8, is_dora: false },
-        ];
-
+    let mut hand = parse_tiles("2223456777s111z").unwrap();
     hand.sort();
-    let (tenpai1, waits2) = check_tenpai(&hand);
-    print_hand(&hand);
-    print_hand(&waits2);
-    assert_eq!(waits2, expected_output);
-    assert_eq!(tenpai1, true);
-
+    let (tenpai, waits) = check_tenpai(&hand);
+    assert_eq!(waits, parse_tiles("12345678s").unwrap());
+    assert!(tenpai);
 }
+
 #[test]
-#[rustfmt::skip]
 fn chiitoi_completion() {
-
-    let mut hand = vec![
-        MahjongTile { suit: Suit::Souzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 4, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 4, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 8, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 8, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 9, is_dora: false },   
-        MahjongTile { suit: Suit::Pinzu, value: 9, is_dora: false },
-        MahjongTile { suit: Suit::Sangen, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Sangen, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Kaze, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Kaze, value: 1, is_dora: false },
-    ];
-    print_hand(&hand);
+    let mut hand = parse_tiles("99p22334488s1166z").unwrap();
     hand.sort();
-    let complete = is_complete(&hand);
-    assert_eq!(complete, true);
-
+    assert!(is_complete(&hand));
 }
+
 #[test]
-#[rustfmt::skip]
 fn pair_mix_completion() {
-        //1112344m33p22345s
-    let mut hand = vec![
-        MahjongTile { suit: Suit::Manzu, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 4, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 4, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 3, is_dora: false },   
-        MahjongTile { suit: Suit::Souzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 4, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 5, is_dora: false },
-    ];
-    print_hand(&hand);
+    let mut hand = parse_tiles("1112344m33p22345s").unwrap();
     hand.sort();
-    let complete = is_complete(&hand);
-    assert_eq!(complete, false);
-
+    assert!(!is_complete(&hand));
 }
+
 #[test]
-#[rustfmt::skip]
 fn test_nobetan() {
-        //1112344m33p22345s
-    let mut hand = vec![
-        MahjongTile { suit: Suit::Manzu, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 4, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 4, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 5, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 2, is_dora: false },   
-        MahjongTile { suit: Suit::Souzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 4, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 5, is_dora: false },
-    ];
-    print_hand(&hand);
+    let mut hand = parse_tiles("1234m345p222345s").unwrap();
     hand.sort();
-
-
-    let expected_output = vec![
-        MahjongTile { suit: Suit::Manzu, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 4, is_dora: false },
-        ];
-
-
     let (_, waits) = check_tenpai(&hand);
-    assert_eq!(waits, expected_output);
-
+    assert_eq!(waits, parse_tiles("14m").unwrap());
 }
 
 #[test]
-#[rustfmt::skip]
 fn test_hand_construction() {
-    let mut hand = vec![
-        MahjongTile { suit: Suit::Manzu, value: 4, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 2, is_dora: false },   
-        MahjongTile { suit: Suit::Souzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 4, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 5, is_dora: false },
-    ];
-    print_hand(&hand);
+    let mut hand = parse_tiles("11222234m222345s").unwrap();
     hand.sort();
 
-    let hand_melds = construct_unique_meld_set(&hand);
-    for meld in hand_melds {
-        print_hand(&meld);
-}
-    let expected_output = vec![
-        MahjongTile { suit: Suit::Manzu, value: 1, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 4, is_dora: false },
-        ];
-
+    let hand_melds = construct_unique_meld_set(&hand).unwrap();
+    let expected_output: Vec<Vec<MahjongTile>> = ["11m", "222m", "234m", "222s", "345s"]
+        .iter()
+        .map(|meld| parse_tiles(meld).unwrap())
+        .collect();
+    assert_eq!(hand_melds, expected_output);
 }
 
 #[test]
-#[rustfmt::skip]
 fn test_hand_iipeikou_const() {
-    let mut hand = vec![
-        MahjongTile { suit: Suit::Pinzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 4, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 4, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 5, is_dora: false },
-        MahjongTile { suit: Suit::Pinzu, value: 5, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 2, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 3, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 4, is_dora: false },   
-        MahjongTile { suit: Suit::Souzu, value: 5, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 6, is_dora: false },
-        MahjongTile { suit: Suit::Souzu, value: 7, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 9, is_dora: false },
-        MahjongTile { suit: Suit::Manzu, value: 9, is_dora: false },
-    ];
-    print_hand(&hand);
+    let mut hand = parse_tiles("23499m334455p567s").unwrap();
     hand.sort();
 
-    let hand_melds = construct_unique_meld_set(&hand);
-    for meld in hand_melds {
-        print_hand(&meld);
+    let hand_melds = construct_unique_meld_set(&hand).unwrap();
+    let twice = parse_tiles("345p").unwrap();
+    assert_eq!(hand_melds.iter().filter(|meld| **meld == twice).count(), 2);
 }
 
-
+#[test]
+fn test_chuuren_waits() {
+    let hand = parse_tiles("1112345678999m").unwrap();
+    let (tenpai, waits) = check_tenpai(&hand);
+    assert!(tenpai);
+    assert_eq!(waits, parse_tiles("123456789m").unwrap());
 }
@@ -1,6 +1,16 @@
 pub mod mahjong_tile;
+pub mod notation;
+pub mod suit_table;
+pub mod tile_counts;
+pub mod wait;
+use crate::stats::PlayerStats;
 use mahjong_tile::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Arc;
 
+/// Which wind a player sits as this round; East deals.
 #[derive(Debug, Clone, PartialEq)]
 pub enum SeatWind {
     East,
@@ -9,45 +19,55 @@ pub enum SeatWind {
     North,
 }
 
-#[derive(Debug, Clone)]
+impl FromStr for SeatWind {
+    type Err = String;
+
+    /// Reads `east`, `south`, `west` or `north`, or their first letter, in any case.
+    fn from_str(name: &str) -> Result<SeatWind, String> {
+        match name.to_lowercase().as_str() {
+            "east" | "e" => Ok(SeatWind::East),
+            "south" | "s" => Ok(SeatWind::South),
+            "west" | "w" => Ok(SeatWind::West),
+            "north" | "n" => Ok(SeatWind::North),
+            _ => Err(format!("unknown seat wind '{}'", name)),
+        }
+    }
+}
+
+/// A seat at the table and the strategy playing it. `id` counts from 1 in the original
+/// seating.
+#[derive(Clone)]
 pub struct Player {
     pub points: i32,
     pub seat_wind: SeatWind,
-    pub strategy: Strategy,
+    pub strategy: Arc<dyn Strategy>,
     pub id: usize,
 }
-
-impl Default for Player {
-    fn default() -> Player {
-        Player {
-            points: 25000,
-            seat_wind: SeatWind::East,
-            strategy: Strategy::default(),
-            id: 0,
-        }
-    }
-}
 impl PartialEq for Player {
     fn eq(&self, other: &Self) -> bool {
         self.id == other.id
     }
 }
 impl Player {
+    /// Passes the deal on: whoever was South, next in turn after the dealer, becomes East.
     pub fn next_wind(&mut self) {
         self.seat_wind = match self.seat_wind {
-            SeatWind::East => SeatWind::South,
-            SeatWind::South => SeatWind::West,
-            SeatWind::West => SeatWind::North,
-            SeatWind::North => SeatWind::East,
+            SeatWind::East => SeatWind::North,
+            SeatWind::South => SeatWind::East,
+            SeatWind::West => SeatWind::South,
+            SeatWind::North => SeatWind::West,
         };
     }
 }
 
+/// Every seat's closed hand, called tiles and discards, indexed by seat.
 #[derive(Debug, Clone)]
 pub struct PlayerTiles {
     pub hand: Vec<Vec<MahjongTile>>,
     pub open_hand: Vec<Vec<MahjongTile>>,
     pub discards: Vec<Vec<MahjongTile>>,
+    /// Indices into each player's discards of the tiles another player called
+    pub called: Vec<Vec<usize>>,
 }
 
 impl Default for PlayerTiles {
@@ -55,73 +75,94 @@ impl Default for PlayerTiles {
         let hand = vec![vec![]; 4];
         let open_hand = vec![vec![]; 4];
         let discards = vec![vec![]; 4];
+        let called = vec![vec![]; 4];
         Self {
             hand,
             open_hand,
             discards,
+            called,
         }
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct Strategy {
-    pub call_chi: fn(StrategyInput) -> bool,
-    pub call_pon: fn(StrategyInput) -> bool,
-    pub discard: fn(StrategyInput) -> usize,
-    pub tsumo: fn(StrategyInput) -> bool,
-    pub kan: fn(StrategyInput) -> bool,
-    pub riichi: fn(StrategyInput) -> bool,
-}
-
-impl Strategy {
-    fn new(
-        call_chi: fn(StrategyInput) -> bool,
-        call_pon: fn(StrategyInput) -> bool,
-        discard: fn(StrategyInput) -> usize,
-        tsumo: fn(StrategyInput) -> bool,
-        kan: fn(StrategyInput) -> bool,
-        riichi: fn(StrategyInput) -> bool,
-    ) -> Strategy {
-        Strategy {
-            call_chi,
-            call_pon,
-            discard,
-            tsumo,
-            kan,
-            riichi,
-        }
+/// Every choice a player makes in a game. Only the discard has to be decided; by default a
+/// strategy wins, declares riichi and kans whenever it can, and never calls a discard. A
+/// closure from the input to the index of the tile to discard is a strategy too.
+pub trait Strategy: Send + Sync {
+    /// Index into `input.hand` of the tile to throw.
+    fn discard(&self, input: &StrategyInput) -> usize;
+
+    fn tsumo(&self, _input: &StrategyInput) -> bool {
+        true
     }
-}
 
-impl Default for Strategy {
-    fn default() -> Strategy {
-        Strategy {
-            call_chi: default_boolean_strategy,
-            call_pon: default_boolean_strategy,
-            discard: default_discard_strategy,
-            tsumo: default_boolean_strategy,
-            kan: default_boolean_strategy,
-            riichi: default_boolean_strategy,
-        }
+    fn call_pon(&self, _input: &StrategyInput) -> bool {
+        false
+    }
+
+    fn call_chi(&self, _input: &StrategyInput) -> bool {
+        false
     }
-}
 
-fn default_discard_strategy(_strategy_input: StrategyInput) -> usize {
-    0
+    fn kan(&self, _input: &StrategyInput) -> bool {
+        true
+    }
+
+    fn riichi(&self, _input: &StrategyInput) -> bool {
+        true
+    }
+
+    /// Tuning values handed to the strategy with every input.
+    fn params(&self) -> StrategyParams {
+        StrategyParams::default()
+    }
 }
 
-fn default_boolean_strategy(_strategy_input: StrategyInput) -> bool {
-    true
+impl<F> Strategy for F
+where
+    F: Fn(&StrategyInput) -> usize + Send + Sync,
+{
+    fn discard(&self, input: &StrategyInput) -> usize {
+        self(input)
+    }
 }
 
+/// Named tuning values a strategy reads from its input, e.g. how much a dora is worth.
+pub type StrategyParams = Arc<BTreeMap<String, f64>>;
+
+/// What a strategy sees when it decides.
 #[derive(Debug, Clone)]
 pub struct StrategyInput {
     pub hand: Vec<MahjongTile>,
     pub discards: Vec<Vec<MahjongTile>>,
     pub seat_wind: SeatWind,
     pub round_number: u8,
+    pub params: StrategyParams,
 }
 
+impl StrategyInput {
+    /// The strategy's value for `name`, or `default` when it has none.
+    pub fn param(&self, name: &str, default: f64) -> f64 {
+        self.params.get(name).copied().unwrap_or(default)
+    }
+}
+
+/// Closed tiles, the last one just drawn or won on, and called melds. Parses from and
+/// prints as MPSZ notation with melds in brackets, e.g. `123m0p[555p]`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Hand {
+    pub closed: Vec<MahjongTile>,
+    pub called: Vec<Vec<MahjongTile>>,
+}
+
+impl Hand {
+    /// Tiles held, counting each call as three so a kan doesn't add one.
+    pub fn tile_count(&self) -> usize {
+        self.closed.len() + 3 * self.called.len()
+    }
+}
+
+/// The walls of a round and how many dora indicators are turned.
 #[derive(Debug, Clone)]
 pub struct BoardTiles {
     pub wall: Vec<MahjongTile>,
@@ -130,9 +171,79 @@ pub struct BoardTiles {
     pub dora_index: usize,
 }
 
+/// A yaku, or dora, and the han it is worth.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Yaku {
+    pub name: String,
+    pub han: i32,
+}
+
+/// How a winning hand was scored. Dora count as yaku here, the way score sheets list them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HandScore {
+    pub han: i32,
+    pub fu: i32,
+    pub yaku: Vec<Yaku>,
+    pub base_points: i32,
+}
+
+/// Final scores of a game, uma included.
+#[derive(Debug, Clone)]
 pub struct GameResult {
     pub player_1_score: i32,
     pub player_2_score: i32,
     pub player_3_score: i32,
     pub player_4_score: i32,
+    /// Seed the game was played from, enough to play it again
+    pub seed: u64,
+    /// Seat each player sat in, 0 for the first dealer, in the order of the scores
+    pub seats: Vec<usize>,
+    /// Each player's statistics in the order of the scores, empty unless the game was logged
+    pub stats: Vec<PlayerStats>,
+}
+
+/// The rules a game is played under.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ruleset {
+    pub name: String,
+    pub rounds: u8,
+    pub uma: bool,
+    pub tobi: bool,
+    pub starting_points: i32,
+}
+
+impl Default for Ruleset {
+    fn default() -> Ruleset {
+        Ruleset::hanchan()
+    }
+}
+
+impl Ruleset {
+    /// East and south rounds with uma, played to the end even below zero points.
+    pub fn hanchan() -> Ruleset {
+        Ruleset {
+            name: "hanchan".to_string(),
+            rounds: 4 * 2,
+            uma: true,
+            tobi: false,
+            starting_points: 25000,
+        }
+    }
+
+    /// East round only.
+    pub fn tonpuusen() -> Ruleset {
+        Ruleset {
+            name: "tonpuusen".to_string(),
+            rounds: 4,
+            ..Ruleset::hanchan()
+        }
+    }
+
+    pub fn by_name(name: &str) -> Option<Ruleset> {
+        match name {
+            "hanchan" => Some(Ruleset::hanchan()),
+            "tonpuusen" => Some(Ruleset::tonpuusen()),
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,283 @@
+use super::mahjong_tile::*;
+use super::Hand;
+use std::fmt;
+use std::str::FromStr;
+
+// Standard MPSZ notation: digits followed by their suit, `0` for a red five, winds as
+// 1-4z and dragons as 5-7z, called melds in brackets, e.g. `123m0p[555p]`.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotationError {
+    Empty,
+    MissingSuit(String),
+    UnexpectedCharacter(char),
+    InvalidTile(char, char),
+    TooManyCopies(MahjongTile),
+    UnclosedMeld,
+    InvalidMeld(String),
+    NotSingleTile(String),
+}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NotationError::Empty => write!(f, "no tiles given"),
+            NotationError::MissingSuit(digits) => write!(f, "digits '{}' have no suit", digits),
+            NotationError::UnexpectedCharacter(c) => write!(f, "unexpected character '{}'", c),
+            NotationError::InvalidTile(value, suit) => {
+                write!(f, "'{}{}' is not a tile", value, suit)
+            }
+            NotationError::TooManyCopies(tile) => write!(f, "more than four copies of {}", tile),
+            NotationError::UnclosedMeld => write!(f, "called meld is missing its ']'"),
+            NotationError::InvalidMeld(meld) => write!(f, "'[{}]' is not a meld", meld),
+            NotationError::NotSingleTile(notation) => {
+                write!(f, "'{}' is not a single tile", notation)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NotationError {}
+
+fn suit_char(suit: Suit) -> char {
+    match suit {
+        Suit::Manzu => 'm',
+        Suit::Pinzu => 'p',
+        Suit::Souzu => 's',
+        Suit::Kaze | Suit::Sangen => 'z',
+    }
+}
+
+fn value_char(tile: &MahjongTile) -> char {
+    let value = match tile.suit {
+        _ if tile.is_red => 0,
+        Suit::Sangen => tile.value + 4,
+        _ => tile.value,
+    };
+    (b'0' + value) as char
+}
+
+fn parse_tile(value: char, suit: char) -> Result<MahjongTile, NotationError> {
+    let invalid = NotationError::InvalidTile(value, suit);
+    let digit = value.to_digit(10).ok_or(invalid.clone())? as u8;
+    let (suit, value) = match (suit, digit) {
+        ('m', 1..=9) => (Suit::Manzu, digit),
+        ('p', 1..=9) => (Suit::Pinzu, digit),
+        ('s', 1..=9) => (Suit::Souzu, digit),
+        ('m', 0) | ('p', 0) | ('s', 0) => {
+            return parse_tile('5', suit).map(|tile| MahjongTile {
+                is_red: true,
+                ..tile
+            });
+        }
+        ('z', 1..=4) => (Suit::Kaze, digit),
+        ('z', 5..=7) => (Suit::Sangen, digit - 4),
+        _ => return Err(invalid),
+    };
+    Ok(MahjongTile {
+        suit,
+        value,
+        is_dora: false,
+        is_red: false,
+    })
+}
+
+/// Parses tiles without called melds, e.g. `1112344m33p22345s`.
+pub fn parse_tiles(notation: &str) -> Result<Vec<MahjongTile>, NotationError> {
+    let mut tiles = Vec::new();
+    let mut digits = String::new();
+    for c in notation.chars().filter(|c| !c.is_whitespace()) {
+        match c {
+            '0'..='9' => digits.push(c),
+            'm' | 'p' | 's' | 'z' => {
+                if digits.is_empty() {
+                    return Err(NotationError::UnexpectedCharacter(c));
+                }
+                for value in digits.drain(..) {
+                    tiles.push(parse_tile(value, c)?);
+                }
+            }
+            _ => return Err(NotationError::UnexpectedCharacter(c)),
+        }
+    }
+    if !digits.is_empty() {
+        return Err(NotationError::MissingSuit(digits));
+    }
+    Ok(tiles)
+}
+
+/// Writes tiles in the order given, naming the suit once per run of the same suit.
+pub fn format_tiles(tiles: &[MahjongTile]) -> String {
+    let mut result = String::new();
+    for (index, tile) in tiles.iter().enumerate() {
+        result.push(value_char(tile));
+        let suit = suit_char(tile.suit);
+        if tiles.get(index + 1).map(|next| suit_char(next.suit)) != Some(suit) {
+            result.push(suit);
+        }
+    }
+    result
+}
+
+fn is_meld(tiles: &[MahjongTile]) -> bool {
+    match tiles.len() {
+        3 | 4 if tiles.iter().all(|tile| tile == &tiles[0]) => true,
+        3 => {
+            let mut sorted = tiles.to_vec();
+            sorted.sort();
+            sorted[0].suit != Suit::Kaze
+                && sorted[0].suit != Suit::Sangen
+                && sorted
+                    .windows(2)
+                    .all(|w| w[0].suit == w[1].suit && w[0].value + 1 == w[1].value)
+        }
+        _ => false,
+    }
+}
+
+fn check_copies(hand: &Hand) -> Result<(), NotationError> {
+    let all_tiles: Vec<&MahjongTile> = hand
+        .closed
+        .iter()
+        .chain(hand.called.iter().flatten())
+        .collect();
+    for tile in &all_tiles {
+        if all_tiles.iter().filter(|other| other == &tile).count() > 4 {
+            return Err(NotationError::TooManyCopies(**tile));
+        }
+    }
+    Ok(())
+}
+
+impl FromStr for Hand {
+    type Err = NotationError;
+
+    fn from_str(notation: &str) -> Result<Hand, NotationError> {
+        let mut hand = Hand::default();
+        let mut rest = notation;
+        while let Some(open) = rest.find('[') {
+            hand.closed.extend(parse_tiles(&rest[..open])?);
+            let close = rest[open..].find(']').ok_or(NotationError::UnclosedMeld)? + open;
+            let inner = &rest[open + 1..close];
+            if inner.contains('[') {
+                return Err(NotationError::UnclosedMeld);
+            }
+            let meld = parse_tiles(inner)?;
+            if !is_meld(&meld) {
+                return Err(NotationError::InvalidMeld(inner.to_string()));
+            }
+            hand.called.push(meld);
+            rest = &rest[close + 1..];
+        }
+        hand.closed.extend(parse_tiles(rest)?);
+        if hand.closed.is_empty() && hand.called.is_empty() {
+            return Err(NotationError::Empty);
+        }
+        check_copies(&hand)?;
+        Ok(hand)
+    }
+}
+
+impl fmt::Display for Hand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", format_tiles(&self.closed))?;
+        for meld in &self.called {
+            write!(f, "[{}]", format_tiles(meld))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for MahjongTile {
+    type Err = NotationError;
+
+    fn from_str(notation: &str) -> Result<MahjongTile, NotationError> {
+        match parse_tiles(notation)?.as_slice() {
+            [tile] => Ok(*tile),
+            _ => Err(NotationError::NotSingleTile(notation.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for MahjongTile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", value_char(self), suit_char(self.suit))
+    }
+}
+
+#[test]
+#[rustfmt::skip]
+fn test_parse_tiles() {
+    let tiles = parse_tiles("1112344m33p22345s").unwrap();
+    let expected_output = vec![
+        MahjongTile { suit: Suit::Manzu, value: 1, is_dora: false, is_red: false },
+        MahjongTile { suit: Suit::Manzu, value: 1, is_dora: false, is_red: false },
+        MahjongTile { suit: Suit::Manzu, value: 1, is_dora: false, is_red: false },
+        MahjongTile { suit: Suit::Manzu, value: 2, is_dora: false, is_red: false },
+        MahjongTile { suit: Suit::Manzu, value: 3, is_dora: false, is_red: false },
+        MahjongTile { suit: Suit::Manzu, value: 4, is_dora: false, is_red: false },
+        MahjongTile { suit: Suit::Manzu, value: 4, is_dora: false, is_red: false },
+        MahjongTile { suit: Suit::Pinzu, value: 3, is_dora: false, is_red: false },
+        MahjongTile { suit: Suit::Pinzu, value: 3, is_dora: false, is_red: false },
+        MahjongTile { suit: Suit::Souzu, value: 2, is_dora: false, is_red: false },
+        MahjongTile { suit: Suit::Souzu, value: 2, is_dora: false, is_red: false },
+        MahjongTile { suit: Suit::Souzu, value: 3, is_dora: false, is_red: false },
+        MahjongTile { suit: Suit::Souzu, value: 4, is_dora: false, is_red: false },
+        MahjongTile { suit: Suit::Souzu, value: 5, is_dora: false, is_red: false },
+    ];
+    assert_eq!(tiles, expected_output);
+}
+
+#[test]
+fn test_honours_and_red_fives() {
+    let tiles = parse_tiles("0p1z5z7z").unwrap();
+    assert!(tiles[0].is_red && tiles[0].value == 5 && tiles[0].suit == Suit::Pinzu);
+    assert_eq!((tiles[1].suit, tiles[1].value), (Suit::Kaze, 1));
+    assert_eq!((tiles[2].suit, tiles[2].value), (Suit::Sangen, 1));
+    assert_eq!((tiles[3].suit, tiles[3].value), (Suit::Sangen, 3));
+    assert_eq!(format_tiles(&tiles), "0p157z");
+    assert_eq!("7z".parse::<MahjongTile>().unwrap(), tiles[3]);
+}
+
+#[test]
+fn test_hand_round_trip() {
+    for notation in ["123m406p789s11z[555p]", "1122z[1111m][789s]", "5m1z5m"] {
+        let hand: Hand = notation.parse().unwrap();
+        assert_eq!(hand.to_string(), notation);
+    }
+    let hand: Hand = "23m[555p][055s]".parse().unwrap();
+    assert_eq!(hand.closed.len(), 2);
+    assert_eq!(hand.called.len(), 2);
+    assert!(hand.called[1][0].is_red);
+}
+
+#[test]
+fn test_notation_errors() {
+    assert_eq!(
+        parse_tiles("123"),
+        Err(NotationError::MissingSuit("123".to_string()))
+    );
+    assert_eq!(parse_tiles("8z"), Err(NotationError::InvalidTile('8', 'z')));
+    assert_eq!(parse_tiles("0z"), Err(NotationError::InvalidTile('0', 'z')));
+    assert_eq!(
+        parse_tiles("12x"),
+        Err(NotationError::UnexpectedCharacter('x'))
+    );
+    assert_eq!("".parse::<Hand>(), Err(NotationError::Empty));
+    assert_eq!(
+        "123m[555p".parse::<Hand>(),
+        Err(NotationError::UnclosedMeld)
+    );
+    assert_eq!(
+        "123m[556p]".parse::<Hand>(),
+        Err(NotationError::InvalidMeld("556p".to_string()))
+    );
+    assert!(matches!(
+        "11111m".parse::<Hand>(),
+        Err(NotationError::TooManyCopies(_))
+    ));
+    assert!(matches!(
+        "12m".parse::<MahjongTile>(),
+        Err(NotationError::NotSingleTile(_))
+    ));
+}
@@ -0,0 +1,343 @@
+use super::tile_counts::*;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const MAX_SUIT_TILES: u32 = 14;
+const MAX_MELDS: usize = 4;
+const NONE: u8 = u8::MAX;
+const CACHE_MAGIC: &[u8; 4] = b"MJST";
+const CACHE_VERSION: u8 = 1;
+const NUMBER_STATES: usize = 5usize.pow(9);
+const HONOR_STATES: usize = 5usize.pow(7);
+
+// For one suit, the most partial shapes that fit next to `m` melds, first without the
+// pair (indices 0..=4) and then with it (5..=9). NONE marks an impossible meld count.
+type SuitEntry = [u8; 2 * (MAX_MELDS + 1)];
+
+static GLOBAL_TABLES: OnceLock<SuitTables> = OnceLock::new();
+
+/// How to compute agari and shanten: by searching the hand, or from precomputed per-suit
+/// tables, which is much faster once they are built.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Evaluator {
+    #[default]
+    Reference,
+    Table,
+}
+
+impl Evaluator {
+    pub fn is_agari(&self, counts: &TileCounts) -> bool {
+        match self {
+            Evaluator::Reference => is_agari(counts),
+            Evaluator::Table => SuitTables::global().is_agari(counts),
+        }
+    }
+
+    pub fn shanten(&self, counts: &TileCounts) -> i8 {
+        match self {
+            Evaluator::Reference => shanten(counts),
+            Evaluator::Table => SuitTables::global().shanten(counts),
+        }
+    }
+}
+
+/// Per-suit meld and partial-shape counts for every suit holding at most 14 tiles,
+/// indexed by the suit's counts read as a base-5 number.
+pub struct SuitTables {
+    number: Vec<SuitEntry>,
+    honor: Vec<SuitEntry>,
+}
+
+impl SuitTables {
+    pub fn generate() -> SuitTables {
+        SuitTables {
+            number: generate_suit(9, true),
+            honor: generate_suit(7, false),
+        }
+    }
+
+    /// Sets up the tables shared by the whole process from the disk cache at `path`, or
+    /// generates them and writes them there. The tables are set up even when writing them
+    /// fails; the error is why the next process has to generate them again. Does nothing
+    /// once the tables are in use.
+    pub fn init(path: &Path) -> io::Result<()> {
+        let mut saved = Ok(());
+        GLOBAL_TABLES.get_or_init(|| {
+            let (tables, result) = SuitTables::load_or_generate(path);
+            saved = result;
+            tables
+        });
+        saved
+    }
+
+    /// Tables shared by the whole process, set up by `init` or else on first use from the
+    /// cache at `cache_path`, which is written to if it can be. WebAssembly has no disk, so
+    /// there they are generated every time.
+    pub fn global() -> &'static SuitTables {
+        if cfg!(target_arch = "wasm32") {
+            return GLOBAL_TABLES.get_or_init(SuitTables::generate);
+        }
+        // Generating again next time is slow but harmless
+        GLOBAL_TABLES.get_or_init(|| SuitTables::load_or_generate(&SuitTables::cache_path()).0)
+    }
+
+    /// Where the tables are cached by default: `MAHJONG_TABLE_CACHE`, or a file in the
+    /// temporary directory.
+    pub fn cache_path() -> PathBuf {
+        match env::var_os("MAHJONG_TABLE_CACHE") {
+            Some(path) => PathBuf::from(path),
+            None => env::temp_dir().join(format!("mahjong-suit-tables-v{}.bin", CACHE_VERSION)),
+        }
+    }
+
+    /// Tables read from `path`, or generated and written there, with the error of writing
+    /// them if that failed.
+    pub fn load_or_generate(path: &Path) -> (SuitTables, io::Result<()>) {
+        if let Ok(tables) = SuitTables::load(path) {
+            return (tables, Ok(()));
+        }
+        let tables = SuitTables::generate();
+        let saved = tables.save(path);
+        (tables, saved)
+    }
+
+    pub fn load(path: &Path) -> io::Result<SuitTables> {
+        let bytes = fs::read(path)?;
+        let entry_size = std::mem::size_of::<SuitEntry>();
+        let expected_len = 5 + (NUMBER_STATES + HONOR_STATES) * entry_size;
+        if bytes.len() != expected_len || &bytes[..4] != CACHE_MAGIC || bytes[4] != CACHE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "suit table cache is stale or corrupt",
+            ));
+        }
+        let mut entries = bytes[5..].chunks_exact(entry_size).map(|chunk| {
+            let mut entry = [NONE; 2 * (MAX_MELDS + 1)];
+            entry.copy_from_slice(chunk);
+            entry
+        });
+        let number = entries.by_ref().take(NUMBER_STATES).collect();
+        let honor = entries.collect();
+        Ok(SuitTables { number, honor })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut bytes = Vec::with_capacity(5 + (self.number.len() + self.honor.len()) * 10);
+        bytes.extend_from_slice(CACHE_MAGIC);
+        bytes.push(CACHE_VERSION);
+        for entry in self.number.iter().chain(&self.honor) {
+            bytes.extend_from_slice(entry);
+        }
+        // Write next to the target first so a concurrent reader never sees half a file
+        let temporary = path.with_extension(format!("tmp{}", std::process::id()));
+        fs::write(&temporary, bytes)?;
+        fs::rename(&temporary, path)
+    }
+
+    pub fn is_agari(&self, counts: &TileCounts) -> bool {
+        tile_count(counts) % 3 == 2 && self.shanten(counts) == -1
+    }
+
+    pub fn standard_shanten(&self, counts: &TileCounts) -> i8 {
+        let melds_needed = melds_needed(counts) as usize;
+        let suits = [
+            &self.number[encode(&counts[0..9])],
+            &self.number[encode(&counts[9..18])],
+            &self.number[encode(&counts[18..27])],
+            &self.honor[encode(&counts[27..34])],
+        ];
+
+        let mut best = 0;
+        // Without a pair, then with the pair taken from each suit in turn
+        for pair_suit in [None, Some(0), Some(1), Some(2), Some(3)] {
+            let mut combined = [NONE; MAX_MELDS + 1];
+            combined[0] = 0;
+            for (suit, entry) in suits.iter().enumerate() {
+                let offset = if pair_suit == Some(suit) {
+                    MAX_MELDS + 1
+                } else {
+                    0
+                };
+                combined = combine(&combined, &entry[offset..offset + MAX_MELDS + 1]);
+            }
+            let pair = pair_suit.is_some() as usize;
+            for (melds, &partials) in combined.iter().enumerate().take(melds_needed + 1) {
+                if partials == NONE {
+                    continue;
+                }
+                let partials = (partials as usize).min(melds_needed - melds);
+                best = best.max(2 * melds + partials + pair);
+            }
+        }
+        2 * melds_needed as i8 - best as i8
+    }
+
+    pub fn shanten(&self, counts: &TileCounts) -> i8 {
+        let standard = self.standard_shanten(counts);
+        if melds_needed(counts) == 4 {
+            standard.min(chiitoitsu_shanten(counts))
+        } else {
+            standard
+        }
+    }
+}
+
+fn encode(suit: &[u8]) -> usize {
+    suit.iter()
+        .rev()
+        .fold(0, |code, &count| code * 5 + count as usize)
+}
+
+fn combine(left: &[u8], right: &[u8]) -> [u8; MAX_MELDS + 1] {
+    let mut result = [NONE; MAX_MELDS + 1];
+    for (left_melds, &left_partials) in left.iter().enumerate() {
+        if left_partials == NONE {
+            continue;
+        }
+        for (right_melds, &right_partials) in right.iter().enumerate() {
+            if right_partials == NONE || left_melds + right_melds > MAX_MELDS {
+                continue;
+            }
+            let slot = &mut result[left_melds + right_melds];
+            let partials = left_partials.saturating_add(right_partials);
+            if *slot == NONE || partials > *slot {
+                *slot = partials;
+            }
+        }
+    }
+    result
+}
+
+// Every entry only depends on suits with fewer tiles, and those have smaller codes, so one
+// ascending pass fills the table. The lowest held tile is isolated or starts a meld, a
+// partial shape or the pair.
+fn generate_suit(positions: u32, sequences: bool) -> Vec<SuitEntry> {
+    let states = 5usize.pow(positions);
+    let mut table = vec![[NONE; 2 * (MAX_MELDS + 1)]; states];
+    table[0][0] = 0;
+    let mut counts = [0u8; 9];
+    for code in 1..states {
+        let mut rest = code;
+        for count in counts.iter_mut().take(positions as usize) {
+            *count = (rest % 5) as u8;
+            rest /= 5;
+        }
+        if counts.iter().map(|&c| c as u32).sum::<u32>() > MAX_SUIT_TILES {
+            continue;
+        }
+        let index = counts.iter().position(|&c| c > 0).unwrap();
+        let place = 5usize.pow(index as u32);
+        let fits = |shape: &[u8]| {
+            index + shape.len() <= positions as usize
+                && shape
+                    .iter()
+                    .zip(&counts[index..])
+                    .all(|(needed, held)| held >= needed)
+        };
+        let without = |shape: &[u8]| {
+            code - shape
+                .iter()
+                .enumerate()
+                .map(|(offset, &needed)| needed as usize * place * 5usize.pow(offset as u32))
+                .sum::<usize>()
+        };
+
+        let mut entry = table[without(&[1])];
+        let mut merge = |from: usize, melds: usize, partials: u8, into_pair: bool| {
+            let source = table[from];
+            for pair in [false, true] {
+                let target_pair = pair || into_pair;
+                if into_pair && pair {
+                    continue;
+                }
+                for from_melds in 0..=MAX_MELDS {
+                    let from_partials = source[from_melds + pair as usize * (MAX_MELDS + 1)];
+                    if from_partials == NONE || from_melds + melds > MAX_MELDS {
+                        continue;
+                    }
+                    let slot =
+                        &mut entry[from_melds + melds + target_pair as usize * (MAX_MELDS + 1)];
+                    let partials = from_partials + partials;
+                    if *slot == NONE || partials > *slot {
+                        *slot = partials;
+                    }
+                }
+            }
+        };
+        if fits(&[3]) {
+            merge(without(&[3]), 1, 0, false);
+        }
+        if fits(&[2]) {
+            merge(without(&[2]), 0, 1, false);
+            merge(without(&[2]), 0, 0, true);
+        }
+        if sequences {
+            for shape in [&[1, 1, 1][..], &[1, 1], &[1, 0, 1]] {
+                if fits(shape) {
+                    let melds = (shape.len() == 3 && shape[1] == 1) as usize;
+                    merge(without(shape), melds, 1 - melds as u8, false);
+                }
+            }
+        }
+        table[code] = entry;
+    }
+    table
+}
+
+#[test]
+fn test_tables_match_reference() {
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+    let mut wall: Vec<usize> = (0..TILE_KINDS)
+        .flat_map(|index| std::iter::repeat_n(index, 4))
+        .collect();
+    // Every other hand is drawn from souzu only to exercise long single-suit shapes
+    let mut souzu_wall: Vec<usize> = (18..27)
+        .flat_map(|index| std::iter::repeat_n(index, 4))
+        .collect();
+    let mut rng = StdRng::seed_from_u64(27);
+    for round in 0..2000 {
+        let wall = if round % 2 == 0 {
+            &mut wall
+        } else {
+            &mut souzu_wall
+        };
+        wall.shuffle(&mut rng);
+        let hand_size = [14, 13, 11, 10, 8, 5, 2][round % 7];
+        let counts = wall[..hand_size]
+            .iter()
+            .fold([0; TILE_KINDS], |mut counts, &i| {
+                counts[i] += 1;
+                counts
+            });
+        for (table, reference) in [
+            (
+                Evaluator::Table.shanten(&counts),
+                Evaluator::Reference.shanten(&counts),
+            ),
+            (
+                Evaluator::Table.is_agari(&counts) as i8,
+                Evaluator::Reference.is_agari(&counts) as i8,
+            ),
+        ] {
+            assert_eq!(table, reference, "{:?}", counts);
+        }
+    }
+}
+
+#[test]
+fn test_table_cache_round_trip() {
+    let path = env::temp_dir().join(format!(
+        "mahjong-suit-tables-test-{}.bin",
+        std::process::id()
+    ));
+    let (generated, saved) = SuitTables::load_or_generate(&path);
+    saved.unwrap();
+    let loaded = SuitTables::load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(generated.number == loaded.number && generated.honor == loaded.honor);
+}
@@ -0,0 +1,555 @@
+use super::mahjong_tile::*;
+
+/// Different tiles in the game: three suits of nine, four winds and three dragons.
+pub const TILE_KINDS: usize = 34;
+
+/// Number of copies held of each of the 34 tile kinds, indexed by `tile_index`.
+/// Manzu occupy 0..9, pinzu 9..18, souzu 18..27, winds 27..31 and dragons 31..34.
+pub type TileCounts = [u8; TILE_KINDS];
+
+/// A group of tiles in one reading of a hand, by the index of its lowest tile.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Block {
+    Pair(usize),
+    Triplet(usize),
+    Sequence(usize), // index of the lowest tile
+}
+
+impl Block {
+    pub fn tiles(&self) -> Vec<MahjongTile> {
+        match *self {
+            Block::Pair(index) => vec![index_to_tile(index); 2],
+            Block::Triplet(index) => vec![index_to_tile(index); 3],
+            Block::Sequence(index) => (index..index + 3).map(index_to_tile).collect(),
+        }
+    }
+}
+
+/// Position of a tile kind in `TileCounts`.
+pub fn tile_index(tile: &MahjongTile) -> usize {
+    let value = tile.value as usize - 1;
+    match tile.suit {
+        Suit::Manzu => value,
+        Suit::Pinzu => 9 + value,
+        Suit::Souzu => 18 + value,
+        Suit::Kaze => 27 + value,
+        Suit::Sangen => 31 + value,
+    }
+}
+
+/// The plain tile of a kind, neither red nor dora.
+pub fn index_to_tile(index: usize) -> MahjongTile {
+    let (suit, value) = match index {
+        0..=8 => (Suit::Manzu, index),
+        9..=17 => (Suit::Pinzu, index - 9),
+        18..=26 => (Suit::Souzu, index - 18),
+        27..=30 => (Suit::Kaze, index - 27),
+        31..=33 => (Suit::Sangen, index - 31),
+        _ => panic!("Invalid tile index!"),
+    };
+    MahjongTile {
+        suit,
+        value: value as u8 + 1,
+        is_dora: false,
+        is_red: false,
+    }
+}
+
+/// How many of each kind the tiles hold.
+pub fn to_counts(tiles: &[MahjongTile]) -> TileCounts {
+    let mut counts = [0; TILE_KINDS];
+    for tile in tiles {
+        counts[tile_index(tile)] += 1;
+    }
+    counts
+}
+
+/// The tiles of the counts in kind order.
+pub fn from_counts(counts: &TileCounts) -> Vec<MahjongTile> {
+    let mut tiles = Vec::with_capacity(counts.iter().map(|&c| c as usize).sum());
+    for (index, &count) in counts.iter().enumerate() {
+        for _ in 0..count {
+            tiles.push(index_to_tile(index));
+        }
+    }
+    tiles
+}
+
+/// Total tiles in the counts.
+pub fn tile_count(counts: &TileCounts) -> usize {
+    counts.iter().map(|&c| c as usize).sum()
+}
+
+fn is_number_index(index: usize) -> bool {
+    index < 27
+}
+
+fn can_start_sequence(index: usize) -> bool {
+    is_number_index(index) && index % 9 <= 6
+}
+
+// A number suit splits into melds only if, from the lowest tile upward, the copies that
+// don't fit into a triplet start sequences.
+fn suit_is_melds(mut suit: [u8; 9]) -> bool {
+    for i in 0..9 {
+        let sequences = suit[i] % 3;
+        if sequences == 0 {
+            continue;
+        }
+        if i > 6 || suit[i + 1] < sequences || suit[i + 2] < sequences {
+            return false;
+        }
+        suit[i + 1] -= sequences;
+        suit[i + 2] -= sequences;
+    }
+    true
+}
+
+fn suit_is_melds_and_pair(suit: [u8; 9]) -> bool {
+    for i in 0..9 {
+        if suit[i] >= 2 {
+            let mut without_pair = suit;
+            without_pair[i] -= 2;
+            if suit_is_melds(without_pair) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Whether the counts form four melds and a pair, or as many melds as are left after
+/// calls.
+pub fn is_standard_agari(counts: &TileCounts) -> bool {
+    if tile_count(counts) % 3 != 2 {
+        return false;
+    }
+    let mut pair_found = false;
+    for suit_start in [0, 9, 18] {
+        let mut suit = [0; 9];
+        suit.copy_from_slice(&counts[suit_start..suit_start + 9]);
+        match suit.iter().sum::<u8>() % 3 {
+            0 => {
+                if !suit_is_melds(suit) {
+                    return false;
+                }
+            }
+            2 => {
+                if pair_found || !suit_is_melds_and_pair(suit) {
+                    return false;
+                }
+                pair_found = true;
+            }
+            _ => return false,
+        }
+    }
+    for &count in &counts[27..] {
+        match count {
+            0 | 3 => {}
+            2 if !pair_found => pair_found = true,
+            _ => return false,
+        }
+    }
+    pair_found
+}
+
+/// Whether the counts are seven different pairs.
+pub fn is_chiitoitsu_agari(counts: &TileCounts) -> bool {
+    counts.iter().all(|&c| c == 0 || c == 2) && counts.iter().filter(|&&c| c == 2).count() == 7
+}
+
+/// Whether the counts are a complete hand of either shape.
+pub fn is_agari(counts: &TileCounts) -> bool {
+    is_standard_agari(counts) || is_chiitoitsu_agari(counts)
+}
+
+/// Melds the closed part of a hand still has to form, so a hand with one call needs three.
+pub fn melds_needed(counts: &TileCounts) -> u8 {
+    (tile_count(counts) / 3) as u8
+}
+
+/// Shanten of a closed hand of 13 or 14 tiles playing for seven pairs.
+pub fn chiitoitsu_shanten(counts: &TileCounts) -> i8 {
+    let pairs = counts.iter().filter(|&&c| c >= 2).count() as i8;
+    let kinds = counts.iter().filter(|&&c| c > 0).count() as i8;
+    6 - pairs + (7 - kinds).max(0)
+}
+
+/// Shanten for four melds and a pair: `2 * melds_needed - 2m - t - p`, where `m` melds,
+/// `t` partial shapes and `p` pair are taken from the hand and `m + t` can't exceed the
+/// melds still needed.
+pub fn standard_shanten(counts: &TileCounts) -> i8 {
+    let mut search = ShantenSearch {
+        hand: *counts,
+        melds_needed: melds_needed(counts),
+        remaining: tile_count(counts) as u8,
+        best: 0,
+    };
+    search.run(0, 0, 0, false);
+    2 * search.melds_needed as i8 - search.best as i8
+}
+
+/// Tiles away from tenpai, the lower of the standard and seven pairs shanten. Tenpai is 0
+/// and a complete hand -1.
+pub fn shanten(counts: &TileCounts) -> i8 {
+    let standard = standard_shanten(counts);
+    if melds_needed(counts) == 4 {
+        standard.min(chiitoitsu_shanten(counts))
+    } else {
+        standard
+    }
+}
+
+struct ShantenSearch {
+    hand: TileCounts,
+    melds_needed: u8,
+    remaining: u8,
+    best: u8,
+}
+
+impl ShantenSearch {
+    fn run(&mut self, start: usize, melds: u8, partials: u8, pair: bool) {
+        let value = 2 * melds + partials.min(self.melds_needed - melds) + pair as u8;
+        // No shape is worth more than two thirds of a point per tile
+        if value + 2 * self.remaining / 3 <= self.best || self.best > 2 * self.melds_needed {
+            return;
+        }
+        let Some(index) = (start..TILE_KINDS).find(|&i| self.hand[i] > 0) else {
+            self.best = value;
+            return;
+        };
+        if melds < self.melds_needed {
+            if self.hand[index] >= 3 {
+                self.take(index, &[3], |search| {
+                    search.run(index, melds + 1, partials, pair)
+                });
+            }
+            if can_start_sequence(index) {
+                self.take(index, &[1, 1, 1], |search| {
+                    search.run(index, melds + 1, partials, pair)
+                });
+            }
+        }
+        if self.hand[index] >= 2 {
+            if !pair {
+                self.take(index, &[2], |search| {
+                    search.run(index, melds, partials, true)
+                });
+            }
+            self.take(index, &[2], |search| {
+                search.run(index, melds, partials + 1, pair)
+            });
+        }
+        if is_number_index(index) && index % 9 <= 7 {
+            self.take(index, &[1, 1], |search| {
+                search.run(index, melds, partials + 1, pair)
+            });
+        }
+        if can_start_sequence(index) {
+            self.take(index, &[1, 0, 1], |search| {
+                search.run(index, melds, partials + 1, pair)
+            });
+        }
+        self.take(index, &[1], |search| {
+            search.run(index, melds, partials, pair)
+        });
+    }
+
+    // Removes `shape` starting at `index` if the hand holds it, runs `next` and puts the
+    // tiles back.
+    fn take(&mut self, index: usize, shape: &[u8], next: impl FnOnce(&mut Self)) {
+        let tiles = &self.hand[index..index + shape.len()];
+        if tiles
+            .iter()
+            .zip(shape)
+            .any(|(&held, &needed)| held < needed)
+        {
+            return;
+        }
+        for (count, &needed) in self.hand[index..].iter_mut().zip(shape) {
+            *count -= needed;
+        }
+        let taken: u8 = shape.iter().sum();
+        self.remaining -= taken;
+        next(self);
+        self.remaining += taken;
+        for (count, &needed) in self.hand[index..].iter_mut().zip(shape) {
+            *count += needed;
+        }
+    }
+}
+
+// Only tiles already held or next to a held number tile can complete a shape.
+fn touches_hand(counts: &TileCounts, index: usize) -> bool {
+    if counts[index] > 0 {
+        return true;
+    }
+    if !is_number_index(index) {
+        return false;
+    }
+    let position = index % 9;
+    (position > 0 && counts[index - 1] > 0) || (position < 8 && counts[index + 1] > 0)
+}
+
+/// Bit `i` is set when drawing tile kind `i` completes the hand. Kinds already held
+/// four times are never waits.
+pub fn wait_mask(counts: &TileCounts) -> u64 {
+    let mut hand = *counts;
+    let mut mask = 0;
+    for index in 0..TILE_KINDS {
+        if hand[index] >= 4 || !touches_hand(counts, index) {
+            continue;
+        }
+        hand[index] += 1;
+        if is_agari(&hand) {
+            mask |= 1 << index;
+        }
+        hand[index] -= 1;
+    }
+    mask
+}
+
+/// Tile kinds that complete a tenpai hand, in kind order.
+pub fn waits(counts: &TileCounts) -> Vec<MahjongTile> {
+    let mask = wait_mask(counts);
+    (0..TILE_KINDS)
+        .filter(|index| mask & (1 << index) != 0)
+        .map(index_to_tile)
+        .collect()
+}
+
+/// Tile kinds that would bring a hand of 3n+1 tiles closer to complete, with how many copies
+/// are still unseen when `visible` counts every tile in sight, the hand included.
+pub fn ukeire(counts: &TileCounts, visible: &TileCounts) -> Vec<(MahjongTile, u8)> {
+    let current = shanten(counts);
+    let mut hand = *counts;
+    let mut accepted = Vec::new();
+    for index in 0..TILE_KINDS {
+        if visible[index] >= 4 || hand[index] >= 4 {
+            continue;
+        }
+        hand[index] += 1;
+        if shanten(&hand) < current {
+            accepted.push((index_to_tile(index), 4 - visible[index]));
+        }
+        hand[index] -= 1;
+    }
+    accepted
+}
+
+/// Every distinct way to split a hand into melds and one pair, each sorted.
+pub fn decompositions(counts: &TileCounts) -> Vec<Vec<Block>> {
+    let mut results = Vec::new();
+    if tile_count(counts) % 3 != 2 {
+        return results;
+    }
+    let mut hand = *counts;
+    let mut blocks = [Block::Pair(0); 5];
+    for pair in 0..TILE_KINDS {
+        if hand[pair] < 2 {
+            continue;
+        }
+        hand[pair] -= 2;
+        blocks[0] = Block::Pair(pair);
+        collect_melds(&mut hand, 0, &mut blocks, 1, &mut results);
+        hand[pair] += 2;
+    }
+    for result in &mut results {
+        result.sort();
+    }
+    results.sort();
+    results
+}
+
+fn collect_melds(
+    hand: &mut TileCounts,
+    start: usize,
+    blocks: &mut [Block; 5],
+    depth: usize,
+    results: &mut Vec<Vec<Block>>,
+) {
+    let Some(index) = (start..TILE_KINDS).find(|&i| hand[i] > 0) else {
+        results.push(blocks[..depth].to_vec());
+        return;
+    };
+    if hand[index] >= 3 {
+        hand[index] -= 3;
+        blocks[depth] = Block::Triplet(index);
+        collect_melds(hand, index, blocks, depth + 1, results);
+        hand[index] += 3;
+    }
+    if can_start_sequence(index) && hand[index + 1] > 0 && hand[index + 2] > 0 {
+        for count in &mut hand[index..index + 3] {
+            *count -= 1;
+        }
+        blocks[depth] = Block::Sequence(index);
+        collect_melds(hand, index, blocks, depth + 1, results);
+        for count in &mut hand[index..index + 3] {
+            *count += 1;
+        }
+    }
+}
+
+/// The tiles left over after taking out as many melds and at most `max_pairs` pairs
+/// as possible.
+pub fn leftover_tiles(counts: &TileCounts, max_pairs: u8) -> TileCounts {
+    let mut search = LeftoverSearch {
+        hand: *counts,
+        leftover: [0; TILE_KINDS],
+        leftover_count: 0,
+        best: *counts,
+        best_count: tile_count(counts),
+    };
+    search.run(0, max_pairs);
+    search.best
+}
+
+struct LeftoverSearch {
+    hand: TileCounts,
+    leftover: TileCounts,
+    leftover_count: usize,
+    best: TileCounts,
+    best_count: usize,
+}
+
+impl LeftoverSearch {
+    fn run(&mut self, start: usize, pairs_left: u8) {
+        if self.leftover_count >= self.best_count {
+            return;
+        }
+        let Some(index) = (start..TILE_KINDS).find(|&i| self.hand[i] > 0) else {
+            self.best = self.leftover;
+            self.best_count = self.leftover_count;
+            return;
+        };
+        if self.hand[index] >= 3 {
+            self.hand[index] -= 3;
+            self.run(index, pairs_left);
+            self.hand[index] += 3;
+        }
+        if can_start_sequence(index) && self.hand[index + 1] > 0 && self.hand[index + 2] > 0 {
+            for count in &mut self.hand[index..index + 3] {
+                *count -= 1;
+            }
+            self.run(index, pairs_left);
+            for count in &mut self.hand[index..index + 3] {
+                *count += 1;
+            }
+        }
+        if self.hand[index] >= 2 && pairs_left > 0 {
+            self.hand[index] -= 2;
+            self.run(index, pairs_left - 1);
+            self.hand[index] += 2;
+        }
+        self.hand[index] -= 1;
+        self.leftover[index] += 1;
+        self.leftover_count += 1;
+        self.run(index, pairs_left);
+        self.leftover_count -= 1;
+        self.leftover[index] -= 1;
+        self.hand[index] += 1;
+    }
+}
+
+#[test]
+fn test_index_round_trip() {
+    for index in 0..TILE_KINDS {
+        assert_eq!(tile_index(&index_to_tile(index)), index);
+    }
+    let tile = MahjongTile {
+        suit: Suit::Kaze,
+        value: 4,
+        is_dora: false,
+        is_red: false,
+    };
+    assert_eq!(tile_index(&tile), 30);
+}
+
+#[test]
+fn test_agari_counts() {
+    // 111222333m456p77s
+    let mut counts = [0; TILE_KINDS];
+    counts[0] = 3;
+    counts[1] = 3;
+    counts[2] = 3;
+    counts[12] = 1;
+    counts[13] = 1;
+    counts[14] = 1;
+    counts[24] = 2;
+    assert!(is_agari(&counts));
+    assert_eq!(decompositions(&counts).len(), 2);
+
+    counts[24] = 1;
+    counts[25] = 1;
+    assert!(!is_agari(&counts));
+    assert!(decompositions(&counts).is_empty());
+}
+
+#[test]
+fn test_wait_mask_matches_waits() {
+    // 1112345678999m, the nine-sided wait
+    let mut counts = [0; TILE_KINDS];
+    counts[0] = 3;
+    for count in counts.iter_mut().take(8).skip(1) {
+        *count = 1;
+    }
+    counts[8] = 3;
+    assert_eq!(wait_mask(&counts), 0b1_1111_1111);
+    assert_eq!(waits(&counts).len(), 9);
+}
+
+#[test]
+fn test_leftover_tiles() {
+    // 123m55p789s1z2z3z + 4z: only the honours are left over
+    let mut counts = [0; TILE_KINDS];
+    for index in [0, 1, 2, 24, 25, 26, 27, 28, 29, 30] {
+        counts[index] = 1;
+    }
+    counts[13] = 2;
+    let leftover = leftover_tiles(&counts, 2);
+    assert_eq!(from_counts(&leftover).len(), 4);
+    assert!(from_counts(&leftover)
+        .iter()
+        .all(|tile| tile.suit == Suit::Kaze));
+}
+
+#[test]
+fn test_shanten_counts() {
+    // 123m456p789s1122z is tenpai, and drawing 2z completes it
+    let mut counts = [0; TILE_KINDS];
+    for index in [0, 1, 2, 12, 13, 14, 24, 25, 26] {
+        counts[index] = 1;
+    }
+    counts[27] = 2;
+    counts[28] = 2;
+    assert_eq!(shanten(&counts), 0);
+    counts[28] = 3;
+    assert_eq!(shanten(&counts), -1);
+
+    // 1199m1199p1199s1z: seven pairs is one tile away, four melds are far off
+    let mut counts = [0; TILE_KINDS];
+    for index in [0, 8, 9, 17, 18, 26] {
+        counts[index] = 2;
+    }
+    counts[27] = 1;
+    assert_eq!(chiitoitsu_shanten(&counts), 0);
+    assert_eq!(standard_shanten(&counts), 3);
+    assert_eq!(shanten(&counts), 0);
+}
+
+#[test]
+fn test_ukeire() {
+    let hand = to_counts(&crate::types::notation::parse_tiles("23m456p789s11z135s").unwrap());
+    let mut visible = hand;
+    visible[tile_index(&"1m".parse().unwrap())] += 3;
+    let accepted: Vec<(String, u8)> = ukeire(&hand, &visible)
+        .iter()
+        .map(|(tile, unseen)| (tile.to_string(), *unseen))
+        .collect();
+    assert_eq!(shanten(&hand), 1);
+    assert_eq!(accepted[0], ("1m".to_string(), 1));
+    assert!(accepted.contains(&("4m".to_string(), 4)));
+    assert!(accepted.contains(&("2s".to_string(), 4)));
+    assert!(accepted.contains(&("4s".to_string(), 4)));
+    assert!(!accepted.iter().any(|(tile, _)| tile == "9m"));
+}
@@ -0,0 +1,201 @@
+use super::mahjong_tile::*;
+use super::tile_counts::*;
+
+/// The shape a hand waited in for the tile that completed it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum WaitShape {
+    Ryanmen,
+    Kanchan,
+    Penchan,
+    Shanpon,
+    Tanki,
+}
+
+impl WaitShape {
+    /// Fu the wait itself is worth.
+    pub fn fu(&self) -> u8 {
+        match self {
+            WaitShape::Ryanmen | WaitShape::Shanpon => 0,
+            WaitShape::Kanchan | WaitShape::Penchan | WaitShape::Tanki => 2,
+        }
+    }
+}
+
+/// One reading of a winning hand: the shape the winning tile completed and the
+/// decomposition it completed it in. Seven pairs readings hold seven `Block::Pair`s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShapeMatch {
+    pub shape: WaitShape,
+    pub decomposition: Vec<Block>,
+}
+
+/// A tile a tenpai hand waits on, and every shape it completes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TileWait {
+    pub tile: MahjongTile,
+    pub shapes: Vec<ShapeMatch>,
+}
+
+impl TileWait {
+    pub fn has_shape(&self, shape: WaitShape) -> bool {
+        self.shapes
+            .iter()
+            .any(|shape_match| shape_match.shape == shape)
+    }
+
+    /// Most wait fu any reading of this win can claim.
+    pub fn max_fu(&self) -> u8 {
+        self.shapes.iter().map(|m| m.shape.fu()).max().unwrap_or(0)
+    }
+}
+
+/// Every shape the tile at `winning_index` completes in a complete hand, across all of
+/// its decompositions.
+pub fn win_shapes(counts: &TileCounts, winning_index: usize) -> Vec<ShapeMatch> {
+    let mut matches = Vec::new();
+    for decomposition in decompositions(counts) {
+        let mut shapes: Vec<WaitShape> = decomposition
+            .iter()
+            .filter_map(|block| block_shape(block, winning_index))
+            .collect();
+        shapes.sort();
+        shapes.dedup();
+        for shape in shapes {
+            matches.push(ShapeMatch {
+                shape,
+                decomposition: decomposition.clone(),
+            });
+        }
+    }
+    if is_chiitoitsu_agari(counts) {
+        matches.push(ShapeMatch {
+            shape: WaitShape::Tanki,
+            decomposition: (0..TILE_KINDS)
+                .filter(|&index| counts[index] == 2)
+                .map(Block::Pair)
+                .collect(),
+        });
+    }
+    matches
+}
+
+fn block_shape(block: &Block, winning_index: usize) -> Option<WaitShape> {
+    match *block {
+        Block::Pair(index) if index == winning_index => Some(WaitShape::Tanki),
+        Block::Triplet(index) if index == winning_index => Some(WaitShape::Shanpon),
+        Block::Sequence(start) if (start..start + 3).contains(&winning_index) => {
+            let position = start % 9;
+            Some(match winning_index - start {
+                1 => WaitShape::Kanchan,
+                0 if position == 6 => WaitShape::Penchan,
+                2 if position == 0 => WaitShape::Penchan,
+                _ => WaitShape::Ryanmen,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Each tile a tenpai hand waits on together with every shape that tile completes.
+pub fn classify_waits(counts: &TileCounts) -> Vec<TileWait> {
+    let mask = wait_mask(counts);
+    let mut hand = *counts;
+    let mut waits = Vec::new();
+    for index in (0..TILE_KINDS).filter(|index| mask & (1 << index) != 0) {
+        hand[index] += 1;
+        waits.push(TileWait {
+            tile: index_to_tile(index),
+            shapes: win_shapes(&hand, index),
+        });
+        hand[index] -= 1;
+    }
+    waits
+}
+
+/// A wait on more than two tile kinds, like sanmen or an entotsu.
+pub fn is_multi_sided(waits: &[TileWait]) -> bool {
+    waits.len() > 2
+}
+
+#[cfg(test)]
+fn shapes_of(notation: &str) -> Vec<(String, Vec<WaitShape>)> {
+    let hand = super::notation::parse_tiles(notation).unwrap();
+    classify_waits(&to_counts(&hand))
+        .iter()
+        .map(|wait| {
+            let mut shapes: Vec<WaitShape> = wait.shapes.iter().map(|m| m.shape).collect();
+            shapes.dedup();
+            (wait.tile.to_string(), shapes)
+        })
+        .collect()
+}
+
+#[test]
+fn test_basic_wait_shapes() {
+    use WaitShape::*;
+    assert_eq!(
+        shapes_of("23m456p789s11z555z"),
+        vec![
+            ("1m".to_string(), vec![Ryanmen]),
+            ("4m".to_string(), vec![Ryanmen])
+        ]
+    );
+    assert_eq!(
+        shapes_of("13m456p789s11z555z"),
+        vec![("2m".to_string(), vec![Kanchan])]
+    );
+    assert_eq!(
+        shapes_of("89m456p789s11z555z"),
+        vec![("7m".to_string(), vec![Penchan])]
+    );
+    assert_eq!(
+        shapes_of("12m456p789s11z555z"),
+        vec![("3m".to_string(), vec![Penchan])]
+    );
+    assert_eq!(
+        shapes_of("55m456p789s11z555z"),
+        vec![
+            ("5m".to_string(), vec![Shanpon]),
+            ("1z".to_string(), vec![Shanpon])
+        ]
+    );
+    assert_eq!(
+        shapes_of("123m456p789s1z555z"),
+        vec![("1z".to_string(), vec![Tanki])]
+    );
+}
+
+#[test]
+fn test_complex_waits() {
+    use WaitShape::*;
+    // Nobetan: both ends are tanki waits
+    assert_eq!(
+        shapes_of("1234m456p789s555z"),
+        vec![
+            ("1m".to_string(), vec![Tanki]),
+            ("4m".to_string(), vec![Tanki])
+        ]
+    );
+    // 2m completes 11m+123m as a kanchan, 3m completes 111m+33m as a tanki
+    let waits = classify_waits(&to_counts(
+        &super::notation::parse_tiles("1113m456p789s555z").unwrap(),
+    ));
+    assert_eq!(waits.len(), 2);
+    assert!(waits[0].has_shape(Kanchan) && waits[0].tile.value == 2);
+    assert!(waits[1].has_shape(Tanki) && waits[1].tile.value == 3);
+    // Sanmen
+    let waits = classify_waits(&to_counts(
+        &super::notation::parse_tiles("23456m456p789s55z").unwrap(),
+    ));
+    assert!(is_multi_sided(&waits));
+    assert!(waits.iter().all(|wait| wait.has_shape(Ryanmen)));
+}
+
+#[test]
+fn test_chiitoitsu_wait() {
+    let hand = super::notation::parse_tiles("1199m1199p1199s1z").unwrap();
+    let waits = classify_waits(&to_counts(&hand));
+    assert_eq!(waits.len(), 1);
+    assert_eq!(waits[0].shapes[0].shape, WaitShape::Tanki);
+    assert_eq!(waits[0].shapes[0].decomposition.len(), 7);
+}
@@ -0,0 +1,167 @@
+use crate::scoring::score_hand as score;
+use crate::types::mahjong_tile::MahjongTile;
+use crate::types::notation::parse_tiles;
+use crate::types::tile_counts::{self, index_to_tile, is_agari, tile_index, to_counts};
+use crate::types::{Hand, HandScore, SeatWind};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+// The JavaScript API of the hand calculator, built with
+// `wasm-pack build --target web --no-default-features --features wasm`. Hands go in as MPSZ
+// notation, results come back as plain objects with tiles as strings like "5m" or "0p", and
+// bad input throws an Error.
+
+#[derive(Debug, PartialEq, Serialize)]
+struct ParsedHand {
+    closed: Vec<String>,
+    called: Vec<Vec<String>>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct Acceptance {
+    tile: String,
+    /// Copies not yet in sight
+    left: u8,
+}
+
+fn to_js<T: Serialize>(result: Result<T, String>) -> Result<JsValue, JsError> {
+    let value = result.map_err(|error| JsError::new(&error))?;
+    serde_wasm_bindgen::to_value(&value).map_err(|error| JsError::new(&error.to_string()))
+}
+
+fn tile_names(tiles: &[MahjongTile]) -> Vec<String> {
+    tiles.iter().map(|tile| tile.to_string()).collect()
+}
+
+/// Parses a hand and checks it holds `sizes` tiles.
+fn read_hand(notation: &str, sizes: &[usize]) -> Result<Hand, String> {
+    let hand: Hand = notation.parse().map_err(|error| format!("{}", error))?;
+    if !sizes.contains(&hand.tile_count()) {
+        let expected: Vec<String> = sizes.iter().map(|size| size.to_string()).collect();
+        return Err(format!(
+            "expected a hand of {} tiles, '{}' has {}",
+            expected.join(" or "),
+            notation,
+            hand.tile_count()
+        ));
+    }
+    Ok(hand)
+}
+
+fn hand_tiles(notation: &str) -> Result<ParsedHand, String> {
+    let hand: Hand = notation.parse().map_err(|error| format!("{}", error))?;
+    Ok(ParsedHand {
+        closed: tile_names(&hand.closed),
+        called: hand.called.iter().map(|meld| tile_names(meld)).collect(),
+    })
+}
+
+fn hand_shanten(notation: &str) -> Result<i8, String> {
+    let hand = read_hand(notation, &[13, 14])?;
+    Ok(tile_counts::shanten(&to_counts(&hand.closed)))
+}
+
+fn hand_waits(notation: &str) -> Result<Vec<String>, String> {
+    let hand = read_hand(notation, &[13])?;
+    Ok(tile_names(&tile_counts::waits(&to_counts(&hand.closed))))
+}
+
+fn hand_ukeire(notation: &str, visible: &str) -> Result<Vec<Acceptance>, String> {
+    let hand = read_hand(notation, &[13])?;
+    let counts = to_counts(&hand.closed);
+    let mut seen = to_counts(&hand.called.concat());
+    for tile in parse_tiles(visible).map_err(|error| format!("{}", error))? {
+        seen[tile_index(&tile)] += 1;
+    }
+    for (index, count) in counts.iter().enumerate() {
+        seen[index] += count;
+        if seen[index] > 4 {
+            return Err(format!(
+                "more than four copies of {} in sight",
+                index_to_tile(index)
+            ));
+        }
+    }
+    Ok(tile_counts::ukeire(&counts, &seen)
+        .into_iter()
+        .map(|(tile, left)| Acceptance {
+            tile: tile.to_string(),
+            left,
+        })
+        .collect())
+}
+
+fn hand_score(notation: &str, tsumo: bool, seat_wind: &str) -> Result<HandScore, String> {
+    let hand = read_hand(notation, &[14])?;
+    let seat_wind: SeatWind = seat_wind.parse()?;
+    if !is_agari(&to_counts(&hand.closed)) {
+        return Err(format!("'{}' is not a complete hand", notation));
+    }
+    Ok(score(&hand.closed, &hand.called, tsumo, &seat_wind))
+}
+
+/// `{closed, called}`: the closed tiles and each called meld.
+#[wasm_bindgen(js_name = parseHand)]
+pub fn parse_hand(hand: &str) -> Result<JsValue, JsError> {
+    to_js(hand_tiles(hand))
+}
+
+/// Tiles away from tenpai for a hand of 13 or 14 tiles; tenpai is 0 and complete -1.
+#[wasm_bindgen]
+pub fn shanten(hand: &str) -> Result<i8, JsError> {
+    hand_shanten(hand).map_err(|error| JsError::new(&error))
+}
+
+/// Tiles that complete a hand of 13 tiles.
+#[wasm_bindgen]
+pub fn waits(hand: &str) -> Result<JsValue, JsError> {
+    to_js(hand_waits(hand))
+}
+
+/// `[{tile, left}]`: tiles that bring a hand of 13 tiles closer to complete, and how many
+/// are left once the hand and the `visible` tiles are counted.
+#[wasm_bindgen]
+pub fn ukeire(hand: &str, visible: &str) -> Result<JsValue, JsError> {
+    to_js(hand_ukeire(hand, visible))
+}
+
+/// `{han, fu, yaku: [{name, han}], base_points}` of a complete hand, the last closed tile
+/// being the one won on, with its called melds in brackets counted toward yaku and fu. The
+/// seat wind is `east`, `south`, `west` or `north`.
+#[wasm_bindgen(js_name = scoreHand)]
+pub fn score_hand(hand: &str, tsumo: bool, seat_wind: &str) -> Result<JsValue, JsError> {
+    to_js(hand_score(hand, tsumo, seat_wind))
+}
+
+#[test]
+fn test_wasm_hand_functions() {
+    assert_eq!(
+        hand_tiles("12m[555z]"),
+        Ok(ParsedHand {
+            closed: vec!["1m".to_string(), "2m".to_string()],
+            called: vec![vec!["5z".to_string(); 3]],
+        })
+    );
+    assert_eq!(hand_shanten("123m456p789s1135z"), Ok(1));
+    assert_eq!(
+        hand_waits("123m456p789s1122z"),
+        Ok(vec!["1z".to_string(), "2z".to_string()])
+    );
+    let accepted = hand_ukeire("23m456p789s11z135s", "1m").unwrap();
+    assert_eq!(
+        accepted[0],
+        Acceptance {
+            tile: "1m".to_string(),
+            left: 3
+        }
+    );
+    let score = hand_score("123m456p789s11222z", true, "east").unwrap();
+    assert_eq!((score.han, score.fu), (1, 40));
+    let called = hand_score("234m678s55p[777z][666s]", true, "east").unwrap();
+    assert_eq!(called.yaku[0].name, "yakuhai");
+    assert_eq!((called.han, called.fu), (1, 30));
+    assert!(hand_score("123m456p789s11223z", true, "east").is_err());
+    assert!(hand_score("123m456p789s11222z", true, "centre").is_err());
+    assert!(hand_waits("123m").is_err());
+    assert!(hand_ukeire("23m456p789s11z135s", "2222m").is_err());
+}
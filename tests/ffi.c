@@ -0,0 +1,82 @@
+/* Scores a hand through the generated header and the cdylib, the way C and C++ callers
+ * will. Exits non-zero and says why when an answer is off. */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "mahjong_simulator.h"
+
+static int failures = 0;
+
+static void expect(int ok, const char *what) {
+  if (!ok) {
+    fprintf(stderr, "failed: %s\n", what);
+    failures++;
+  }
+}
+
+int main(void) {
+  expect(mj_abi_version() == MJ_ABI_VERSION, "the library matches the header");
+
+  MjRuleset rules = mj_ruleset_default();
+  MjWinContext context = {
+      .tsumo = false,
+      .seat_wind = 1,
+      .dora_indicators = "4m",
+      .honba = 1,
+      .riichi_sticks = 1,
+  };
+  MjScore score;
+  /* Tanyao, a dora from the 5m and one from the red 5p, won on a discard by South */
+  MjStatus status = mj_score_hand("234567m067p345s22s", NULL, 0, &context, &rules, &score);
+  expect(status == MJ_STATUS_OK, mj_status_message(status));
+  expect(score.han == 3 && score.fu == 40, "3 han 40 fu");
+  expect(score.payments.ron == 5200 + 300, "5200 from the discarder plus a honba");
+  expect(score.payments.total == 5200 + 300 + 1000, "the riichi stick goes to the winner");
+  int found = 0;
+  for (unsigned int i = 0; i < score.yaku_count; i++) {
+    found |= strcmp(score.yaku[i].name, "tanyao") == 0 && score.yaku[i].han == 1;
+  }
+  expect(found, "tanyao is listed");
+
+  const char *melds[] = {"345p", "666s"};
+  context.tsumo = true;
+  context.seat_wind = 0;
+  context.dora_indicators = NULL;
+  context.honba = 0;
+  context.riichi_sticks = 0;
+  status = mj_score_hand("234m678s55p", melds, 2, &context, &rules, &score);
+  expect(status == MJ_STATUS_OK, mj_status_message(status));
+  expect(score.han == 1, "open tanyao");
+  expect(score.payments.tsumo_dealer == 0 &&
+             score.payments.total == 3 * score.payments.tsumo_non_dealer,
+         "everyone else pays the dealer's tsumo");
+
+  /* The chun pon is yakuhai and a dora once for each 6z indicator */
+  const char *dragon_melds[] = {"777z", "666s"};
+  context.dora_indicators = "6z6z";
+  status = mj_score_hand("234m678s55p", dragon_melds, 2, &context, &rules, &score);
+  expect(status == MJ_STATUS_OK, mj_status_message(status));
+  expect(score.han == 1 + 6 && score.fu == 30, "yakuhai and six dora, 30 fu");
+
+  int8_t shanten = 0;
+  expect(mj_shanten("123m456p789s1135z", NULL, 0, &shanten) == MJ_STATUS_OK && shanten == 1,
+         "one away from tenpai");
+  uint64_t waits = 0;
+  expect(mj_waits("123m456p789s1122z", NULL, 0, &waits) == MJ_STATUS_OK &&
+             waits == ((1ULL << 27) | (1ULL << 28)),
+         "waits on East and South");
+
+  expect(mj_score_hand("123m456p789s11223z", NULL, 0, &context, &rules, &score) ==
+             MJ_STATUS_NOT_COMPLETE,
+         "an incomplete hand isn't scored");
+  expect(mj_waits("123m", NULL, 0, &waits) == MJ_STATUS_WRONG_TILE_COUNT,
+         "waits need 13 tiles");
+  expect(mj_shanten("12x", NULL, 0, &shanten) == MJ_STATUS_INVALID_NOTATION, "bad notation");
+  expect(mj_shanten(NULL, NULL, 0, &shanten) == MJ_STATUS_NULL_ARGUMENT, "NULL hand");
+
+  if (failures == 0) {
+    printf("ok\n");
+  }
+  return failures == 0 ? 0 : 1;
+}
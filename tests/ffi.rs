@@ -0,0 +1,243 @@
+// The C interface, called through its C ABI: from Rust through the exported functions, and
+// from a C program built against the generated header and the cdylib.
+
+use mahjong_simulator::ffi::*;
+use std::ffi::{c_char, CStr, CString};
+use std::path::PathBuf;
+use std::process::Command;
+use std::ptr;
+
+fn no_context() -> MjWinContext {
+    MjWinContext {
+        tsumo: true,
+        seat_wind: 0,
+        dora_indicators: ptr::null(),
+        honba: 0,
+        riichi_sticks: 0,
+    }
+}
+
+fn score(hand: &str, melds: &[&str], context: &MjWinContext, rules: &MjRuleset) -> MjScore {
+    let hand = CString::new(hand).unwrap();
+    let melds: Vec<CString> = melds
+        .iter()
+        .map(|meld| CString::new(*meld).unwrap())
+        .collect();
+    let pointers: Vec<*const c_char> = melds.iter().map(|meld| meld.as_ptr()).collect();
+    let mut score = std::mem::MaybeUninit::<MjScore>::uninit();
+    let status = unsafe {
+        mj_score_hand(
+            hand.as_ptr(),
+            pointers.as_ptr(),
+            pointers.len(),
+            context,
+            rules,
+            score.as_mut_ptr(),
+        )
+    };
+    assert_eq!(status, MjStatus::Ok);
+    unsafe { score.assume_init() }
+}
+
+fn yaku_names(score: &MjScore) -> Vec<String> {
+    score.yaku[..score.yaku_count as usize]
+        .iter()
+        .map(|yaku| {
+            unsafe { CStr::from_ptr(yaku.name.as_ptr()) }
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect()
+}
+
+#[test]
+fn test_score_through_the_c_abi() {
+    let rules = mj_ruleset_default();
+    let closed = score("123m456p789s11222z", &[], &no_context(), &rules);
+    assert_eq!((closed.han, closed.fu, closed.base_points), (1, 40, 320));
+    assert_eq!(yaku_names(&closed), ["menzen tsumo"]);
+    // Dealer tsumo: 700 from each of the others
+    assert_eq!(closed.payments.tsumo_non_dealer, 700);
+    assert_eq!(closed.payments.total, 2100);
+
+    // The red 5p only counts when the rules have red fives
+    let indicator = CString::new("4m").unwrap();
+    let context = MjWinContext {
+        tsumo: false,
+        seat_wind: 2,
+        dora_indicators: indicator.as_ptr(),
+        ..no_context()
+    };
+    let red = score("234567m067p345s22s", &[], &context, &rules);
+    assert_eq!(
+        yaku_names(&red),
+        ["dora", "aka dora", "tanyao"],
+        "{:?}",
+        red.yaku_count
+    );
+    let plain = MjRuleset {
+        red_fives: false,
+        ..rules
+    };
+    assert_eq!(score("234567m067p345s22s", &[], &context, &plain).han, 2);
+
+    let open = score("234m678s55p", &["345p", "666s"], &no_context(), &rules);
+    assert_eq!(yaku_names(&open), ["tanyao"]);
+
+    // Called tiles count toward yaku and fu: an open chun pon is 4 fu, 666s 2, the tanki
+    // and the tsumo 2 each
+    let dragon = score("234m678s55p", &["777z", "666s"], &no_context(), &rules);
+    assert_eq!(yaku_names(&dragon), ["yakuhai"]);
+    assert_eq!((dragon.han, dragon.fu), (1, 30));
+    let terminal = score("234m678s55p", &["345p", "999s"], &no_context(), &rules);
+    assert!(!yaku_names(&terminal).contains(&"tanyao".to_string()));
+
+    // A 5m in a meld is a dora once for each of the two 4m indicators
+    let indicators = CString::new("4m4m").unwrap();
+    let context = MjWinContext {
+        dora_indicators: indicators.as_ptr(),
+        ..no_context()
+    };
+    let doubled = score("234m678s55p", &["456m", "666s"], &context, &rules);
+    assert_eq!(yaku_names(&doubled), ["dora", "tanyao"]);
+    assert_eq!(doubled.yaku[0].han, 2);
+
+    // One suit with honors is honitsu, and chinitsu a han less when open
+    let half = score("234m567m789m44z333z", &[], &no_context(), &rules);
+    assert_eq!(yaku_names(&half), ["menzen tsumo", "honitsu"]);
+    assert_eq!(half.han, 4);
+    let full = score("22334455p777p", &["888p"], &no_context(), &rules);
+    assert_eq!(yaku_names(&full), ["tanyao", "chinitsu"]);
+    assert_eq!(full.han, 6);
+
+    // 16 han of chinitsu, ryanpeikou, dora and the rest is a yakuman unless kazoe yakuman
+    // is off
+    let indicator = CString::new("1p1p").unwrap();
+    let context = MjWinContext {
+        dora_indicators: indicator.as_ptr(),
+        ..no_context()
+    };
+    let counted = score("22334455667788p", &[], &context, &rules);
+    assert_eq!(
+        yaku_names(&counted),
+        [
+            "menzen tsumo",
+            "dora",
+            "tanyao",
+            "chinitsu",
+            "ryanpeikou",
+            "pinfu"
+        ]
+    );
+    assert_eq!(counted.han, 16);
+    assert_eq!(counted.base_points, 8000);
+    let capped = MjRuleset {
+        kazoe_yakuman: false,
+        ..rules
+    };
+    assert_eq!(
+        score("22334455667788p", &[], &context, &capped).base_points,
+        6000
+    );
+}
+
+#[test]
+fn test_errors_through_the_c_abi() {
+    let mut shanten = 0i8;
+    let mut waits = 0u64;
+    let hand = CString::new("123m456p789s1122z").unwrap();
+    unsafe {
+        assert_eq!(
+            mj_waits(hand.as_ptr(), ptr::null(), 0, &mut waits),
+            MjStatus::Ok
+        );
+        assert_eq!(waits, (1 << 27) | (1 << 28));
+        assert_eq!(
+            mj_shanten(hand.as_ptr(), ptr::null(), 1, &mut shanten),
+            MjStatus::NullArgument
+        );
+        assert_eq!(
+            mj_shanten(hand.as_ptr(), ptr::null(), 0, ptr::null_mut()),
+            MjStatus::NullArgument
+        );
+        let bracket = CString::new("55]5z[").unwrap();
+        let melds = [bracket.as_ptr()];
+        assert_eq!(
+            mj_shanten(hand.as_ptr(), melds.as_ptr(), 1, &mut shanten),
+            MjStatus::InvalidNotation
+        );
+        let context = MjWinContext {
+            seat_wind: 4,
+            ..no_context()
+        };
+        let won = CString::new("123m456p789s11222z").unwrap();
+        let mut score = std::mem::MaybeUninit::<MjScore>::uninit();
+        assert_eq!(
+            mj_score_hand(
+                won.as_ptr(),
+                ptr::null(),
+                0,
+                &context,
+                &mj_ruleset_default(),
+                score.as_mut_ptr()
+            ),
+            MjStatus::InvalidSeatWind
+        );
+        let message = CStr::from_ptr(mj_status_message(MjStatus::InvalidSeatWind));
+        assert_eq!(message.to_str(), Ok("the seat wind is not 0 to 3"));
+    }
+}
+
+/// Builds the cdylib with the `ffi` feature on its own, since the one in `target/debug` is
+/// whatever the last build of the crate left there, and returns its directory.
+fn build_library() -> PathBuf {
+    let target = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("ffi-library");
+    let built = Command::new(env!("CARGO"))
+        .args([
+            "build",
+            "--lib",
+            "--no-default-features",
+            "--features",
+            "ffi",
+        ])
+        .arg("--manifest-path")
+        .arg(format!("{}/Cargo.toml", env!("CARGO_MANIFEST_DIR")))
+        .arg("--target-dir")
+        .arg(&target)
+        .status()
+        .expect("cargo runs");
+    assert!(built.success());
+    target.join("debug")
+}
+
+#[test]
+#[cfg(unix)]
+fn test_c_program() {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let library = build_library();
+    let program = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("ffi-test");
+    let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let built = Command::new(compiler)
+        .args(["-std=c99", "-Wall", "-Werror", "-o"])
+        .arg(&program)
+        .arg(format!("{}/tests/ffi.c", root))
+        .arg(format!("-I{}/include", root))
+        .arg(format!("-L{}", library.display()))
+        .arg(format!("-Wl,-rpath,{}", library.display()))
+        .arg("-lmahjong_simulator")
+        .status()
+        .expect("a C compiler is installed");
+    assert!(built.success());
+    // cargo test puts target/debug first on the library path, ahead of the rpath
+    let output = Command::new(&program)
+        .env_remove("LD_LIBRARY_PATH")
+        .env_remove("DYLD_LIBRARY_PATH")
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\n");
+}